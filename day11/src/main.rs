@@ -1,7 +1,7 @@
 mod paint_robot;
-mod intcode;
 
 use intcode::{IntCode, IntCodeState};
+use intcode::input::Callback;
 use paint_robot::PaintRobot;
 
 const FILE_NAME: &str = "src/day11.txt";
@@ -19,20 +19,16 @@ fn main() {
 }
 
 fn paint(robot: &mut PaintRobot) {
-    let mut program = IntCode::file_to_program(&FILE_NAME);
+    let mut program = IntCode::file_to_program(FILE_NAME).unwrap();
     let mut paint = true;
     loop {
-        match &program.run_slice() {
-            IntCodeState::NeedInput => program.add_input(robot.get_color_here()),
-            IntCodeState::Output(data) => {
-                if paint { 
-                    robot.paint_here(*data);
-                } else {
-                    robot.turn_and_move(*data);
-                }
-                paint = !paint;
-            },
-            IntCodeState::Done => return
+        // The camera is asked for the color under the robot whenever the program reads it
+        let state = program.run_slice_from(&mut Callback(|| Some(robot.get_color_here()))).unwrap();
+        match state {
+            IntCodeState::Output(data) if paint => robot.paint_here(data),
+            IntCodeState::Output(data) => robot.turn_and_move(data),
+            _ => return
         }
+        paint = !paint;
     }
-}
\ No newline at end of file
+}
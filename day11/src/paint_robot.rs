@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::cmp;
 
+use intcode::Cell;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Point {
     pub x: isize,
@@ -22,7 +24,7 @@ pub struct PaintRobot {
     position: Point,
     dx: isize,
     dy: isize, 
-    panel_info: HashMap<Point, isize>
+    panel_info: HashMap<Point, Cell>
 }
 
 impl PaintRobot {
@@ -44,7 +46,7 @@ impl PaintRobot {
         self.dx = 0; self.dy = 1;
     }
 
-    fn turn_robot(&mut self, turn: isize) {
+    fn turn_robot(&mut self, turn: Cell) {
         match turn {
             0 => self.turn_left(),
             1 => self.turn_right(),
@@ -57,20 +59,20 @@ impl PaintRobot {
         self.position.y += self.dy;
     }
 
-    pub fn turn_and_move(&mut self, direction: isize) {
+    pub fn turn_and_move(&mut self, direction: Cell) {
         self.turn_robot(direction);
         self.move_robot();
     }
 
-    pub fn paint_here(&mut self, color: isize) {
+    pub fn paint_here(&mut self, color: Cell) {
         self.panel_info.entry(self.position).and_modify(|panel| *panel = color).or_insert(color);
     }
 
-    fn get_color_at(&self, position: Point) -> isize {
+    fn get_color_at(&self, position: Point) -> Cell {
         *self.panel_info.get(&position).unwrap_or(&0)
     }
 
-    pub fn get_color_here(&self) -> isize {
+    pub fn get_color_here(&self) -> Cell {
         self.get_color_at(self.position)
     }
 
@@ -81,7 +83,7 @@ impl PaintRobot {
     fn get_min_max_coord(&self) -> (Point, Point) {
         let mut min = Point::new(0, 0);
         let mut max = Point::new(0, 0);
-        for (p, _) in &self.panel_info {
+        for p in self.panel_info.keys() {
             min.x = cmp::min(min.x, p.x);
             min.y = cmp::min(min.y, p.y);
             max.x = cmp::max(max.x, p.x);
@@ -1,14 +1,13 @@
-pub mod intcode;
 use intcode::*;
+use intcode::symbolic::{Symbolic, Target};
 
-const EXPECTED: usize = 19690720;
+const EXPECTED: Cell = 19690720;
 
 fn main() {
     let input_file = "src/day2.txt";
-    let original = IntCode::new(&input_file);
+    let original = IntCode::file_to_program(input_file).unwrap();
 
-    let mut p = original.clone();
-    let result = p.run_patched_program(12, 2);
+    let result = run_patched_program(&original, 12, 2);
     println!("Result is {}", result);
 
     let (noun, verb) = find_verb_noun(&original);
@@ -16,13 +15,20 @@ fn main() {
     println!("Noun {}, verb {} gives code {}", noun, verb, 100 * noun + verb);
 }
 
-fn find_verb_noun(program: &IntCode) -> (usize, usize) {
-    for v in 0..=99 {
-        for n in 0..=99 {
-            let mut p = program.clone();
-            if p.run_patched_program(n, v) == EXPECTED { return (n, v); }
-        }
-    }
+fn run_patched_program(program: &IntCode, noun: Cell, verb: Cell) -> Cell {
+    let mut p = program.clone();
+    p.poke(1, noun);
+    p.poke(2, verb);
+    p.run_program().unwrap();
+    p.peek(0)
+}
 
-    (0, 0)
+fn find_verb_noun(program: &IntCode) -> (Cell, Cell) {
+    let mut symbolic = Symbolic::new(program);
+    let noun = symbolic.cell(1, 0..=99);
+    let verb = symbolic.cell(2, 0..=99);
+    match symbolic.solve(&Target::Cell(0, EXPECTED)).unwrap() {
+        Some(values) => (values[noun], values[verb]),
+        None => (0, 0)
+    }
 }
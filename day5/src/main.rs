@@ -1,16 +1,20 @@
-pub mod intcode;
 use intcode::*;
 
 fn main() {
     let input_file = "src/day5.txt";
-    let original = IntCode::new(&input_file);
+    let original = IntCode::file_to_program(input_file).unwrap();
 
     println!("Part 1 - test AC");
-    let output = original.clone().run_program(1);
+    let output = run_with_input(&original, 1);
     println!("{:?}", output);
 
     println!("\nPart 2 - test thermal radiator");
-    let output = original.clone().run_program(5);
+    let output = run_with_input(&original, 5);
     println!("{:?}", output);
 }
 
+fn run_with_input(program: &IntCode, input: Cell) -> Vec<Cell> {
+    let mut p = program.clone();
+    p.add_input(input);
+    p.run_program().unwrap()
+}
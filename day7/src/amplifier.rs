@@ -0,0 +1,124 @@
+use intcode::*;
+use intcode::channels::{channel, Executor, Node};
+
+fn permutations(list: Vec<Cell>, pointer: usize, acc: &mut Vec<Vec<Cell>>) {
+    if pointer == list.len() {
+        acc.push(list);
+        return;
+    }
+    for i in pointer..list.len() {
+        let mut permutation = list.clone();
+        permutation[pointer] = list[i];
+        permutation[i] = list[pointer];
+        permutations(permutation, pointer + 1, acc);
+    }
+}
+
+fn amplifier_output(program: &IntCode, sequence: Vec<Cell>) -> Cell {
+    let mut output = 0;
+    for phase in sequence {
+        let mut p = program.clone();
+        p.add_input(phase);
+        p.add_input(output);
+        output = p.run_program().unwrap()[0];
+    }
+    output
+}
+
+pub fn max_amplifier_output(program: &IntCode) -> Cell {
+    let mut max = -1;
+    let mut perm = Vec::new();
+    permutations(vec![0, 1, 2, 3, 4], 0, &mut perm);
+    for sequence in perm.drain(..) {
+        let result = amplifier_output(program, sequence);
+        if result > max { max = result }
+    }
+    max
+}
+
+fn amplifier_output_with_feedback(program: &IntCode, sequence: Vec<Cell>) -> Cell {
+    let (senders, receivers): (Vec<_>, Vec<_>) = sequence.iter().map(|_| channel()).unzip();
+    for (sender, phase) in senders.iter().zip(&sequence) {
+        sender.send(*phase).unwrap();
+    }
+    senders[0].send(0).unwrap();
+
+    // Each amp feeds the next, the last one also feeds result so its final output can be read
+    let (result_sender, result) = channel();
+    let mut executor = Executor::new();
+    for (i, receiver) in receivers.into_iter().enumerate() {
+        let mut amp = Node::new(program.clone(), receiver);
+        amp.connect(senders[(i + 1) % senders.len()].clone());
+        if i == senders.len() - 1 { amp.connect(result_sender.clone()); }
+        executor.add(amp);
+    }
+    executor.run().unwrap();
+    result.try_iter().last().unwrap()
+}
+
+pub fn max_feedback_amplifier_output(program: &IntCode) -> Cell {
+    let mut max = -1;
+    let mut perm = Vec::new();
+    permutations(vec![5, 6, 7, 8, 9], 0, &mut perm);
+    for sequence in perm.drain(..) {
+        let result = amplifier_output_with_feedback(program, sequence);
+        if result > max { max = result }
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_from_part2_sample1_should_give_139629729() {
+        let p = IntCode::string_to_program("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5").unwrap();
+        let actual = max_feedback_amplifier_output(&p);
+        assert_eq!(actual, 139629729);
+    }
+
+    #[test]
+    fn max_from_part2_given_phase_setting_should_give_139629729() {
+        let p = IntCode::string_to_program("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5").unwrap();
+        let actual = amplifier_output_with_feedback(&p, vec![9,8,7,6,5]);
+        assert_eq!(actual, 139629729);
+    }
+
+    #[test]
+    fn max_from_part2_sample2_should_give_18216() {
+        let p = IntCode::string_to_program("3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10").unwrap();
+        let actual = max_feedback_amplifier_output(&p);
+        assert_eq!(actual, 18216);
+    }
+
+    #[test]
+    fn all_permutations_of_list_of_length_3_is_6() {
+        let l = vec![0, 1, 2];
+        let mut acc = Vec::new();
+        permutations(l, 0, &mut acc);
+
+        assert_eq!(6, acc.len());
+    }
+
+    #[test]
+    fn max_thruster_for_sample_1_should_be_43210() {
+        let p = IntCode::string_to_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").unwrap();
+        let actual = max_amplifier_output(&p);
+        assert_eq!(actual, 43210);
+    }
+
+    #[test]
+    fn max_thruster_for_sample_2_should_be_54321() {
+        let p = IntCode::string_to_program("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0").unwrap();
+        let actual = max_amplifier_output(&p);
+        assert_eq!(actual, 54321);
+    }
+
+    #[test]
+    fn max_thruster_for_sample_3_should_be_65210() {
+        let p = IntCode::string_to_program("3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0").unwrap();
+        let actual = max_amplifier_output(&p);
+        assert_eq!(actual, 65210);
+    }
+}
@@ -1,9 +1,11 @@
-pub mod intcode;
-use intcode::*;
+mod amplifier;
+
+use intcode::IntCode;
+use amplifier::*;
 
 fn main() {
     let input_file = "src/day7.txt";
-    let original = IntCode::file_to_program(&input_file);
+    let original = IntCode::file_to_program(input_file).unwrap();
 
     let output = max_amplifier_output(&original);
     println!("Max thruster {}", output);
@@ -11,4 +13,3 @@ fn main() {
     let output = max_feedback_amplifier_output(&original);
     println!("Max thruster w feedback loop {}", output);
 }
-
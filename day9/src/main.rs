@@ -1,22 +1,18 @@
-pub mod intcode;
-pub mod old_intcode_tests;
-
 use intcode::*;
 
 fn main() {
     let input_file = "src/day9.txt";
-    let original = IntCode::file_to_program(&input_file);
+    let original = IntCode::file_to_program(input_file).unwrap();
 
     let mut program = original.clone();
     program.add_input(1);
-    let result = program.run_program();
+    let result = program.run_program().unwrap();
 
     println!("BOOST keycode for test run is {:?}", result);
 
     let mut program = original.clone();
     program.add_input(2);
-    let result = program.run_program();
+    let result = program.run_program().unwrap();
 
     println!("BOOST keycode for sensor boost mode is {:?}", result);
 }
-
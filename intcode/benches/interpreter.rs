@@ -0,0 +1,169 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use intcode::{Cell, IntCode, IntCodeError, IntCodeState};
+use intcode::compiler::{Compiled, CompiledMachine};
+
+/// Ways of running the current interpreter. Turning the decode cache off
+/// is not the interpreter as it was before the cache, so these compare the
+/// tiers with each other rather than against an older version.
+#[derive(Clone, Copy)]
+enum Tier {
+    DecodeCacheOff,
+    DecodeCacheOn,
+    Compiled
+}
+
+const TIERS: [(&str, Tier); 3] = [
+    ("decode cache off", Tier::DecodeCacheOff),
+    ("decode cache on", Tier::DecodeCacheOn),
+    ("compiled", Tier::Compiled)
+];
+
+fn load(file_name: &str) -> IntCode {
+    IntCode::file_to_program(file_name).unwrap()
+}
+
+/// Compiles the program for the compiled tier. The code is shared by every
+/// machine of a benchmark iteration, so its cost is included once.
+fn compile(program: &IntCode, tier: Tier) -> Option<Compiled> {
+    match tier {
+        Tier::Compiled => Some(Compiled::new(program)),
+        _ => None
+    }
+}
+
+/// A machine on any tier.
+enum Machine<'a> {
+    Interpreted(IntCode),
+    Compiled(CompiledMachine<'a>)
+}
+
+impl<'a> Machine<'a> {
+    fn new(program: &IntCode, tier: Tier, code: Option<&'a Compiled>, setup: impl FnOnce(&mut IntCode)) -> Self {
+        let mut p = program.clone();
+        p.set_decode_cache(matches!(tier, Tier::DecodeCacheOn));
+        setup(&mut p);
+        match code {
+            Some(code) => Machine::Compiled(CompiledMachine::new(code, p)),
+            None => Machine::Interpreted(p)
+        }
+    }
+
+    fn add_input(&mut self, value: Cell) {
+        match self {
+            Machine::Interpreted(p) => p.add_input(value),
+            Machine::Compiled(p) => p.add_input(value)
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        match self {
+            Machine::Interpreted(p) => p.is_done(),
+            Machine::Compiled(p) => p.is_done()
+        }
+    }
+
+    fn run_slice(&mut self) -> Result<IntCodeState, IntCodeError> {
+        match self {
+            Machine::Interpreted(p) => p.run_slice(),
+            Machine::Compiled(p) => p.run_slice()
+        }
+    }
+
+    fn run_program(&mut self) -> Vec<Cell> {
+        match self {
+            Machine::Interpreted(p) => p.run_program().unwrap(),
+            Machine::Compiled(p) => p.run_program().unwrap()
+        }
+    }
+
+    fn peek(&self, address: usize) -> Cell {
+        match self {
+            Machine::Interpreted(p) => p.peek(address),
+            Machine::Compiled(p) => p.machine().peek(address)
+        }
+    }
+}
+
+fn day2(program: &IntCode, tier: Tier) -> Vec<Cell> {
+    // A corner of the noun/verb search space, every run is a fresh machine
+    let code = compile(program, tier);
+    let mut sum = 0;
+    for noun in 0..10 {
+        for verb in 0..10 {
+            let mut p = Machine::new(program, tier, code.as_ref(), |p| {
+                p.poke(1, noun);
+                p.poke(2, verb);
+            });
+            p.run_program();
+            sum += p.peek(0);
+        }
+    }
+    vec![sum]
+}
+
+fn run_with_input(program: &IntCode, tier: Tier, input: Cell) -> Vec<Cell> {
+    let code = compile(program, tier);
+    Machine::new(program, tier, code.as_ref(), |p| p.add_input(input)).run_program()
+}
+
+fn day5(program: &IntCode, tier: Tier) -> Vec<Cell> {
+    run_with_input(program, tier, 5)
+}
+
+fn day7(program: &IntCode, tier: Tier) -> Vec<Cell> {
+    let code = compile(program, tier);
+    let mut max = 0;
+    for a in 5..10 {
+        for b in 5..10 {
+            for c in 5..10 {
+                for d in 5..10 {
+                    for e in 5..10 {
+                        let phases = [a, b, c, d, e];
+                        if (1..5).any(|i| phases[..i].contains(&phases[i])) { continue; }
+                        max = max.max(feedback_loop(program, tier, code.as_ref(), &phases));
+                    }
+                }
+            }
+        }
+    }
+    vec![max]
+}
+
+fn feedback_loop(program: &IntCode, tier: Tier, code: Option<&Compiled>, phases: &[Cell]) -> Cell {
+    let mut amps: Vec<Machine> = phases.iter().map(|phase| Machine::new(program, tier, code, |p| p.add_input(*phase))).collect();
+    let mut signal = 0;
+    while !amps[4].is_done() {
+        for amp in amps.iter_mut() {
+            amp.add_input(signal);
+            if let IntCodeState::Output(value) = amp.run_slice().unwrap() { signal = value; }
+        }
+    }
+    signal
+}
+
+fn day9(program: &IntCode, tier: Tier) -> Vec<Cell> {
+    run_with_input(program, tier, 2)
+}
+
+type Run = fn(&IntCode, Tier) -> Vec<Cell>;
+
+fn bench_programs(c: &mut Criterion) {
+    let programs: [(&str, IntCode, Run); 4] = [
+        ("day2", load("../day2/src/day2.txt"), day2),
+        ("day5", load("../day5/src/day5.txt"), day5),
+        ("day7", load("../day7/src/day7.txt"), day7),
+        ("day9", load("../day9/src/day9.txt"), day9)
+    ];
+    for (name, program, run) in programs.iter() {
+        let mut group = c.benchmark_group(*name);
+        group.sample_size(10);
+        for (tier_name, tier) in TIERS {
+            group.bench_with_input(BenchmarkId::from_parameter(tier_name), program, |b, p| b.iter(|| run(p, tier)));
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_programs);
+criterion_main!(benches);
@@ -0,0 +1,465 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::Number;
+use crate::disassembler::{decode_at, Statement};
+use crate::instruction::{Instruction, Mode, Opcode, Parameter};
+
+/// Where control can go after an instruction.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum Edge {
+    /// The next instruction in memory.
+    Next(usize),
+    /// A jump with a known target.
+    Jump(usize),
+    /// A jump that stores where to come back in `[rb+0]` first, the target
+    /// is None for calls through a computed address.
+    Call { target: Option<usize>, returns: usize },
+    /// A jump back through `[rb+0]`.
+    Return,
+    /// A jump whose target is only known at run time.
+    Computed
+}
+
+/// A straight run of instructions entered only at the top.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    pub start: usize,
+    /// Addresses of the instructions in the block.
+    pub instructions: Vec<usize>,
+    pub successors: Vec<Edge>
+}
+
+/// An instruction that writes into cells holding reachable code.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SelfModification {
+    pub address: usize,
+    pub target: usize
+}
+
+/// Static analysis of a program image: the instructions reachable from the
+/// entry points, their control-flow graph, and what they read and write.
+///
+/// A jump target, or jump condition, is resolved when it is an immediate
+/// or a position parameter reading a constant cell, one that no reachable
+/// instruction writes with a position parameter. Writes through relative
+/// parameters are assumed to stay out of the image, which holds for the
+/// stack-like use the puzzle programs make of them.
+///
+/// Calls are recognised by the usual calling convention: the caller writes
+/// the address after an unconditional jump to `[rb+0]` in the same block,
+/// and the callee returns by jumping through `[rb+0]`. Control is assumed
+/// to come back after every call.
+#[derive(Debug, Clone)]
+pub struct Analysis<C> {
+    program: Vec<C>,
+    instructions: BTreeMap<usize, Instruction>,
+    successors: BTreeMap<usize, Vec<Edge>>,
+    written: BTreeSet<usize>,
+    invalid: BTreeSet<usize>,
+    calls: BTreeMap<usize, usize>,
+    entries: Vec<usize>,
+    blocks: BTreeMap<usize, Block>
+}
+
+impl<C: Number> Analysis<C> {
+    /// Analyses the program from address 0.
+    pub fn new(program: &[C]) -> Self {
+        Analysis::with_entries(program, &[0])
+    }
+
+    pub fn with_entries(program: &[C], entries: &[usize]) -> Self {
+        let mut analysis = Analysis {
+            program: program.to_vec(),
+            instructions: BTreeMap::new(),
+            successors: BTreeMap::new(),
+            written: BTreeSet::new(),
+            invalid: BTreeSet::new(),
+            calls: BTreeMap::new(),
+            entries: entries.to_vec(),
+            blocks: BTreeMap::new()
+        };
+        // More reachable code can mean more writes, which can make cells that
+        // resolved jumps non-constant, and more calls, so explore until both
+        // settle
+        loop {
+            analysis.explore(entries);
+            let written: BTreeSet<usize> = analysis.instructions.iter()
+                .flat_map(|(address, instruction)| analysis.write_targets(*address, instruction))
+                .collect();
+            let calls: BTreeMap<usize, usize> = analysis.successors.iter()
+                .filter(|(_, edges)| matches!(edges.as_slice(), [Edge::Jump(_) | Edge::Call { .. } | Edge::Computed]))
+                .filter_map(|(address, _)| Some((*address, analysis.call_return(*address)?)))
+                .collect();
+            if written.is_subset(&analysis.written) && calls == analysis.calls { break; }
+            analysis.written.extend(written);
+            analysis.calls = calls;
+        }
+        analysis.build_blocks(entries);
+        analysis
+    }
+
+    fn parameter(&self, address: usize, index: usize) -> &C {
+        &self.program[address + 1 + index]
+    }
+
+    fn write_targets(&self, address: usize, instruction: &Instruction) -> Vec<usize> {
+        instruction.opcode.parameters().iter().enumerate()
+            .filter(|(i, parameter)| **parameter == Parameter::Write && instruction.modes[*i] == Mode::Position)
+            .filter_map(|(i, _)| self.parameter(address, i).to_i64())
+            .filter(|target| *target >= 0)
+            .map(|target| target as usize)
+            .collect()
+    }
+
+    /// The value of a parameter when it is known without running the program,
+    /// which also needs the parameter cell itself not to be patched.
+    fn constant(&self, address: usize, instruction: &Instruction, index: usize) -> Option<C> {
+        if self.written.contains(&(address + 1 + index)) { return None; }
+        let value = self.parameter(address, index);
+        match instruction.modes[index] {
+            Mode::Immediate => Some(value.clone()),
+            Mode::Position => {
+                let cell = value.to_i64().filter(|a| *a >= 0)? as usize;
+                if self.written.contains(&cell) { return None; }
+                Some(self.program.get(cell).cloned().unwrap_or_else(C::zero))
+            },
+            Mode::Relative => None
+        }
+    }
+
+    /// The return address a jump passes when it is a call: the fall-through
+    /// chain before it writes the address after it to `[rb+0]`.
+    fn call_return(&self, address: usize) -> Option<usize> {
+        let returns = address + self.instructions[&address].size();
+        let mut current = address;
+        loop {
+            let (previous, instruction) = self.instructions.range(..current).next_back()?;
+            if previous + instruction.size() != current || self.successors[previous] != [Edge::Next(current)] { return None; }
+            match instruction.opcode {
+                Opcode::AdjustRelativeBase => return None,
+                Opcode::Add | Opcode::Multiply if instruction.modes[2] == Mode::Relative && self.parameter(*previous, 2).is_zero() => {
+                    let a = self.constant(*previous, instruction, 0)?;
+                    let b = self.constant(*previous, instruction, 1)?;
+                    let value = match instruction.opcode {
+                        Opcode::Add => a.checked_add(&b)?,
+                        _ => a.checked_mul(&b)?
+                    };
+                    return Some(returns).filter(|r| value.to_i64() == Some(*r as i64));
+                },
+                _ if instruction.opcode.parameters().last() == Some(&Parameter::Write)
+                    && instruction.modes[instruction.size() - 2] == Mode::Relative
+                    && self.parameter(*previous, instruction.size() - 2).is_zero() => return None,
+                _ => current = *previous
+            }
+        }
+    }
+
+    fn edges(&self, address: usize, instruction: &Instruction) -> Vec<Edge> {
+        let next = Edge::Next(address + instruction.size());
+        match instruction.opcode {
+            Opcode::Halt => vec![],
+            Opcode::JumpNotZero | Opcode::JumpZero => {
+                let target = self.constant(address, instruction, 1)
+                    .and_then(|t| t.to_i64())
+                    .filter(|t| *t >= 0)
+                    .map(|t| t as usize);
+                let target = match (self.calls.get(&address), target) {
+                    (Some(returns), target) => Edge::Call { target, returns: *returns },
+                    (None, Some(target)) => Edge::Jump(target),
+                    _ if instruction.modes[1] == Mode::Relative && self.parameter(address, 1).is_zero() => Edge::Return,
+                    _ => Edge::Computed
+                };
+                let jumps = self.constant(address, instruction, 0)
+                    .map(|condition| condition.is_zero() == (instruction.opcode == Opcode::JumpZero));
+                match jumps {
+                    Some(true) => vec![target],
+                    Some(false) => vec![next],
+                    None => vec![next, target]
+                }
+            },
+            _ => vec![next]
+        }
+    }
+
+    fn explore(&mut self, entries: &[usize]) {
+        self.instructions.clear();
+        self.successors.clear();
+        self.invalid.clear();
+        let mut work: Vec<usize> = entries.to_vec();
+        while let Some(address) = work.pop() {
+            if self.instructions.contains_key(&address) || self.invalid.contains(&address) { continue; }
+            let instruction = match address < self.program.len() {
+                true => decode_at(&self.program, address),
+                false => None
+            };
+            let instruction = match instruction {
+                Some(instruction) => instruction,
+                None => {
+                    self.invalid.insert(address);
+                    continue;
+                }
+            };
+            let edges = self.edges(address, &instruction);
+            work.extend(edges.iter().flat_map(|edge| match edge {
+                Edge::Next(target) | Edge::Jump(target) => vec![*target],
+                Edge::Call { target, returns } => target.iter().copied().chain(Some(*returns)).collect(),
+                Edge::Return | Edge::Computed => vec![]
+            }));
+            self.instructions.insert(address, instruction);
+            self.successors.insert(address, edges);
+        }
+    }
+
+    /// Blocks start at entries, jump targets and instructions not reached
+    /// only by falling through from the one before.
+    fn build_blocks(&mut self, entries: &[usize]) {
+        let mut leaders: BTreeSet<usize> = entries.iter().copied().collect();
+        leaders.extend(self.successors.values().flatten().filter_map(|edge| match edge {
+            Edge::Jump(target) | Edge::Call { target: Some(target), .. } => Some(*target),
+            _ => None
+        }));
+        for address in self.instructions.keys() {
+            let previous = self.instructions.range(..address).next_back();
+            let falls_through = previous.is_some_and(|(p, i)| p + i.size() == *address && self.successors[p] == [Edge::Next(*address)]);
+            if !falls_through { leaders.insert(*address); }
+        }
+        let mut blocks = BTreeMap::new();
+        for start in leaders.iter().copied().filter(|a| self.instructions.contains_key(a)) {
+            let mut instructions = vec![start];
+            let mut address = start;
+            loop {
+                match self.successors[&address].as_slice() {
+                    [Edge::Next(next)] if !leaders.contains(next) && self.instructions.contains_key(next) => {
+                        address = *next;
+                        instructions.push(address);
+                    },
+                    _ => break
+                }
+            }
+            let successors = self.successors[&address].clone();
+            blocks.insert(start, Block { start, instructions, successors });
+        }
+        self.blocks = blocks;
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    pub fn block(&self, start: usize) -> Option<&Block> {
+        self.blocks.get(&start)
+    }
+
+    /// Entry points of the program and of every called function.
+    pub fn functions(&self) -> BTreeSet<usize> {
+        let targets = self.successors.values().flatten().filter_map(|edge| match edge {
+            Edge::Call { target, .. } => *target,
+            _ => None
+        });
+        self.entries.iter().copied().chain(targets).filter(|a| self.is_reachable(*a)).collect()
+    }
+
+    pub(crate) fn program(&self) -> &[C] {
+        &self.program
+    }
+
+    pub(crate) fn instruction(&self, address: usize) -> &Instruction {
+        &self.instructions[&address]
+    }
+
+    /// Whether a reachable instruction may write the cell through a position
+    /// parameter.
+    pub fn is_written(&self, address: usize) -> bool {
+        self.written.contains(&address)
+    }
+
+    /// Addresses of every reachable instruction.
+    pub fn reachable(&self) -> impl Iterator<Item = usize> + '_ {
+        self.instructions.keys().copied()
+    }
+
+    pub fn is_reachable(&self, address: usize) -> bool {
+        self.instructions.contains_key(&address)
+    }
+
+    /// Addresses control can reach that do not hold a valid instruction,
+    /// where the program would fail if it gets there.
+    pub fn invalid(&self) -> impl Iterator<Item = usize> + '_ {
+        self.invalid.iter().copied()
+    }
+
+    /// Cells of the image that are not part of any reachable instruction.
+    pub fn unreachable(&self) -> Vec<Range<usize>> {
+        let code = self.code_cells();
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for address in (0..self.program.len()).filter(|a| !code.contains(a)) {
+            match ranges.last_mut() {
+                Some(range) if range.end == address => range.end += 1,
+                _ => ranges.push(address..address + 1)
+            }
+        }
+        ranges
+    }
+
+    fn code_cells(&self) -> BTreeSet<usize> {
+        self.instructions.iter().flat_map(|(address, instruction)| *address..address + instruction.size()).collect()
+    }
+
+    /// Writes that may change reachable code, through position parameters,
+    /// including reachable cells that only decode once patched.
+    pub fn self_modifications(&self) -> Vec<SelfModification> {
+        let mut code = self.code_cells();
+        code.extend(&self.invalid);
+        self.instructions.iter()
+            .flat_map(|(address, instruction)| self.write_targets(*address, instruction).into_iter()
+                .filter(|target| code.contains(target))
+                .map(move |target| SelfModification { address: *address, target }))
+            .collect()
+    }
+
+    /// Reachable INPUT and OUTPUT instructions.
+    pub fn io_sites(&self) -> Vec<Statement<C>> {
+        self.instructions.keys().copied()
+            .filter(|address| matches!(self.instructions[address].opcode, Opcode::Input | Opcode::Output))
+            .map(|address| self.statement(address))
+            .collect()
+    }
+
+    pub(crate) fn statement(&self, address: usize) -> Statement<C> {
+        let instruction = self.instructions[&address].clone();
+        let parameters = self.program[address + 1..address + instruction.size()].to_vec();
+        Statement::Instruction { address, instruction, parameters }
+    }
+
+    /// The control-flow graph in Graphviz DOT format, one node per block.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph intcode {\n    node [shape=box, fontname=\"monospace\"];\n");
+        let mut computed = false;
+        for block in self.blocks.values() {
+            let label: String = block.instructions.iter()
+                .map(|a| self.statement(*a).to_string().replace('"', "\\\"") + "\\l")
+                .collect();
+            writeln!(dot, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+            for edge in &block.successors {
+                match edge {
+                    Edge::Next(target) => writeln!(dot, "    b{} -> {};", block.start, self.node(*target)).unwrap(),
+                    Edge::Jump(target) => writeln!(dot, "    b{} -> {} [label=\"jump\"];", block.start, self.node(*target)).unwrap(),
+                    Edge::Call { target, returns } => {
+                        match target {
+                            Some(target) => writeln!(dot, "    b{} -> {} [label=\"call\"];", block.start, self.node(*target)).unwrap(),
+                            None => {
+                                computed = true;
+                                writeln!(dot, "    b{} -> computed [style=dashed, label=\"call\"];", block.start).unwrap()
+                            }
+                        }
+                        writeln!(dot, "    b{} -> {} [style=dotted];", block.start, self.node(*returns)).unwrap()
+                    },
+                    Edge::Return => (),
+                    Edge::Computed => {
+                        computed = true;
+                        writeln!(dot, "    b{} -> computed [style=dashed];", block.start).unwrap()
+                    }
+                }
+            }
+        }
+        for address in &self.invalid {
+            writeln!(dot, "    invalid{} [shape=octagon, label=\"invalid {}\"];", address, address).unwrap();
+        }
+        if computed { dot += "    computed [shape=ellipse, label=\"computed jump\"];\n"; }
+        dot + "}\n"
+    }
+
+    fn node(&self, address: usize) -> String {
+        match self.invalid.contains(&address) {
+            true => format!("invalid{}", address),
+            false => format!("b{}", address)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntCode;
+
+    fn analyse(program: &str) -> Analysis<i64> {
+        Analysis::new(IntCode::string_to_program(program).unwrap().memory())
+    }
+
+    #[test]
+    fn conditional_jump_should_split_blocks() {
+        let analysis = analyse("3,12,1005,12,9,104,0,99,0,104,1,99,0");
+        let blocks: Vec<&Block> = analysis.blocks().collect();
+
+        assert_eq!(vec![
+            &Block { start: 0, instructions: vec![0, 2], successors: vec![Edge::Next(5), Edge::Jump(9)] },
+            &Block { start: 5, instructions: vec![5, 7], successors: vec![] },
+            &Block { start: 9, instructions: vec![9, 11], successors: vec![] }
+        ], blocks);
+        assert_eq!(vec![8..9, 12..13], analysis.unreachable());
+        assert_eq!(3, analysis.io_sites().len());
+        assert!(analysis.self_modifications().is_empty());
+    }
+
+    #[test]
+    fn constant_condition_should_only_follow_the_taken_edge() {
+        let analysis = analyse("1106,0,4,99,104,1,99");
+
+        assert_eq!(vec![0, 4, 6], analysis.reachable().collect::<Vec<_>>());
+        assert_eq!(vec![Edge::Jump(4)], analysis.blocks().next().unwrap().successors);
+        assert_eq!(vec![3..4], analysis.unreachable());
+    }
+
+    #[test]
+    fn jump_through_written_cell_should_be_computed() {
+        let analysis = analyse("3,100,105,1,100,99");
+
+        assert_eq!(vec![Edge::Computed], analysis.blocks().next().unwrap().successors);
+        assert!(!analysis.is_reachable(5));
+        assert!(analysis.to_dot().contains("b0 -> computed [style=dashed];"));
+    }
+
+    #[test]
+    fn writes_into_code_should_be_detected() {
+        let analysis = analyse("104,7,1005,17,16,1101,0,4,0,1101,1,0,17,1106,0,0,99,0");
+
+        assert_eq!(vec![SelfModification { address: 5, target: 0 }], analysis.self_modifications());
+        assert_eq!(vec![Edge::Next(5), Edge::Jump(16)], analysis.successors[&2]);
+    }
+
+    #[test]
+    fn calls_should_come_back_after_the_jump() {
+        let analysis = analyse("109,100,21101,0,7,1,21101,0,13,0,1105,1,16,204,1,99,109,2,22101,1,-1,-1,109,-2,2105,1,0");
+
+        assert_eq!(vec![0, 16], analysis.functions().into_iter().collect::<Vec<_>>());
+        assert_eq!(vec![Edge::Call { target: Some(16), returns: 13 }], analysis.block(0).unwrap().successors);
+        assert_eq!(vec![Edge::Return], analysis.block(16).unwrap().successors);
+        assert!(analysis.unreachable().is_empty());
+    }
+
+    #[test]
+    fn jumping_into_data_should_be_invalid() {
+        let analysis = analyse("1105,1,3,0");
+
+        assert_eq!(vec![3], analysis.invalid().collect::<Vec<_>>());
+        assert!(analysis.to_dot().contains("b0 -> invalid3 [label=\"jump\"];"));
+    }
+
+    #[test]
+    fn robot_brain_should_have_its_io_sites_found() {
+        let program = IntCode::file_to_program("../day11/src/day11.txt").unwrap();
+        let analysis = Analysis::new(program.memory());
+        let sites = analysis.io_sites();
+        let inputs = sites.iter()
+            .filter(|s| matches!(s, Statement::Instruction { instruction, .. } if instruction.opcode == Opcode::Input))
+            .count();
+
+        assert_eq!((19, 40), (inputs, sites.len() - inputs));
+        assert!(analysis.invalid().next().is_none());
+        let dot = analysis.to_dot();
+        assert!(dot.starts_with("digraph intcode {\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
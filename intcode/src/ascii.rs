@@ -0,0 +1,167 @@
+use std::io::{BufRead, Write};
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number};
+
+/// Decoded program output.
+#[derive(Debug, PartialEq, Clone)]
+pub enum AsciiOutput<C = Cell> {
+    /// A line of text without its newline. Text still pending when the
+    /// program stops, like a prompt, is reported as a line too.
+    Line(String),
+    /// A value outside 0..=127, which cannot be text.
+    Value(C)
+}
+
+/// Encodes a line of text as input cells, terminated by a newline. Text
+/// that is not ASCII cannot be encoded.
+pub fn encode_line<C: Number>(line: &str) -> Result<Vec<C>, IntCodeError<C>> {
+    if let Some((offset, character)) = line.char_indices().find(|(_, c)| !c.is_ascii()) {
+        return Err(IntCodeError::NonAscii { offset, character });
+    }
+    Ok(line.bytes().chain(Some(b'\n')).map(|b| C::from_i64(b as i64)).collect())
+}
+
+/// Talks to a program that reads and writes ASCII text.
+#[derive(Debug, Clone)]
+pub struct Ascii<C = Cell> {
+    machine: IntCode<C>,
+    text: String
+}
+
+impl<C: Number> Ascii<C> {
+    pub fn new(machine: IntCode<C>) -> Self {
+        Ascii { machine, text: String::new() }
+    }
+
+    pub fn machine(&self) -> &IntCode<C> {
+        &self.machine
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.machine.is_done()
+    }
+
+    /// Queues the line as input, nothing is queued if it is not ASCII.
+    pub fn send_line(&mut self, line: &str) -> Result<(), IntCodeError<C>> {
+        encode_line(line)?.into_iter().for_each(|c| self.machine.add_input(c));
+        Ok(())
+    }
+
+    fn flush_text(&mut self, output: &mut Vec<AsciiOutput<C>>) {
+        if !self.text.is_empty() {
+            output.push(AsciiOutput::Line(std::mem::take(&mut self.text)));
+        }
+    }
+
+    /// Runs until the program halts or wants input, returning what it wrote.
+    pub fn run(&mut self) -> Result<Vec<AsciiOutput<C>>, IntCodeError<C>> {
+        let mut output = Vec::new();
+        while let IntCodeState::Output(value) = self.machine.run_slice()? {
+            match value.to_i64().filter(|v| (0..=127).contains(v)) {
+                Some(10) => output.push(AsciiOutput::Line(std::mem::take(&mut self.text))),
+                Some(c) => self.text.push(c as u8 as char),
+                None => {
+                    self.flush_text(&mut output);
+                    output.push(AsciiOutput::Value(value));
+                }
+            }
+        }
+        self.flush_text(&mut output);
+        Ok(output)
+    }
+
+    /// Lets a human converse with the program: its output is written to
+    /// output, and each time it wants input a line is read from input.
+    /// Returns when the program halts or input ends. A line that is not
+    /// ASCII is reported and read again.
+    pub fn interact(&mut self, mut input: impl BufRead, mut output: impl Write) -> Result<(), IntCodeError<C>> {
+        let io_error = |e: std::io::Error| IntCodeError::Io(e.to_string());
+        loop {
+            for item in self.run()? {
+                match item {
+                    AsciiOutput::Line(line) => writeln!(output, "{}", line),
+                    AsciiOutput::Value(value) => writeln!(output, "[{}]", value)
+                }.map_err(io_error)?;
+            }
+            output.flush().map_err(io_error)?;
+            if self.is_done() { return Ok(()); }
+
+            loop {
+                let mut line = String::new();
+                if input.read_line(&mut line).map_err(io_error)? == 0 { return Ok(()); }
+                match self.send_line(line.trim_end_matches(['\r', '\n'])) {
+                    Err(e @ IntCodeError::NonAscii { .. }) => writeln!(output, "{}", e).map_err(io_error)?,
+                    result => break result?
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+
+    // Echoes every character and follows each newline with the value 1000
+    const ECHO: &str = "
+        loop:   INPUT [c]
+                OUTPUT [c]
+                STORE_EQUAL [c], #10 -> [t]
+                JUMP_ZERO [t], #loop
+                OUTPUT #1000
+                JUMP_ZERO #0, #loop
+        c:      .data 0
+        t:      .data 0
+    ";
+
+    fn echo() -> Ascii {
+        Ascii::new(IntCode::new(assembler::assemble(ECHO).unwrap()))
+    }
+
+    #[test]
+    fn encode_line_should_append_newline() {
+        assert_eq!(Ok(vec![72, 105, 10]), encode_line::<Cell>("Hi"));
+    }
+
+    #[test]
+    fn large_values_should_be_reported_separately() {
+        let mut a = echo();
+        a.send_line("hello").unwrap();
+
+        assert_eq!(vec![AsciiOutput::Line("hello".to_string()), AsciiOutput::Value(1000)], a.run().unwrap());
+    }
+
+    #[test]
+    fn unterminated_text_should_be_flushed_when_the_program_stops() {
+        let mut a = Ascii::new(IntCode::string_to_program("104,72,104,105,104,-1,104,33,99").unwrap());
+
+        assert_eq!(vec![
+            AsciiOutput::Line("Hi".to_string()),
+            AsciiOutput::Value(-1),
+            AsciiOutput::Line("!".to_string())
+        ], a.run().unwrap());
+        assert!(a.is_done());
+    }
+
+    #[test]
+    fn interact_should_converse_line_by_line() {
+        let mut a = echo();
+        let mut output = Vec::new();
+        a.interact("one\ntwo\n".as_bytes(), &mut output).unwrap();
+
+        assert_eq!("one\n[1000]\ntwo\n[1000]\n", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn non_ascii_text_should_be_rejected() {
+        assert_eq!(Err(IntCodeError::NonAscii { offset: 2, character: 'é' }), encode_line::<Cell>("caé"));
+        let mut a = echo();
+        assert!(a.send_line("é").is_err());
+        assert!(a.machine().pending_input().is_empty());
+
+        let mut output = Vec::new();
+        a.interact("café\ncafe\n".as_bytes(), &mut output).unwrap();
+        assert_eq!("Non-ASCII character 'é' at offset 3 in input text\ncafe\n[1000]\n", String::from_utf8(output).unwrap());
+    }
+}
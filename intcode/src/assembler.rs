@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::Number;
+use crate::extension::InstructionSet;
+use crate::instruction::{Instruction, Mode, Opcode, Parameter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssemblerError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssemblerError {}
+
+/// A number or a label, optionally offset by a number, e.g. `loop+2`.
+#[derive(Debug, Clone)]
+enum Term<C> {
+    Number(C),
+    Label(String, i64)
+}
+
+#[derive(Debug)]
+enum Statement<C> {
+    Instruction(Opcode, Vec<(Mode, Term<C>)>),
+    Data(Vec<Term<C>>)
+}
+
+struct Line<C> {
+    number: usize,
+    statement: Statement<C>
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_term<C: Number>(text: &str) -> Result<Term<C>, String> {
+    let text = text.trim();
+    if let Ok(value) = text.parse() { return Ok(Term::Number(value)); }
+    let (name, offset) = match text.find(['+', '-']) {
+        Some(i) => {
+            let offset: i64 = text[i + 1..].trim().parse().map_err(|_| format!("Invalid offset in '{}'", text))?;
+            (text[..i].trim(), if &text[i..=i] == "-" { -offset } else { offset })
+        },
+        None => (text, 0)
+    };
+    if !is_label(name) { return Err(format!("Invalid value '{}'", text)); }
+    Ok(Term::Label(name.to_string(), offset))
+}
+
+fn parse_parameter<C: Number>(text: &str) -> Result<(Mode, Term<C>), String> {
+    let text = text.trim();
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok((Mode::Immediate, parse_term(value)?));
+    }
+    let inner = text.strip_prefix('[').and_then(|t| t.strip_suffix(']'))
+        .ok_or_else(|| format!("Invalid parameter '{}', use [addr], #imm or [rb+off]", text))?
+        .trim();
+    match inner.strip_prefix("rb") {
+        Some(offset) if offset.trim().is_empty() => Ok((Mode::Relative, Term::Number(C::zero()))),
+        Some(offset) if offset.trim_start().starts_with('+') => Ok((Mode::Relative, parse_term(&offset.trim_start()[1..])?)),
+        Some(offset) if offset.trim_start().starts_with('-') => {
+            let negated = format!("-{}", offset.trim_start()[1..].trim());
+            Ok((Mode::Relative, parse_term(&negated)?))
+        },
+        _ => Ok((Mode::Position, parse_term(inner)?))
+    }
+}
+
+/// Strips a comment and any leading `label:` or `address:` prefixes.
+/// Returns the labels, the address prefix if any, and the remaining text.
+fn split_prefixes(text: &str) -> (Vec<String>, Option<usize>, &str) {
+    let mut text = match text.find(';') {
+        Some(i) => &text[..i],
+        None => text
+    }.trim();
+    let mut labels = Vec::new();
+    let mut address = None;
+    while let Some(i) = text.find(':') {
+        let prefix = text[..i].trim();
+        if let Ok(a) = prefix.parse() {
+            address = Some(a);
+        } else if is_label(prefix) {
+            labels.push(prefix.to_string());
+        } else {
+            break;
+        }
+        text = text[i + 1..].trim();
+    }
+    (labels, address, text)
+}
+
+fn parse_statement<C: Number>(text: &str, instructions: &InstructionSet<C>) -> Result<Option<Statement<C>>, String> {
+    if text.is_empty() { return Ok(None); }
+    let (word, rest) = match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim()),
+        None => (text, "")
+    };
+    let operands: Vec<&str> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',').flat_map(|s| s.split("->")).collect()
+    };
+    if word.eq_ignore_ascii_case(".data") {
+        let values = operands.iter().map(|o| parse_term(o)).collect::<Result<Vec<_>, _>>()?;
+        if values.is_empty() { return Err(".data needs at least one value".to_string()); }
+        return Ok(Some(Statement::Data(values)));
+    }
+    let opcode = instructions.from_mnemonic(word).ok_or_else(|| format!("Unknown mnemonic '{}'", word))?;
+    let kinds = opcode.parameters();
+    if operands.len() != kinds.len() {
+        return Err(format!("{} takes {} parameter(s), found {}", opcode, kinds.len(), operands.len()));
+    }
+    let mut parameters = Vec::new();
+    for (operand, kind) in operands.iter().zip(kinds) {
+        let (mode, term) = parse_parameter(operand)?;
+        if *kind == Parameter::Write && mode == Mode::Immediate {
+            return Err(format!("{} cannot write to immediate parameter '{}'", opcode, operand.trim()));
+        }
+        parameters.push((mode, term));
+    }
+    Ok(Some(Statement::Instruction(opcode, parameters)))
+}
+
+fn resolve<C: Number>(term: &Term<C>, labels: &HashMap<String, usize>) -> Result<C, String> {
+    match term {
+        Term::Number(value) => Ok(value.clone()),
+        Term::Label(name, offset) => {
+            let address = labels.get(name).ok_or_else(|| format!("Undefined label '{}'", name))?;
+            (*address as i64).checked_add(*offset).map(C::from_i64)
+                .ok_or_else(|| format!("Offset out of range in '{}{:+}'", name, offset))
+        }
+    }
+}
+
+/// Assembles source text into a program image.
+///
+/// Each line holds at most one statement, optionally preceded by `label:`
+/// definitions, and anything after `;` is a comment. A statement is either
+/// a mnemonic as printed by the disassembler with `[addr]`, `#imm` or
+/// `[rb+off]` parameters, or `.data` followed by comma separated values.
+/// Numbers and labels can be used interchangeably, labels may carry an
+/// offset like `table+3`. A numeric `addr:` prefix, as in disassembler
+/// listings, is checked against the address the statement assembles to.
+pub fn assemble<C: Number>(source: &str) -> Result<Vec<C>, AssemblerError> {
+    assemble_with(source, &InstructionSet::new())
+}
+
+/// Like assemble, accepting the mnemonics of the extensions in instructions.
+pub fn assemble_with<C: Number>(source: &str, instructions: &InstructionSet<C>) -> Result<Vec<C>, AssemblerError> {
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+    let mut address = 0;
+    for (i, text) in source.lines().enumerate() {
+        let number = i + 1;
+        let error = |message| AssemblerError { line: number, message };
+        let (names, expected, text) = split_prefixes(text);
+        if let Some(expected) = expected {
+            if expected != address {
+                return Err(error(format!("Statement is at address {}, not {}", address, expected)));
+            }
+        }
+        for name in names {
+            if labels.insert(name.clone(), address).is_some() {
+                return Err(error(format!("Label '{}' defined twice", name)));
+            }
+        }
+        if let Some(statement) = parse_statement(text, instructions).map_err(error)? {
+            address += match &statement {
+                Statement::Instruction(opcode, _) => opcode.size(),
+                Statement::Data(values) => values.len()
+            };
+            lines.push(Line { number, statement });
+        }
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for line in lines {
+        let number = line.number;
+        let error = |message| AssemblerError { line: number, message };
+        match line.statement {
+            Statement::Instruction(opcode, parameters) => {
+                let modes = parameters.iter().map(|(mode, _)| *mode).collect();
+                program.push(C::from_i64(Instruction { opcode, modes }.encode()));
+                for (_, term) in parameters {
+                    program.push(resolve(&term, &labels).map_err(error)?);
+                }
+            },
+            Statement::Data(values) => for term in values {
+                program.push(resolve(&term, &labels).map_err(error)?);
+            }
+        }
+    }
+    Ok(program)
+}
+
+/// Assembles source text into the comma separated format read by string_to_program.
+pub fn assemble_to_string<C: Number>(source: &str) -> Result<String, AssemblerError> {
+    let program: Vec<C> = assemble(source)?;
+    Ok(program.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cell, IntCode};
+    use crate::disassembler;
+
+    fn assemble_cells(source: &str) -> Result<Vec<Cell>, AssemblerError> {
+        assemble(source)
+    }
+
+    #[test]
+    fn should_assemble_all_parameter_modes() {
+        let actual = assemble_cells("ADD [9], #10 -> [rb+3]\nOUTPUT [rb-1]\nINPUT [rb - 2]\nHALT").unwrap();
+
+        assert_eq!(vec![21001, 9, 10, 3, 204, -1, 203, -2, 99], actual);
+    }
+
+    #[test]
+    fn should_resolve_labels_forward_and_backward() {
+        let source = "
+            ; count down from the input value
+                    INPUT [counter]
+            loop:   OUTPUT [counter]
+                    ADD [counter], #-1 -> [counter]
+                    JUMP_NOT_ZERO [counter], #loop
+                    HALT
+            counter: .data 0
+        ";
+        let actual = assemble_to_string::<Cell>(source).unwrap();
+        assert_eq!("3,12,4,12,1001,12,-1,12,1005,12,2,99,0", actual);
+
+        let mut p = IntCode::string_to_program(&actual).unwrap();
+        p.add_input(3);
+        assert_eq!(vec![3, 2, 1], p.run_program().unwrap());
+    }
+
+    #[test]
+    fn should_support_label_offsets_in_data() {
+        let actual = assemble_cells("table: .data 7, table+1, table-1, end\nend: HALT").unwrap();
+
+        assert_eq!(vec![7, 1, -1, 4, 99], actual);
+    }
+
+    #[test]
+    fn label_offsets_out_of_range_should_be_an_error() {
+        let actual = assemble_cells(".data 0\nx: .data x+9223372036854775807");
+
+        assert_eq!(Err(AssemblerError { line: 2, message: "Offset out of range in 'x+9223372036854775807'".to_string() }), actual);
+    }
+
+    #[test]
+    fn should_accept_disassembler_address_prefixes() {
+        let actual = assemble_cells("    0: ADJUST          #1\n    2: HALT").unwrap();
+
+        assert_eq!(vec![109, 1, 99], actual);
+    }
+
+    #[test]
+    fn wrong_address_prefix_should_be_an_error() {
+        let actual = assemble_cells("0: HALT\n2: HALT");
+
+        assert_eq!(Err(AssemblerError { line: 2, message: "Statement is at address 1, not 2".to_string() }), actual);
+    }
+
+    #[test]
+    fn errors_should_carry_the_line_number() {
+        assert_eq!(3, assemble_cells("HALT\n\nJUMP [1], #2").unwrap_err().line);
+        assert_eq!(1, assemble_cells("ADD #1, #2 -> #3").unwrap_err().line);
+        assert_eq!(1, assemble_cells("OUTPUT [nowhere]").unwrap_err().line);
+        assert_eq!(2, assemble_cells("a: HALT\na: HALT").unwrap_err().line);
+        assert_eq!(1, assemble_cells("ADD #1, #2").unwrap_err().line);
+        assert_eq!(1, assemble_cells("OUTPUT 5").unwrap_err().line);
+    }
+
+    #[test]
+    fn mnemonics_should_be_case_insensitive() {
+        assert_eq!(vec![104, 1, 99], assemble_cells("output #1\nhalt").unwrap());
+    }
+
+    fn round_trip(file_name: &str) {
+        let original = IntCode::file_to_program(file_name).unwrap().memory().to_vec();
+        let listing = disassembler::listing(&original);
+        let actual = assemble_cells(&listing).unwrap();
+
+        assert_eq!(original, actual);
+    }
+
+    #[test]
+    fn disassemble_then_assemble_should_reproduce_day_programs() {
+        round_trip("../day2/src/day2.txt");
+        round_trip("../day5/src/day5.txt");
+        round_trip("../day7/src/day7.txt");
+        round_trip("../day9/src/day9.txt");
+        round_trip("../day11/src/day11.txt");
+    }
+}
@@ -0,0 +1,50 @@
+use std::env;
+use std::process;
+
+use intcode::IntCode;
+use intcode::analysis::{Analysis, Edge};
+
+const USAGE: &str = "Usage: intcode-analyze <program file> [--dot]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (file_name, dot) = match args.as_slice() {
+        [file_name] => (file_name, false),
+        [file_name, option] if option == "--dot" => (file_name, true),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let program = IntCode::file_to_program(file_name).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let analysis = Analysis::new(program.memory());
+    if dot {
+        print!("{}", analysis.to_dot());
+        return;
+    }
+
+    let blocks: Vec<_> = analysis.blocks().collect();
+    println!("{} reachable instructions in {} blocks", analysis.reachable().count(), blocks.len());
+    let functions: Vec<String> = analysis.functions().iter().map(|a| a.to_string()).collect();
+    println!("Functions at: {}", functions.join(", "));
+    let unreachable: Vec<String> = analysis.unreachable().iter().map(|r| format!("{}..{}", r.start, r.end)).collect();
+    println!("Unreachable cells: {}", unreachable.join(", "));
+    let computed: Vec<String> = blocks.iter()
+        .filter(|b| b.successors.contains(&Edge::Computed))
+        .map(|b| b.instructions.last().unwrap().to_string())
+        .collect();
+    println!("Computed jumps at: {}", computed.join(", "));
+    let invalid: Vec<String> = analysis.invalid().map(|a| a.to_string()).collect();
+    println!("Invalid instructions reachable at: {}", invalid.join(", "));
+    println!("Self-modifying writes:");
+    for write in analysis.self_modifications() {
+        println!("{:5}: writes code cell {}", write.address, write.target);
+    }
+    println!("I/O sites:");
+    for site in analysis.io_sites() {
+        println!("{}", site);
+    }
+}
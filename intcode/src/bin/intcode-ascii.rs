@@ -0,0 +1,25 @@
+use std::env;
+use std::io;
+use std::process;
+
+use intcode::IntCode;
+use intcode::ascii::Ascii;
+
+fn main() {
+    let file_name = match env::args().nth(1) {
+        Some(name) => name,
+        None => {
+            eprintln!("Usage: intcode-ascii <program file>");
+            process::exit(2);
+        }
+    };
+    let program = IntCode::file_to_program(&file_name).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let stdin = io::stdin();
+    if let Err(e) = Ascii::new(program).interact(stdin.lock(), io::stdout()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
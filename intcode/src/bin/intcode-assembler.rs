@@ -0,0 +1,27 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use intcode::Cell;
+use intcode::assembler;
+
+fn main() {
+    let file_name = match env::args().nth(1) {
+        Some(name) => name,
+        None => {
+            eprintln!("Usage: intcode-assembler <source file>");
+            process::exit(2);
+        }
+    };
+    let source = fs::read_to_string(&file_name).unwrap_or_else(|e| {
+        eprintln!("{}: {}", file_name, e);
+        process::exit(1);
+    });
+    match assembler::assemble_to_string::<Cell>(&source) {
+        Ok(program) => println!("{}", program),
+        Err(e) => {
+            eprintln!("{}: {}", file_name, e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use intcode::IntCode;
+use intcode::debugger::{Command, Debugger};
+
+fn main() {
+    let file_name = match env::args().nth(1) {
+        Some(name) => name,
+        None => {
+            eprintln!("Usage: intcode-debugger <program file>");
+            process::exit(2);
+        }
+    };
+    let program = IntCode::file_to_program(&file_name).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    let mut debugger = Debugger::new(program);
+    let stdin = io::stdin();
+    let mut last_command = None;
+    println!("Debugging {}, type help for commands", file_name);
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 { break; }
+
+        // An empty line repeats the previous command, handy for stepping
+        let command = if line.trim().is_empty() {
+            match &last_command {
+                Some(command) => Ok(Command::clone(command)),
+                None => continue
+            }
+        } else {
+            Command::parse(&line)
+        };
+        match command {
+            Ok(Command::Quit) => break,
+            Ok(command) => {
+                last_command = Some(command.clone());
+                println!("{}", debugger.execute(command));
+            },
+            Err(e) => println!("{}", e)
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::env;
+use std::process;
+
+use intcode::IntCode;
+use intcode::disassembler;
+
+fn main() {
+    let file_name = match env::args().nth(1) {
+        Some(name) => name,
+        None => {
+            eprintln!("Usage: intcode-disassembler <program file>");
+            process::exit(2);
+        }
+    };
+    match IntCode::file_to_program(&file_name) {
+        Ok(program) => print!("{}", disassembler::listing(program.memory())),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
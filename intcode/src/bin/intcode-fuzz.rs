@@ -0,0 +1,27 @@
+use std::env;
+use std::process;
+
+use intcode::fuzz::Fuzzer;
+
+fn argument(index: usize, default: u64) -> u64 {
+    match env::args().nth(index).map(|arg| arg.parse()) {
+        None => default,
+        Some(Ok(value)) => value,
+        Some(Err(_)) => {
+            eprintln!("Usage: intcode-fuzz [seed] [iterations]");
+            process::exit(2);
+        }
+    }
+}
+
+fn main() {
+    let seed = argument(1, 1);
+    let iterations = argument(2, 10_000) as usize;
+    match Fuzzer::new(seed).run(iterations) {
+        None => println!("No divergence in {} programs from seed {}", iterations, seed),
+        Some(divergence) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use std::env;
+use std::process;
+
+use intcode::Cell;
+use intcode::topology::{MachineStatus, Topology};
+
+fn main() {
+    let file_name = match env::args().nth(1) {
+        Some(name) => name,
+        None => {
+            eprintln!("Usage: intcode-network <topology file>");
+            process::exit(2);
+        }
+    };
+    let topology: Topology<Cell> = Topology::load(&file_name).unwrap_or_else(|e| {
+        eprintln!("{}: {}", file_name, e);
+        process::exit(1);
+    });
+    let reports = topology.run().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    for report in &reports {
+        println!("{}", report);
+    }
+    if reports.iter().any(|r| matches!(r.status, MachineStatus::Failed(_))) { process::exit(1); }
+}
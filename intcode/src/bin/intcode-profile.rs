@@ -0,0 +1,64 @@
+use std::env;
+use std::process;
+
+use intcode::IntCode;
+use intcode::profile::Profiler;
+
+const USAGE: &str = "\
+Usage: intcode-profile <program file> [--json | --lcov <listing file>] [input...]
+
+Prints coverage, executions per opcode, the hottest loops and the annotated
+listing. Line numbers in the lcov output refer to the disassembler listing.";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+enum Report {
+    Text,
+    Json,
+    Lcov(String)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let file_name = args.first().unwrap_or_else(|| usage());
+    let (report, inputs) = match args.get(1).map(|s| s.as_str()) {
+        Some("--json") => (Report::Json, &args[2..]),
+        Some("--lcov") => (Report::Lcov(args.get(2).cloned().unwrap_or_else(|| usage())), &args[3..]),
+        _ => (Report::Text, &args[1..])
+    };
+    let mut program = IntCode::file_to_program(file_name).unwrap_or_else(|e| fail(e));
+    for value in inputs {
+        program.add_input(value.parse().unwrap_or_else(|_| fail(format!("Invalid input value '{}'", value))));
+    }
+    let mut profiler = Profiler::new(&program);
+    let result = profiler.run_program(&mut program);
+    match report {
+        Report::Json => print!("{}", profiler.to_json()),
+        Report::Lcov(listing) => print!("{}", profiler.to_lcov(&listing)),
+        Report::Text => {
+            println!("{} steps", profiler.steps());
+            println!("{}", profiler.coverage());
+            println!();
+            for opcode in profiler.opcodes().into_iter().filter(|op| profiler.opcode(*op) > 0) {
+                println!("{:15} {:>10}", opcode.mnemonic(), profiler.opcode(opcode));
+            }
+            println!();
+            print!("{}", profiler.hot_loops());
+            println!();
+            print!("{}", profiler.annotated());
+        }
+    }
+    // The profile of a failed run is still printed, it shows how far it got
+    match result {
+        Ok(output) => eprintln!("Output: {}", output.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")),
+        Err(e) => fail(e)
+    }
+}
@@ -0,0 +1,69 @@
+use std::env;
+use std::process;
+
+use intcode::{IntCode, IntCodeState};
+use intcode::input::Reader;
+use intcode::session::Session;
+use intcode::trace::Tracer;
+
+const USAGE: &str = "\
+Usage: intcode-session record <program file> <session file> [input...]
+       intcode-session replay <program file> <session file> [--context <n>]
+
+Recording reads input from the arguments first, then from stdin.";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn record(program: &mut IntCode, file_name: &str, input: &[String]) {
+    for value in input {
+        program.add_input(value.parse().unwrap_or_else(|_| fail(format!("Invalid input value '{}'", value))));
+    }
+    let mut tracer = Tracer::new(Session::new());
+    let mut stdin = Reader::stdin();
+    let mut output = Vec::new();
+    let result = loop {
+        match tracer.run_slice_from(program, &mut stdin) {
+            Ok(IntCodeState::Output(value)) => output.push(value.to_string()),
+            Ok(IntCodeState::Done) => break Ok(()),
+            Ok(IntCodeState::NeedInput) => break Err("Input ended while the program was waiting for more".to_string()),
+            Err(e) => break Err(e.to_string())
+        }
+    };
+    // Even a run that failed is worth replaying
+    if let Err(e) = tracer.into_sink().save(file_name) { fail(e); }
+    println!("{}", output.join(","));
+    if let Err(e) = result { fail(e); }
+}
+
+fn replay(program: &mut IntCode, file_name: &str, options: &[String]) {
+    let context = match options {
+        [] => 10,
+        [option, n] if option == "--context" => n.parse().unwrap_or_else(|_| fail(format!("Invalid context '{}'", n))),
+        _ => usage()
+    };
+    let session = Session::load(file_name).unwrap_or_else(|e| fail(e));
+    match session.replay(program, context) {
+        Ok(None) => println!("Replayed {} events", session.events.len()),
+        Ok(Some(divergence)) => fail(divergence),
+        Err(e) => fail(e)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 3 { usage(); }
+    let mut program = IntCode::file_to_program(&args[1]).unwrap_or_else(|e| fail(e));
+    match args[0].as_str() {
+        "record" => record(&mut program, &args[2], &args[3..]),
+        "replay" => replay(&mut program, &args[2], &args[3..]),
+        _ => usage()
+    }
+}
@@ -0,0 +1,88 @@
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::process;
+
+use intcode::{Cell, IntCode};
+use intcode::instruction::Opcode;
+use intcode::trace::{self, Binary, Filter, JsonLines, TraceRecord, TraceSink, Tracer};
+
+const USAGE: &str = "\
+Usage: intcode-trace record <program file> <trace file> [input...]
+       intcode-trace show <trace file> [--pc <pc>] [--op <mnemonic>] [--writes-to <addr>] [--io] [--last <n>]
+
+Trace files ending in .jsonl are written as JSON lines, others in the binary format.";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<&String>, what: &str) -> T {
+    let arg = arg.unwrap_or_else(|| fail(format!("Missing {}", what)));
+    arg.parse().unwrap_or_else(|_| fail(format!("Invalid {} '{}'", what, arg)))
+}
+
+fn record_with<S: TraceSink<Cell>>(sink: S, program: &mut IntCode) {
+    let mut tracer = Tracer::new(sink);
+    let result = tracer.run_program(program);
+    // The trace of a failed run is the interesting one, so flush it either way
+    if let Err(e) = tracer.flush() { fail(e); }
+    if let Some(memory) = tracer.peak_memory() { eprintln!("Peak memory: {}", memory); }
+    match result {
+        Ok(output) => println!("{}", output.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")),
+        Err(e) => fail(e)
+    }
+}
+
+fn record(args: &[String]) {
+    if args.len() < 2 { usage(); }
+    let mut program = IntCode::file_to_program(&args[0]).unwrap_or_else(|e| fail(e));
+    for value in &args[2..] {
+        program.add_input(parse(Some(value), "input value"));
+    }
+    let file = File::create(&args[1]).map(BufWriter::new).unwrap_or_else(|e| fail(format!("{}: {}", args[1], e)));
+    if args[1].ends_with(".jsonl") {
+        record_with(JsonLines::new(file), &mut program);
+    } else {
+        record_with(Binary::new(file).unwrap_or_else(|e| fail(e)), &mut program);
+    }
+}
+
+fn show(args: &[String]) {
+    let file_name = args.first().unwrap_or_else(|| usage());
+    let mut filter = Filter::default();
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--pc" => filter.pc = Some(parse(options.next(), "pc")),
+            "--op" => {
+                let mnemonic: String = parse(options.next(), "mnemonic");
+                filter.opcode = Some(Opcode::from_mnemonic(&mnemonic).unwrap_or_else(|| fail(format!("Unknown mnemonic '{}'", mnemonic))));
+            },
+            "--writes-to" => filter.writes_to = Some(parse(options.next(), "address")),
+            "--io" => filter.io = true,
+            "--last" => filter.last = Some(parse(options.next(), "count")),
+            _ => usage()
+        }
+    }
+    let records: Vec<TraceRecord<Cell>> = trace::read_trace_file(file_name)
+        .unwrap_or_else(|e| fail(format!("{}: {}", file_name, e)));
+    for record in filter.apply(&records) {
+        println!("{}", record);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("record") => record(&args[1..]),
+        Some("show") => show(&args[1..]),
+        _ => usage()
+    }
+}
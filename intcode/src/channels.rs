@@ -0,0 +1,230 @@
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::thread;
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number};
+
+pub use std::sync::mpsc::channel;
+
+/// How a machine stopped when run by Node::poll.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Status {
+    Halted,
+    /// Waiting for input that has not been sent yet.
+    Blocked
+}
+
+/// A machine that reads its input from a channel and sends every output
+/// value to each of its output channels. Output sent to a machine that has
+/// already stopped is dropped.
+pub struct Node<C = Cell> {
+    machine: IntCode<C>,
+    input: Receiver<C>,
+    outputs: Vec<Sender<C>>,
+    sent: u64
+}
+
+impl<C: Number> Node<C> {
+    pub fn new(machine: IntCode<C>, input: Receiver<C>) -> Self {
+        Node { machine, input, outputs: Vec::new(), sent: 0 }
+    }
+
+    /// Adds an output channel. Connecting several gives fan-out.
+    pub fn connect(&mut self, output: Sender<C>) {
+        self.outputs.push(output);
+    }
+
+    pub fn machine(&self) -> &IntCode<C> {
+        &self.machine
+    }
+
+    pub fn into_machine(self) -> IntCode<C> {
+        self.machine
+    }
+
+    /// Number of values sent so far.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    fn send(&mut self, value: C) {
+        for output in &self.outputs {
+            let _ = output.send(value.clone());
+        }
+        self.sent += 1;
+    }
+
+    fn starvation(&self) -> IntCodeError<C> {
+        IntCodeError::InputStarvation { pc: self.machine.pc(), instruction: self.machine.peek(self.machine.pc()) }
+    }
+
+    /// Runs the machine to completion, blocking whenever it waits for input.
+    /// Running out of input, because every sender is gone, is an error.
+    pub fn run_blocking(mut self) -> Result<IntCode<C>, IntCodeError<C>> {
+        loop {
+            match self.machine.run_slice()? {
+                IntCodeState::Done => return Ok(self.machine),
+                IntCodeState::Output(value) => self.send(value),
+                IntCodeState::NeedInput => match self.input.recv() {
+                    Ok(value) => self.machine.add_input(value),
+                    Err(_) => return Err(self.starvation())
+                }
+            }
+        }
+    }
+
+    /// Runs the machine until it halts or needs input that has not arrived,
+    /// without blocking.
+    pub fn poll(&mut self) -> Result<Status, IntCodeError<C>> {
+        loop {
+            match self.machine.run_slice()? {
+                IntCodeState::Done => return Ok(Status::Halted),
+                IntCodeState::Output(value) => self.send(value),
+                IntCodeState::NeedInput => match self.input.try_recv() {
+                    Ok(value) => self.machine.add_input(value),
+                    Err(TryRecvError::Empty) => return Ok(Status::Blocked),
+                    Err(TryRecvError::Disconnected) => return Err(self.starvation())
+                }
+            }
+        }
+    }
+}
+
+/// Runs every node on its own thread and returns the halted machines in
+/// the order given, or the first error.
+pub fn run_threads<C: Number + Send + 'static>(nodes: Vec<Node<C>>) -> Result<Vec<IntCode<C>>, IntCodeError<C>> {
+    let handles: Vec<_> = nodes.into_iter().map(|node| thread::spawn(move || node.run_blocking())).collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().expect("Intcode machine thread panicked")).collect();
+    results.into_iter().collect()
+}
+
+/// Runs nodes cooperatively on the current thread, polling them in turn.
+#[derive(Default)]
+pub struct Executor<C = Cell> {
+    nodes: Vec<Node<C>>
+}
+
+impl<C: Number> Executor<C> {
+    pub fn new() -> Self {
+        Executor { nodes: Vec::new() }
+    }
+
+    /// Adds a node and returns its index.
+    pub fn add(&mut self, node: Node<C>) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Polls the nodes round robin until all have halted. A round in which
+    /// nothing is sent while some node is blocked can never finish, that is
+    /// reported as a deadlock.
+    pub fn run(mut self) -> Result<Vec<IntCode<C>>, IntCodeError<C>> {
+        loop {
+            let sent_before: u64 = self.nodes.iter().map(|n| n.sent()).sum();
+            let mut blocked = Vec::new();
+            for (i, node) in self.nodes.iter_mut().enumerate() {
+                if node.poll()? == Status::Blocked { blocked.push(i); }
+            }
+            if blocked.is_empty() { break; }
+            let sent_after: u64 = self.nodes.iter().map(|n| n.sent()).sum();
+            if sent_after == sent_before { return Err(IntCodeError::Deadlock { blocked }); }
+        }
+        Ok(self.nodes.into_iter().map(|n| n.into_machine()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Outputs each input value plus one until it reads a zero
+    const INCREMENT: &str = "3,100,1006,100,14,1001,100,1,100,4,100,1105,1,0,99";
+
+    fn increment() -> IntCode {
+        IntCode::string_to_program(INCREMENT).unwrap()
+    }
+
+    fn chain() -> (Sender<Cell>, Vec<Node>, Receiver<Cell>) {
+        let (tx, first_rx) = channel();
+        let (middle_tx, middle_rx) = channel();
+        let (result_tx, result_rx) = channel();
+        let mut first = Node::new(increment(), first_rx);
+        first.connect(middle_tx);
+        let mut second = Node::new(increment(), middle_rx);
+        second.connect(result_tx);
+        (tx, vec![first, second], result_rx)
+    }
+
+    #[test]
+    fn chain_should_pass_values_through_threads() {
+        let (tx, nodes, result) = chain();
+        for v in &[1, 2, 3] { tx.send(*v).unwrap(); }
+        tx.send(0).unwrap();
+
+        // The second machine never sees a zero, so it starves once the first halts
+        assert!(matches!(run_threads(nodes), Err(IntCodeError::InputStarvation { .. })));
+        assert_eq!(vec![3, 4, 5], result.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn chain_should_pass_values_through_the_executor() {
+        let (tx, nodes, result) = chain();
+        for v in &[1, 2, 3] { tx.send(*v).unwrap(); }
+        let mut executor = Executor::new();
+        nodes.into_iter().for_each(|n| { executor.add(n); });
+
+        assert_eq!(Err(IntCodeError::Deadlock { blocked: vec![0, 1] }), executor.run().map(|_| ()));
+        assert_eq!(vec![3, 4, 5], result.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn fan_out_should_copy_every_output() {
+        let (tx, rx) = channel();
+        let (left_tx, left) = channel();
+        let (right_tx, right) = channel();
+        let mut node = Node::new(increment(), rx);
+        node.connect(left_tx);
+        node.connect(right_tx);
+        tx.send(41).unwrap();
+        tx.send(0).unwrap();
+        let mut executor = Executor::new();
+        executor.add(node);
+        executor.run().unwrap();
+
+        assert_eq!(vec![42], left.try_iter().collect::<Vec<_>>());
+        assert_eq!(vec![42], right.try_iter().collect::<Vec<_>>());
+    }
+
+    fn feedback_loop(program: &IntCode, phases: &[Cell]) -> (Vec<Node>, Receiver<Cell>) {
+        let channels: Vec<_> = phases.iter().map(|phase| {
+            let (tx, rx) = channel();
+            tx.send(*phase).unwrap();
+            (tx, rx)
+        }).collect();
+        channels[0].0.send(0).unwrap();
+        let (result_tx, result_rx) = channel();
+        let mut nodes: Vec<_> = Vec::new();
+        let senders: Vec<_> = channels.iter().map(|(tx, _)| tx.clone()).collect();
+        for (i, (_, rx)) in channels.into_iter().enumerate() {
+            let mut node = Node::new(program.clone(), rx);
+            node.connect(senders[(i + 1) % senders.len()].clone());
+            nodes.push(node);
+        }
+        nodes.last_mut().unwrap().connect(result_tx);
+        (nodes, result_rx)
+    }
+
+    #[test]
+    fn feedback_loop_should_give_the_same_result_in_both_modes() {
+        let program = IntCode::string_to_program("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5").unwrap();
+
+        let (nodes, result) = feedback_loop(&program, &[9, 8, 7, 6, 5]);
+        run_threads(nodes).unwrap();
+        assert_eq!(Some(139629729), result.try_iter().last());
+
+        let (nodes, result) = feedback_loop(&program, &[9, 8, 7, 6, 5]);
+        let mut executor = Executor::new();
+        nodes.into_iter().for_each(|n| { executor.add(n); });
+        executor.run().unwrap();
+        assert_eq!(Some(139629729), result.try_iter().last());
+    }
+}
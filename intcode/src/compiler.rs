@@ -0,0 +1,372 @@
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number, Observer};
+use crate::instruction::{Opcode, Parameter};
+
+/// A parameter with its mode resolved at compile time.
+#[derive(Debug, PartialEq, Clone)]
+enum Operand<C> {
+    Immediate(C),
+    Position(usize),
+    Relative(C)
+}
+
+#[derive(Debug, Clone)]
+struct Op<C> {
+    opcode: Opcode,
+    operands: [Operand<C>; 3]
+}
+
+fn operand<C: Number>(word: &C, mode: i64, parameter: Parameter) -> Option<Operand<C>> {
+    match (mode, parameter) {
+        (0, _) => word.to_i64().filter(|a| *a >= 0 && *a as u64 <= usize::MAX as u64).map(|a| Operand::Position(a as usize)),
+        (1, Parameter::Read) => Some(Operand::Immediate(word.clone())),
+        (2, _) => Some(Operand::Relative(word.clone())),
+        _ => None
+    }
+}
+
+/// Compiles the instruction at pc, or None for anything the interpreter
+/// should handle, including every instruction that fails.
+fn compile_at<C: Number>(image: &[C], pc: usize) -> Option<Op<C>> {
+    let word = image[pc].to_i64()?;
+    let opcode = Opcode::from_code(word % 100)?;
+    let mut operands = [Operand::Immediate(C::zero()), Operand::Immediate(C::zero()), Operand::Immediate(C::zero())];
+    let mut modes = word / 100;
+    for (i, parameter) in opcode.parameters().iter().enumerate() {
+        operands[i] = operand(image.get(pc + 1 + i)?, modes % 10, *parameter)?;
+        modes /= 10;
+    }
+    Some(Op { opcode, operands })
+}
+
+/// A program image translated into pre-decoded instructions, with every
+/// parameter mode and position resolved. Compile once and run it on as many
+/// machines as needed, see CompiledMachine.
+///
+/// Every address is compiled as if it starts an instruction, so jumps into
+/// the middle of one work too.
+#[derive(Debug, Clone)]
+pub struct Compiled<C = Cell> {
+    image: Vec<C>,
+    ops: Vec<Option<Op<C>>>
+}
+
+impl<C: Number> Compiled<C> {
+    pub fn new(program: &IntCode<C>) -> Self {
+        let image = program.memory().to_vec();
+        let ops = (0..image.len()).map(|pc| compile_at(&image, pc)).collect();
+        Compiled { image, ops }
+    }
+
+    /// Number of addresses holding a compiled instruction.
+    pub fn compiled(&self) -> usize {
+        self.ops.iter().filter(|op| op.is_some()).count()
+    }
+}
+
+/// Marks compiled instructions overlapping written addresses as stale. The
+/// stale flags are only allocated once something is written to code.
+struct Invalidator<'a, C> {
+    ops: &'a [Option<Op<C>>],
+    stale: &'a mut Vec<bool>
+}
+
+impl<'a, C: Number> Invalidator<'a, C> {
+    fn invalidate(&mut self, address: usize) {
+        for start in address.saturating_sub(3)..=address {
+            if let Some(Some(op)) = self.ops.get(start) {
+                if start + op.opcode.size() > address {
+                    if self.stale.is_empty() { self.stale.resize(self.ops.len(), false); }
+                    self.stale[start] = true;
+                }
+            }
+        }
+    }
+}
+
+impl<'a, C: Number> Observer<C> for Invalidator<'a, C> {
+    fn write(&mut self, address: usize, _old: &C, _new: &C) {
+        self.invalidate(address);
+    }
+}
+
+/// Runs a machine on compiled code. An instruction whose cells have been
+/// written since compilation, by the program or before the run, is executed
+/// by the interpreter instead, so self-modifying programs behave exactly as
+/// they do on IntCode.
+pub struct CompiledMachine<'a, C = Cell> {
+    code: &'a Compiled<C>,
+    machine: IntCode<C>,
+    stale: Vec<bool>,
+    interpreted: u64
+}
+
+impl<'a, C: Number> CompiledMachine<'a, C> {
+    /// Runs machine on code. Cells where the machine differs from the
+    /// compiled image, like a patched day 2 noun and verb, are interpreted.
+    pub fn new(code: &'a Compiled<C>, machine: IntCode<C>) -> Self {
+        let mut compiled = CompiledMachine { code, machine, stale: Vec::new(), interpreted: 0 };
+        if compiled.machine.memory() != code.image.as_slice() {
+            let machine = &compiled.machine;
+            let changed: Vec<usize> = code.image.iter().enumerate().filter(|(a, word)| machine.peek(*a) != **word).map(|(a, _)| a).collect();
+            changed.into_iter().for_each(|address| compiled.invalidator().invalidate(address));
+        }
+        compiled
+    }
+
+    fn invalidator(&mut self) -> Invalidator<'_, C> {
+        Invalidator { ops: &self.code.ops, stale: &mut self.stale }
+    }
+
+    pub fn machine(&self) -> &IntCode<C> {
+        &self.machine
+    }
+
+    pub fn into_machine(self) -> IntCode<C> {
+        self.machine
+    }
+
+    /// Number of instructions executed by the interpreter.
+    pub fn interpreted(&self) -> u64 {
+        self.interpreted
+    }
+
+    pub fn add_input(&mut self, input: C) {
+        self.machine.add_input(input);
+    }
+
+    pub fn poke(&mut self, address: usize, value: C) {
+        self.machine.poke(address, value);
+        self.invalidator().invalidate(address);
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.machine.is_done()
+    }
+
+    fn load(&self, operand: &Operand<C>) -> Result<C, IntCodeError<C>> {
+        match operand {
+            Operand::Immediate(value) => Ok(value.clone()),
+            Operand::Position(address) => Ok(self.machine.peek(*address)),
+            Operand::Relative(offset) => Ok(self.machine.peek(self.machine.relative_address(offset)?))
+        }
+    }
+
+    fn store_address(&self, operand: &Operand<C>) -> Result<usize, IntCodeError<C>> {
+        match operand {
+            Operand::Position(address) => Ok(*address),
+            Operand::Relative(offset) => self.machine.relative_address(offset),
+            Operand::Immediate(_) => unreachable!("write parameters are never compiled in immediate mode")
+        }
+    }
+
+    fn store(&mut self, address: usize, value: C) -> Result<(), IntCodeError<C>> {
+        self.machine.write(address, value, &mut ())?;
+        self.invalidator().invalidate(address);
+        Ok(())
+    }
+
+    fn jump(&mut self, condition: bool, pc: usize, target: &Operand<C>) -> Result<(), IntCodeError<C>> {
+        let next = match condition {
+            true => self.machine.address(self.load(target)?)?,
+            false => pc + 3
+        };
+        self.machine.set_pc(next);
+        Ok(())
+    }
+
+    /// Executes a compiled instruction, evaluating parameters in the same
+    /// order as the interpreter so that errors match.
+    fn execute(&mut self, op: &Op<C>) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        let pc = self.machine.pc();
+        let [a, b, c] = &op.operands;
+        match op.opcode {
+            Opcode::Add | Opcode::Multiply | Opcode::StoreLessThan | Opcode::StoreEqual => {
+                let (x, y) = (self.load(a)?, self.load(b)?);
+                let address = self.store_address(c)?;
+                let result = match op.opcode {
+                    Opcode::Add => self.machine.add(&x, &y)?,
+                    Opcode::Multiply => self.machine.multiply(&x, &y)?,
+                    Opcode::StoreLessThan => C::from_i64((x < y) as i64),
+                    _ => C::from_i64((x == y) as i64)
+                };
+                self.store(address, result)?;
+                self.machine.set_pc(pc + 4);
+            },
+            Opcode::Input => match self.machine.input_mut().pop_front() {
+                None => return Ok(Some(IntCodeState::NeedInput)),
+                Some(value) => {
+                    let address = self.store_address(a)?;
+                    self.store(address, value)?;
+                    self.machine.set_pc(pc + 2);
+                }
+            },
+            Opcode::Output => {
+                let value = self.load(a)?;
+                self.machine.set_pc(pc + 2);
+                return Ok(Some(IntCodeState::Output(value)));
+            },
+            Opcode::JumpNotZero => {
+                let condition = !self.load(a)?.is_zero();
+                self.jump(condition, pc, b)?;
+            },
+            Opcode::JumpZero => {
+                let condition = self.load(a)?.is_zero();
+                self.jump(condition, pc, b)?;
+            },
+            Opcode::AdjustRelativeBase => {
+                let offset = self.load(a)?;
+                let base = offset.checked_add(self.machine.relative_base()).ok_or_else(|| self.machine.overflow_error())?;
+                self.machine.set_registers(pc + 2, base);
+            },
+            Opcode::Halt => return Ok(Some(IntCodeState::Done)),
+            Opcode::Extension(_) => unreachable!("extensions are never compiled")
+        }
+        Ok(None)
+    }
+
+    /// Like IntCode::step.
+    pub fn step(&mut self) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        let pc = self.machine.pc();
+        let code = self.code;
+        match code.ops.get(pc) {
+            Some(Some(op)) if !self.stale.get(pc).is_some_and(|stale| *stale) => {
+                self.machine.admit(op.opcode == Opcode::Output)?;
+                let state = self.execute(op)?;
+                self.machine.spend(&state);
+                Ok(state)
+            },
+            _ => {
+                self.interpreted += 1;
+                let mut invalidator = Invalidator { ops: &code.ops, stale: &mut self.stale };
+                self.machine.step_with(&mut invalidator)
+            }
+        }
+    }
+
+    /// Like IntCode::run_slice.
+    pub fn run_slice(&mut self) -> Result<IntCodeState<C>, IntCodeError<C>> {
+        loop {
+            if let Some(state) = self.step()? { return Ok(state); }
+        }
+    }
+
+    /// Like IntCode::run_program.
+    pub fn run_program(&mut self) -> Result<Vec<C>, IntCodeError<C>> {
+        let mut output = Vec::new();
+        loop {
+            match self.run_slice()? {
+                IntCodeState::Done => return Ok(output),
+                IntCodeState::NeedInput => return Err(IntCodeError::InputStarvation {
+                    pc: self.machine.pc(), instruction: self.machine.peek(self.machine.pc())
+                }),
+                IntCodeState::Output(value) => output.push(value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::Limits;
+
+    const INPUTS: [&[Cell]; 7] = [&[], &[0], &[1], &[5], &[8], &[9, 0], &[1, 2, 3]];
+
+    /// Every program literal in the crate's tests plus the puzzle inputs.
+    fn test_programs() -> Vec<String> {
+        let mut programs: Vec<String> = ["day2", "day5", "day7", "day9", "day11"].iter()
+            .map(|day| fs::read_to_string(format!("../{}/src/{}.txt", day, day)).unwrap())
+            .collect();
+        for entry in fs::read_dir("src").unwrap() {
+            let source = fs::read_to_string(entry.unwrap().path()).unwrap_or_default();
+            for literal in source.split("string_to_program(\"").skip(1) {
+                programs.push(literal[..literal.find('"').unwrap()].to_string());
+            }
+        }
+        programs
+    }
+
+    /// Runs both tiers in lockstep and fails at the first step where they differ.
+    fn assert_same_behaviour(source: &str, input: &[Cell]) {
+        let mut interpreter = match IntCode::string_to_program(source) {
+            Ok(p) => p,
+            Err(_) => return
+        };
+        input.iter().for_each(|v| interpreter.add_input(*v));
+        let code = Compiled::new(&interpreter);
+        let mut compiled = CompiledMachine::new(&code, interpreter.clone());
+        for step in 0..100_000 {
+            let expected = interpreter.step();
+            let actual = compiled.step();
+            let context = format!("program {} with input {:?} at step {}", source.trim(), input, step);
+            assert_eq!(expected, actual, "{}", context);
+            assert_eq!((interpreter.pc(), interpreter.relative_base()), (compiled.machine().pc(), compiled.machine().relative_base()), "{}", context);
+            if !matches!(expected, Ok(None) | Ok(Some(IntCodeState::Output(_)))) { break; }
+        }
+        assert_eq!(interpreter.snapshot(), compiled.machine().snapshot(), "program {} with input {:?}", source.trim(), input);
+    }
+
+    #[test]
+    fn both_tiers_should_agree_on_every_test_program() {
+        let programs = test_programs();
+        assert!(programs.len() > 50);
+        for source in &programs {
+            for input in INPUTS.iter() {
+                assert_same_behaviour(source, input);
+            }
+        }
+    }
+
+    #[test]
+    fn limits_should_apply_to_compiled_code() {
+        let mut program = IntCode::string_to_program("104,1,1105,1,0").unwrap();
+        program.set_limits(Limits { instructions: Some(100), outputs: Some(10), ..Limits::default() });
+        let code = Compiled::new(&program);
+        let mut compiled = CompiledMachine::new(&code, program);
+        let actual = compiled.run_program();
+        assert_eq!(Err(IntCodeError::OutputLimit { pc: 0, instruction: 104, limit: 10 }), actual);
+        assert_eq!(20, compiled.machine().budget().unwrap().instructions);
+        assert_eq!(0, compiled.interpreted());
+    }
+
+    #[test]
+    fn patched_cells_should_be_interpreted() {
+        let program = IntCode::file_to_program("../day2/src/day2.txt").unwrap();
+        let code = Compiled::new(&program);
+        let mut machine = program.clone();
+        machine.poke(1, 12);
+        machine.poke(2, 2);
+        let mut compiled = CompiledMachine::new(&code, machine);
+        compiled.run_program().unwrap();
+
+        assert_eq!(3760627, compiled.machine().peek(0));
+        assert_eq!(1, compiled.interpreted());
+    }
+
+    #[test]
+    fn self_modifying_code_should_fall_back_to_the_interpreter() {
+        // Outputs 7, then patches OUTPUT #7 into OUTPUT [7] and runs it again
+        let program = IntCode::string_to_program("104,7,1005,17,16,1101,0,4,0,1101,1,0,17,1106,0,0,99,0").unwrap();
+        let code = Compiled::new(&program);
+        let mut compiled = CompiledMachine::new(&code, program);
+
+        assert_eq!(vec![7, 4], compiled.run_program().unwrap());
+        assert_eq!(1, compiled.interpreted());
+    }
+
+    #[test]
+    fn compiled_code_should_be_shareable_between_machines() {
+        let program = IntCode::file_to_program("../day5/src/day5.txt").unwrap();
+        let code = Compiled::new(&program);
+        let outputs: Vec<Vec<Cell>> = [1, 5].iter().map(|input| {
+            let mut compiled = CompiledMachine::new(&code, program.clone());
+            compiled.add_input(*input);
+            compiled.run_program().unwrap()
+        }).collect();
+
+        assert_eq!(Some(&8834787), outputs[1].last());
+        assert!(outputs[0][..outputs[0].len() - 1].iter().all(|v| *v == 0));
+    }
+}
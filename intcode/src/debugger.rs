@@ -0,0 +1,469 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::{Cell, IntCode, IntCodeState, Number, Observer, Snapshot};
+use crate::history::History;
+
+pub const HELP: &str = "\
+step [n]               execute n instructions (default 1)
+continue               run until breakpoint, watchpoint, input request or halt
+back [n]               undo n instructions or pokes (default 1)
+backto <pc>            go back to the last time the instruction at pc was executed
+break <pc>             set breakpoint
+delete <pc>            remove breakpoint
+watch <addr> [r|w|rw]  stop when the address is read and/or written (default rw)
+unwatch <addr>         remove watchpoint
+print <addr> [n]       show n memory cells starting at addr (default 1)
+poke <addr> <value>    write value to memory
+regs                   show pc, relative base, pending input and output
+memory                 show memory usage
+input <v>...           queue input values
+save <file>            write a snapshot of the machine to a file
+load <file>            replace the machine with a snapshot from a file
+help                   show this text
+quit                   leave the debugger";
+
+/// The most cells a single print shows.
+const MAX_PRINT: usize = 4096;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite
+}
+
+impl Access {
+    fn on_read(self) -> bool { self != Access::Write }
+    fn on_write(self) -> bool { self != Access::Read }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command<C = Cell> {
+    Step(usize),
+    Continue,
+    Back(usize),
+    BackTo(usize),
+    Break(usize),
+    Delete(usize),
+    Watch(usize, Access),
+    Unwatch(usize),
+    Print(usize, usize),
+    Poke(usize, C),
+    Registers,
+    Memory,
+    Input(Vec<C>),
+    Save(String),
+    Load(String),
+    Help,
+    Quit
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("Missing {}", what))?;
+    arg.parse().map_err(|_| format!("Invalid {} '{}'", what, arg))
+}
+
+impl<C: Number> Command<C> {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            None => return Err("Empty command".to_string()),
+            Some(word) => word
+        };
+        let arg = words.next();
+        let result = match command {
+            "s" | "step" => Command::Step(match arg { None => 1, a => parse_arg(a, "step count")? }),
+            "c" | "continue" => Command::Continue,
+            "back" => Command::Back(match arg { None => 1, a => parse_arg(a, "step count")? }),
+            "backto" => Command::BackTo(parse_arg(arg, "pc")?),
+            "b" | "break" => Command::Break(parse_arg(arg, "pc")?),
+            "d" | "delete" => Command::Delete(parse_arg(arg, "pc")?),
+            "w" | "watch" => {
+                let address = parse_arg(arg, "address")?;
+                let access = match words.next() {
+                    None | Some("rw") => Access::ReadWrite,
+                    Some("r") => Access::Read,
+                    Some("w") => Access::Write,
+                    Some(other) => return Err(format!("Invalid access '{}', use r, w or rw", other))
+                };
+                Command::Watch(address, access)
+            },
+            "u" | "unwatch" => Command::Unwatch(parse_arg(arg, "address")?),
+            "p" | "print" => {
+                let address = parse_arg(arg, "address")?;
+                let count = match words.next() { None => 1, n => parse_arg(n, "count")? };
+                Command::Print(address, count)
+            },
+            "poke" => Command::Poke(parse_arg(arg, "address")?, parse_arg(words.next(), "value")?),
+            "r" | "regs" => Command::Registers,
+            "m" | "memory" => Command::Memory,
+            "i" | "input" => {
+                let values = arg.into_iter().chain(words).map(|v| parse_arg(Some(v), "input value"));
+                Command::Input(values.collect::<Result<_, _>>()?)
+            },
+            "save" => Command::Save(parse_arg(arg, "file name")?),
+            "load" => Command::Load(parse_arg(arg, "file name")?),
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            _ => return Err(format!("Unknown command '{}', try help", command))
+        };
+        Ok(result)
+    }
+}
+
+struct WatchHits<'a> {
+    watchpoints: &'a BTreeMap<usize, Access>,
+    hits: Vec<String>
+}
+
+impl<'a, C: Number> Observer<C> for WatchHits<'a> {
+    fn read(&mut self, address: usize, value: &C) {
+        if self.watchpoints.get(&address).is_some_and(|a| a.on_read()) {
+            self.hits.push(format!("Watchpoint: read [{}] = {}", address, value));
+        }
+    }
+
+    fn write(&mut self, address: usize, old: &C, new: &C) {
+        if self.watchpoints.get(&address).is_some_and(|a| a.on_write()) {
+            self.hits.push(format!("Watchpoint: write [{}] {} -> {}", address, old, new));
+        }
+    }
+}
+
+pub struct Debugger<C = Cell> {
+    history: History<C>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, Access>,
+    output: Vec<C>
+}
+
+impl<C: Number> Debugger<C> {
+    pub fn new(machine: IntCode<C>) -> Self {
+        Debugger { history: History::new(machine), breakpoints: BTreeSet::new(), watchpoints: BTreeMap::new(), output: Vec::new() }
+    }
+
+    pub fn machine(&self) -> &IntCode<C> {
+        self.history.machine()
+    }
+
+    pub fn output(&self) -> &[C] {
+        &self.output
+    }
+
+    /// Executes the command and returns the text to show the user.
+    pub fn execute(&mut self, command: Command<C>) -> String {
+        match command {
+            Command::Step(n) => self.run(Some(n)),
+            Command::Continue => self.run(None),
+            Command::Back(n) => {
+                let steps = self.history.step_back(n);
+                format!("Went back {} instruction(s)\n{}", steps, self.location())
+            },
+            Command::BackTo(pc) => match self.history.run_back_to(pc) {
+                true => self.location(),
+                false => format!("No recorded execution of {}", pc)
+            },
+            Command::Break(pc) => {
+                self.breakpoints.insert(pc);
+                format!("Breakpoint set at {}", pc)
+            },
+            Command::Delete(pc) => match self.breakpoints.remove(&pc) {
+                true => format!("Breakpoint at {} removed", pc),
+                false => format!("No breakpoint at {}", pc)
+            },
+            Command::Watch(address, access) => {
+                self.watchpoints.insert(address, access);
+                format!("Watching [{}] for {:?}", address, access)
+            },
+            Command::Unwatch(address) => match self.watchpoints.remove(&address) {
+                Some(_) => format!("Watchpoint at [{}] removed", address),
+                None => format!("No watchpoint at [{}]", address)
+            },
+            Command::Print(address, count) => match address.checked_add(count) {
+                _ if count > MAX_PRINT => format!("Error: Cannot print more than {} cells at once", MAX_PRINT),
+                None => format!("Error: Address {} plus {} cells is out of range", address, count),
+                Some(end) => {
+                    let values: Vec<String> = (address..end).map(|a| self.machine().peek(a).to_string()).collect();
+                    format!("[{}]: {}", address, values.join(" "))
+                }
+            },
+            Command::Poke(address, value) => {
+                let report = format!("[{}]: {} -> {}", address, self.machine().peek(address), value);
+                self.history.poke(address, value);
+                report
+            },
+            Command::Registers => self.registers(),
+            Command::Memory => format!("Memory: {}", self.machine().memory_stats()),
+            Command::Input(values) => {
+                let count = values.len();
+                values.into_iter().for_each(|v| self.history.add_input(v));
+                format!("Queued {} input value(s), {} pending", count, self.machine().pending_input().len())
+            },
+            Command::Save(file_name) => match self.machine().save_snapshot(&file_name) {
+                Ok(()) => format!("Saved snapshot to {}", file_name),
+                Err(e) => format!("Error: {}", e)
+            },
+            Command::Load(file_name) => match Snapshot::load(&file_name) {
+                Ok(snapshot) => {
+                    let mut machine = self.machine().clone();
+                    machine.restore_from(snapshot);
+                    self.history = History::new(machine);
+                    format!("Loaded snapshot from {}\n{}", file_name, self.registers())
+                },
+                Err(e) => format!("Error: {}", e)
+            },
+            Command::Help => HELP.to_string(),
+            Command::Quit => String::new()
+        }
+    }
+
+
+    fn registers(&self) -> String {
+        let input: Vec<String> = self.machine().pending_input().iter().map(|v| v.to_string()).collect();
+        let output: Vec<String> = self.output.iter().map(|v| v.to_string()).collect();
+        format!("pc: {}  relative base: {}\ninput: [{}]\noutput: [{}]",
+            self.machine().pc(), self.machine().relative_base(), input.join(", "), output.join(", "))
+    }
+
+    /// Runs max_steps instructions, or until something interesting happens
+    /// when None. A breakpoint at the starting pc is ignored so that continue
+    /// can leave it.
+    fn run(&mut self, max_steps: Option<usize>) -> String {
+        let mut report = String::new();
+        let mut steps = 0;
+        loop {
+            if max_steps == Some(steps) { break; }
+            if steps > 0 && self.breakpoints.contains(&self.machine().pc()) {
+                writeln!(report, "Breakpoint at {}", self.machine().pc()).unwrap();
+                break;
+            }
+            let mut watch = WatchHits { watchpoints: &self.watchpoints, hits: Vec::new() };
+            match self.history.step_with(&mut watch) {
+                Err(e) => {
+                    writeln!(report, "Error: {}", e).unwrap();
+                    break;
+                },
+                Ok(Some(IntCodeState::Done)) => {
+                    writeln!(report, "Program halted").unwrap();
+                    break;
+                },
+                Ok(Some(IntCodeState::NeedInput)) => {
+                    writeln!(report, "Waiting for input").unwrap();
+                    break;
+                },
+                Ok(Some(IntCodeState::Output(value))) => {
+                    writeln!(report, "Output: {}", value).unwrap();
+                    self.output.push(value);
+                },
+                Ok(None) => ()
+            }
+            steps += 1;
+            if !watch.hits.is_empty() {
+                watch.hits.iter().for_each(|hit| writeln!(report, "{}", hit).unwrap());
+                break;
+            }
+        }
+        report + &self.location()
+    }
+
+    fn location(&self) -> String {
+        match self.machine().disassemble() {
+            Ok(line) => line,
+            Err(e) => format!("{:5}: {}", self.machine().pc(), e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads a number, counts it down to zero, outputs each value and halts.
+    const COUNTDOWN: &str = "3,100,4,100,1001,100,-1,100,1005,100,2,99";
+
+    fn debugger() -> Debugger {
+        Debugger::new(IntCode::string_to_program(COUNTDOWN).unwrap())
+    }
+
+    fn execute(d: &mut Debugger, line: &str) -> String {
+        d.execute(Command::parse(line).unwrap())
+    }
+
+    #[test]
+    fn parse_should_understand_commands_and_abbreviations() {
+        assert_eq!(Ok(Command::Step(1)), Command::<Cell>::parse("s"));
+        assert_eq!(Ok(Command::Step(10)), Command::<Cell>::parse("step 10"));
+        assert_eq!(Ok(Command::Watch(1000, Access::Write)), Command::<Cell>::parse("watch 1000 w"));
+        assert_eq!(Ok(Command::Watch(7, Access::ReadWrite)), Command::<Cell>::parse("w 7"));
+        assert_eq!(Ok(Command::Print(5, 3)), Command::<Cell>::parse("p 5 3"));
+        assert_eq!(Ok(Command::Poke(5, -3)), Command::<Cell>::parse("poke 5 -3"));
+        assert_eq!(Ok(Command::Input(vec![1, 2, 3])), Command::<Cell>::parse("input 1 2 3"));
+    }
+
+    #[test]
+    fn parse_should_reject_bad_input() {
+        assert!(Command::<Cell>::parse("").is_err());
+        assert!(Command::<Cell>::parse("jump 4").is_err());
+        assert!(Command::<Cell>::parse("break").is_err());
+        assert!(Command::<Cell>::parse("watch 4 x").is_err());
+        assert!(Command::<Cell>::parse("input 1 two").is_err());
+    }
+
+    #[test]
+    fn step_without_input_should_wait_for_input() {
+        let mut d = debugger();
+        let report = execute(&mut d, "step");
+
+        assert!(report.starts_with("Waiting for input"));
+        assert_eq!(0, d.machine().pc());
+    }
+
+    #[test]
+    fn step_n_should_execute_n_instructions() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "step 3");
+
+        assert_eq!(8, d.machine().pc());
+        assert_eq!(&[3], d.output());
+    }
+
+    #[test]
+    fn continue_should_stop_at_breakpoint_and_leave_it_on_next_continue() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "break 2");
+        let report = execute(&mut d, "continue");
+        assert!(report.starts_with("Breakpoint at 2"));
+
+        execute(&mut d, "continue");
+        assert_eq!(2, d.machine().pc());
+        assert_eq!(&[3], d.output());
+    }
+
+    #[test]
+    fn continue_should_run_to_halt_after_breakpoint_is_deleted() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "break 2");
+        execute(&mut d, "continue");
+        execute(&mut d, "delete 2");
+        let report = execute(&mut d, "continue");
+
+        assert!(report.contains("Program halted"));
+        assert_eq!(&[3, 2, 1], d.output());
+    }
+
+    #[test]
+    fn write_watchpoint_should_stop_after_the_write() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "watch 100 w");
+        let report = execute(&mut d, "continue");
+
+        assert!(report.contains("Watchpoint: write [100] 0 -> 3"));
+        assert_eq!(2, d.machine().pc());
+    }
+
+    #[test]
+    fn read_watchpoint_should_ignore_writes() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "watch 100 r");
+        let report = execute(&mut d, "continue");
+
+        assert!(report.contains("Watchpoint: read [100] = 3"));
+        assert_eq!(4, d.machine().pc());
+    }
+
+    #[test]
+    fn poke_and_print_should_show_memory() {
+        let mut d = debugger();
+        execute(&mut d, "poke 100 42");
+
+        assert_eq!("[99]: 0 42 0", execute(&mut d, "print 99 3"));
+        assert!(execute(&mut d, "print 18446744073709551615 2").starts_with("Error: "));
+        assert!(execute(&mut d, "print 0 18446744073709551615").starts_with("Error: "));
+    }
+
+    #[test]
+    fn memory_should_show_allocated_pages() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "step");
+
+        assert_eq!("Memory: 1036 cells: image 12, 1 page(s) of 1024, no limit", execute(&mut d, "memory"));
+    }
+
+    #[test]
+    fn regs_should_show_relative_base_and_pending_input() {
+        let mut d = Debugger::new(IntCode::string_to_program("109,19,99").unwrap());
+        execute(&mut d, "input 4 5");
+        execute(&mut d, "step");
+
+        assert_eq!("pc: 2  relative base: 19\ninput: [4, 5]\noutput: []", execute(&mut d, "regs"));
+    }
+
+    #[test]
+    fn load_should_return_to_a_saved_snapshot() {
+        let file_name = std::env::temp_dir().join(format!("icdb-test-{}", std::process::id()));
+        let file_name = file_name.to_str().unwrap();
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "step 2");
+        execute(&mut d, &format!("save {}", file_name));
+        execute(&mut d, "continue");
+        let report = execute(&mut d, &format!("load {}", file_name));
+        std::fs::remove_file(file_name).unwrap();
+
+        assert!(report.contains("pc: 4"));
+        assert_eq!(3, d.machine().peek(100));
+    }
+
+    #[test]
+    fn back_should_undo_instructions() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "step 3");
+        let report = execute(&mut d, "back 2");
+
+        assert!(report.starts_with("Went back 2 instruction(s)"));
+        assert_eq!(2, d.machine().pc());
+        assert_eq!(3, d.machine().peek(100));
+    }
+
+    #[test]
+    fn back_should_undo_a_poke() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "step 2");
+        execute(&mut d, "poke 100 1");
+        let report = execute(&mut d, "back");
+
+        assert!(report.starts_with("Went back 1 instruction(s)"));
+        assert_eq!(3, d.machine().peek(100));
+        execute(&mut d, "back 2");
+        assert_eq!(0, d.machine().pc());
+    }
+
+    #[test]
+    fn backto_should_find_the_last_execution_of_pc() {
+        let mut d = debugger();
+        execute(&mut d, "input 3");
+        execute(&mut d, "continue");
+
+        execute(&mut d, "backto 4");
+        assert_eq!(4, d.machine().pc());
+        assert_eq!(1, d.machine().peek(100));
+        assert_eq!("No recorded execution of 50", execute(&mut d, "backto 50"));
+    }
+
+    #[test]
+    fn errors_should_be_reported_instead_of_panicking() {
+        let mut d = Debugger::new(IntCode::string_to_program("42").unwrap());
+        let report = execute(&mut d, "continue");
+
+        assert!(report.starts_with("Error: Invalid op-code 42 at pc 0"));
+    }
+}
@@ -0,0 +1,628 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::Number;
+use crate::analysis::{Analysis, Block, Edge};
+use crate::instruction::{Mode, Opcode, Parameter};
+
+/// Stands for leaving the function in post-dominator sets.
+const EXIT: usize = usize::MAX;
+
+/// Lifts a program into pseudocode, one function for the entry point and
+/// one for every call target found by the static analysis.
+///
+/// Position cells are named `v<address>`. Relative cells are named by their
+/// place in the frame, tracked through the `ADJUST` instructions with
+/// immediate parameters: `ret` holds the return address, `p<n>` are the
+/// parameters callers pass, `local<n>` the rest of the frame and `out<n>`
+/// the parameters of the next call. The entry point starts with the
+/// relative base at 0, so its relative cells are named by address too.
+/// Cells written into instructions read as `mem[...]`, and control flow
+/// that does not structure as if/else and loops falls back to labels and
+/// `goto`.
+pub fn decompile<C: Number>(program: &[C]) -> String {
+    let analysis = Analysis::new(program);
+    let parameters = parameter_counts(&analysis);
+    let functions: Vec<String> = analysis.functions().into_iter()
+        .map(|entry| {
+            let function = Function::new(&analysis, entry);
+            Emitter::new(&analysis, &function, &parameters).function()
+        })
+        .collect();
+    functions.join("\n")
+}
+
+/// The highest `[rb+n]` written before each call, the number of parameters
+/// the call passes.
+fn arguments<C: Number>(analysis: &Analysis<C>, block: &Block) -> usize {
+    let mut count = 0;
+    for address in &block.instructions {
+        let instruction = analysis.instruction(*address);
+        if instruction.opcode == Opcode::AdjustRelativeBase { count = 0; }
+        if let Some(index) = write_index(instruction.opcode) {
+            let offset = analysis.program()[address + 1 + index].to_i64().unwrap_or(0);
+            if instruction.modes[index] == Mode::Relative && offset > 0 { count = count.max(offset as usize); }
+        }
+    }
+    count
+}
+
+fn parameter_counts<C: Number>(analysis: &Analysis<C>) -> BTreeMap<usize, usize> {
+    let mut counts = BTreeMap::new();
+    for block in analysis.blocks() {
+        if let [Edge::Call { target: Some(target), .. }] = block.successors.as_slice() {
+            let count = counts.entry(*target).or_insert(0);
+            *count = arguments(analysis, block).max(*count);
+        }
+    }
+    counts
+}
+
+fn write_index(opcode: Opcode) -> Option<usize> {
+    opcode.parameters().iter().position(|p| *p == Parameter::Write)
+}
+
+/// The blocks of one function, with what structuring needs to know about
+/// them. Calls are followed to where they return, not into the callee.
+struct Function {
+    entry: usize,
+    successors: BTreeMap<usize, Vec<usize>>,
+    /// How far the function has moved the relative base at each block, None
+    /// once it is moved by a computed amount.
+    depths: BTreeMap<usize, Option<i64>>,
+    dominators: BTreeMap<usize, BTreeSet<usize>>,
+    post_dominators: BTreeMap<usize, BTreeSet<usize>>,
+    predecessors: BTreeMap<usize, Vec<usize>>
+}
+
+impl Function {
+    fn new<C: Number>(analysis: &Analysis<C>, entry: usize) -> Self {
+        let local = |block: &Block| -> Vec<usize> {
+            block.successors.iter()
+                .filter_map(|edge| match edge {
+                    Edge::Next(target) | Edge::Jump(target) => Some(*target),
+                    Edge::Call { returns, .. } => Some(*returns),
+                    Edge::Return | Edge::Computed => None
+                })
+                .filter(|target| analysis.block(*target).is_some())
+                .collect()
+        };
+        let mut function = Function {
+            entry,
+            successors: BTreeMap::new(),
+            depths: BTreeMap::new(),
+            dominators: BTreeMap::new(),
+            post_dominators: BTreeMap::new(),
+            predecessors: BTreeMap::new()
+        };
+        let mut order = Vec::new();
+        let mut exits = BTreeSet::new();
+        let mut work = vec![entry];
+        function.depths.insert(entry, Some(0));
+        while let Some(start) = work.pop() {
+            if function.successors.contains_key(&start) { continue; }
+            let block = analysis.block(start).unwrap();
+            let successors = local(block);
+            if successors.len() < block.successors.len() || block.successors.is_empty() { exits.insert(start); }
+            let depth = block.instructions.iter().fold(function.depths[&start], |depth, address| {
+                let instruction = analysis.instruction(*address);
+                match instruction.opcode {
+                    Opcode::AdjustRelativeBase if instruction.modes[0] == Mode::Immediate => {
+                        depth.zip(analysis.program()[address + 1].to_i64()).map(|(d, v)| d + v)
+                    },
+                    Opcode::AdjustRelativeBase => None,
+                    _ => depth
+                }
+            });
+            for successor in successors.iter().rev() {
+                function.depths.entry(*successor).or_insert(depth);
+                function.predecessors.entry(*successor).or_default().push(start);
+                work.push(*successor);
+            }
+            function.successors.insert(start, successors);
+            order.push(start);
+        }
+        function.dominate(&order);
+        function.post_dominate(&order, &exits);
+        function
+    }
+
+    fn dominate(&mut self, order: &[usize]) {
+        let entry = self.entry;
+        let all: BTreeSet<usize> = order.iter().copied().collect();
+        for block in order {
+            self.dominators.insert(*block, if *block == entry { [*block].into() } else { all.clone() });
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in order.iter().filter(|b| **b != entry) {
+                let mut dominators = intersection(self.predecessors[block].iter().map(|p| &self.dominators[p]));
+                dominators.insert(*block);
+                if dominators != self.dominators[block] {
+                    self.dominators.insert(*block, dominators);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Post-dominators of the blocks that can leave the function, the others
+    /// loop forever and have none.
+    fn post_dominate(&mut self, order: &[usize], exits: &BTreeSet<usize>) {
+        let mut leaving = exits.clone();
+        let mut work: Vec<usize> = exits.iter().copied().collect();
+        while let Some(block) = work.pop() {
+            for predecessor in self.predecessors.get(&block).into_iter().flatten() {
+                if leaving.insert(*predecessor) { work.push(*predecessor); }
+            }
+        }
+        let mut all = leaving.clone();
+        all.insert(EXIT);
+        self.post_dominators.insert(EXIT, [EXIT].into());
+        for block in &leaving {
+            self.post_dominators.insert(*block, all.clone());
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in order.iter().rev().filter(|b| leaving.contains(b)) {
+                let exit = exits.contains(block).then_some(EXIT);
+                let successors = self.successors[block].iter().copied().filter(|s| leaving.contains(s)).chain(exit);
+                let mut post_dominators = intersection(successors.map(|s| &self.post_dominators[&s]));
+                post_dominators.insert(*block);
+                if post_dominators != self.post_dominators[block] {
+                    self.post_dominators.insert(*block, post_dominators);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Where both sides of a branch at the block meet again.
+    fn merge(&self, block: usize) -> Option<usize> {
+        let post_dominators = self.post_dominators.get(&block)?;
+        post_dominators.iter().copied()
+            .filter(|p| *p != block && *p != EXIT)
+            .find(|p| self.post_dominators[p].len() == post_dominators.len() - 1)
+    }
+
+    fn is_header(&self, block: usize) -> bool {
+        self.predecessors.get(&block).into_iter().flatten().any(|p| self.dominators[p].contains(&block))
+    }
+
+    /// The header and every block that reaches one of its back edges
+    /// without going through it.
+    fn loop_body(&self, header: usize) -> BTreeSet<usize> {
+        let mut body: BTreeSet<usize> = [header].into();
+        let mut work: Vec<usize> = self.predecessors[&header].iter().copied()
+            .filter(|p| self.dominators[p].contains(&header))
+            .collect();
+        while let Some(block) = work.pop() {
+            if body.insert(block) {
+                work.extend(self.predecessors.get(&block).into_iter().flatten().copied());
+            }
+        }
+        body
+    }
+}
+
+fn intersection<'a>(mut sets: impl Iterator<Item = &'a BTreeSet<usize>>) -> BTreeSet<usize> {
+    let first = sets.next().cloned().unwrap_or_default();
+    sets.fold(first, |all, set| all.intersection(set).copied().collect())
+}
+
+struct Loop {
+    header: usize,
+    body: BTreeSet<usize>,
+    exit: Option<usize>
+}
+
+/// Writes out one function as nested lines.
+struct Emitter<'a, C> {
+    analysis: &'a Analysis<C>,
+    function: &'a Function,
+    parameters: &'a BTreeMap<usize, usize>,
+    lines: Vec<(usize, String)>,
+    /// The first line written for each block, where its label goes.
+    starts: BTreeMap<usize, usize>,
+    emitted: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+    loops: Vec<Loop>
+}
+
+impl<'a, C: Number> Emitter<'a, C> {
+    fn new(analysis: &'a Analysis<C>, function: &'a Function, parameters: &'a BTreeMap<usize, usize>) -> Self {
+        Emitter {
+            analysis,
+            function,
+            parameters,
+            lines: Vec::new(),
+            starts: BTreeMap::new(),
+            emitted: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+            loops: Vec::new()
+        }
+    }
+
+    fn function(mut self) -> String {
+        let entry = self.function.entry;
+        self.sequence(entry, None, 1);
+        let mut text = match entry {
+            0 => String::from("fn main() {\n"),
+            _ => {
+                let count = self.parameters.get(&entry).copied().unwrap_or(0);
+                let parameters: Vec<String> = (1..=count).map(|n| format!("p{}", n)).collect();
+                format!("fn f{}({}) {{\n", entry, parameters.join(", "))
+            }
+        };
+        let mut labels: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for block in &self.gotos {
+            labels.entry(self.starts[block]).or_default().push(*block);
+        }
+        for (index, (indent, line)) in self.lines.iter().enumerate() {
+            for block in labels.get(&index).into_iter().flatten() {
+                text += &format!("{}L{}:\n", "    ".repeat(indent - 1), block);
+            }
+            text += &format!("{}{}\n", "    ".repeat(*indent), line);
+        }
+        for block in labels.get(&self.lines.len()).into_iter().flatten() {
+            text += &format!("L{}:\n", block);
+        }
+        text + "}\n"
+    }
+
+    fn line(&mut self, indent: usize, line: String) {
+        self.lines.push((indent, line));
+    }
+
+    /// Writes blocks from start on until control reaches follow, leaves the
+    /// function or jumps somewhere already written.
+    fn sequence(&mut self, start: usize, follow: Option<usize>, indent: usize) {
+        let mut next = Some(start);
+        while let Some(block) = next {
+            if Some(block) == follow || self.jump(block, indent) { return; }
+            next = match self.function.is_header(block) {
+                true => self.repeat(block, indent),
+                false => self.block(block, follow, indent)
+            };
+        }
+    }
+
+    /// The statement that gets to a block already written, if it is.
+    fn jump_to(&self, block: usize) -> Option<String> {
+        let innermost = self.loops.last();
+        if innermost.is_some_and(|l| l.header == block) { return Some("continue;".to_string()); }
+        if innermost.is_some_and(|l| l.exit == Some(block)) { return Some("break;".to_string()); }
+        self.emitted.contains(&block).then(|| format!("goto L{};", block))
+    }
+
+    fn jump(&mut self, block: usize, indent: usize) -> bool {
+        match self.jump_to(block) {
+            Some(line) => {
+                if line.starts_with("goto") { self.gotos.insert(block); }
+                self.line(indent, line);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Writes the loop headed by the block, returning where it exits to.
+    fn repeat(&mut self, header: usize, indent: usize) -> Option<usize> {
+        let body = self.function.loop_body(header);
+        let exits: BTreeSet<usize> = body.iter().flat_map(|b| &self.function.successors[b]).copied()
+            .filter(|s| !body.contains(s))
+            .collect();
+        let exit = self.function.merge(header).filter(|m| exits.contains(m)).or_else(|| exits.first().copied());
+        self.line(indent, "loop {".to_string());
+        self.loops.push(Loop { header, body, exit });
+        if let Some(next) = self.block(header, None, indent + 1) {
+            self.sequence(next, None, indent + 1);
+        }
+        self.loops.pop();
+        if self.lines.last().is_some_and(|(i, line)| *i == indent + 1 && line == "continue;") { self.lines.pop(); }
+        self.line(indent, "}".to_string());
+        exit
+    }
+
+    /// Writes one block, returning the block control goes on to.
+    fn block(&mut self, start: usize, follow: Option<usize>, indent: usize) -> Option<usize> {
+        self.emitted.insert(start);
+        self.starts.insert(start, self.lines.len());
+        let block = self.analysis.block(start).unwrap();
+        let mut depth = self.function.depths[&start];
+        // The return address a call stores is implied by the call
+        let skipped = match block.successors.as_slice() {
+            [Edge::Call { .. }] => block.instructions.iter().rev().copied().find(|a| self.writes_return_slot(*a)),
+            _ => None
+        };
+        let last = *block.instructions.last().unwrap();
+        for address in block.instructions.iter().copied().filter(|a| Some(*a) != skipped) {
+            if let Some(line) = self.statement(address, &mut depth) { self.line(indent, line); }
+        }
+        match block.successors.as_slice() {
+            [] => None,
+            [Edge::Next(target)] | [Edge::Jump(target)] => self.go(*target, indent),
+            [Edge::Call { target, returns }] => {
+                let callee = match target {
+                    Some(target) => format!("f{}", target),
+                    None => format!("(*{})", self.operand(last, 1, depth))
+                };
+                let count = arguments(self.analysis, block);
+                let arguments: Vec<String> = (1..=count as i64).map(|n| self.slot(depth, n)).collect();
+                self.line(indent, format!("{}({});", callee, arguments.join(", ")));
+                self.go(*returns, indent)
+            },
+            [Edge::Next(next), Edge::Jump(target)] if self.analysis.block(*target).is_some() => {
+                self.conditional(start, last, depth, (*next, *target), follow, indent)
+            },
+            [Edge::Next(next), edge] => {
+                let condition = self.condition(last, depth, true);
+                self.line(indent, format!("if {} {{", condition));
+                self.leave(edge, last, depth, indent + 1);
+                self.line(indent, "}".to_string());
+                self.go(*next, indent)
+            },
+            [edge] => {
+                self.leave(edge, last, depth, indent);
+                None
+            },
+            _ => unreachable!("an instruction has at most two successors")
+        }
+    }
+
+    /// Control going to a target, unless it does not hold an instruction.
+    fn go(&mut self, target: usize, indent: usize) -> Option<usize> {
+        match self.analysis.block(target) {
+            Some(_) => Some(target),
+            None => {
+                self.line(indent, format!("crash; // no instruction at {}", target));
+                None
+            }
+        }
+    }
+
+    /// Control leaving the function.
+    fn leave(&mut self, edge: &Edge, jump: usize, depth: Option<i64>, indent: usize) {
+        match edge {
+            Edge::Return => self.line(indent, "return;".to_string()),
+            Edge::Computed => {
+                let target = self.operand(jump, 1, depth);
+                self.line(indent, format!("goto *{};", target))
+            },
+            Edge::Next(target) | Edge::Jump(target) => { self.go(*target, indent); },
+            Edge::Call { .. } => unreachable!("calls are unconditional")
+        }
+    }
+
+    fn conditional(&mut self, start: usize, jump: usize, depth: Option<i64>, (next, target): (usize, usize), follow: Option<usize>, indent: usize) -> Option<usize> {
+        let (taken, not_taken) = (self.condition(jump, depth, true), self.condition(jump, depth, false));
+        let innermost = self.loops.last().map(|l| &l.body);
+        let stop = self.function.merge(start)
+            .filter(|m| innermost.is_none_or(|body| body.contains(m)))
+            .or(follow);
+        if target == next {
+            return Some(next);
+        }
+        if Some(target) == stop {
+            self.branch(&not_taken, next, stop, indent);
+            return stop;
+        }
+        if Some(next) == stop {
+            self.branch(&taken, target, stop, indent);
+            return stop;
+        }
+        let continues = |block| self.loops.last().is_some_and(|l| l.header == block);
+        match (self.jump_to(target).is_some(), self.jump_to(next).is_some()) {
+            (true, next_jumps) if !(next_jumps && continues(target)) => {
+                self.branch(&taken, target, None, indent);
+                Some(next)
+            },
+            (_, true) => {
+                self.branch(&not_taken, next, None, indent);
+                Some(target)
+            },
+            _ => {
+                self.line(indent, format!("if {} {{", not_taken));
+                self.sequence(next, stop, indent + 1);
+                self.line(indent, "} else {".to_string());
+                self.sequence(target, stop, indent + 1);
+                self.line(indent, "}".to_string());
+                stop
+            }
+        }
+    }
+
+    fn branch(&mut self, condition: &str, block: usize, stop: Option<usize>, indent: usize) {
+        self.line(indent, format!("if {} {{", condition));
+        self.sequence(block, stop, indent + 1);
+        self.line(indent, "}".to_string());
+    }
+
+    /// The condition under which the jump at address is taken, or not.
+    fn condition(&self, address: usize, depth: Option<i64>, taken: bool) -> String {
+        let value = self.operand(address, 0, depth);
+        let zero = (self.analysis.instruction(address).opcode == Opcode::JumpZero) == taken;
+        format!("{} {} 0", value, if zero { "==" } else { "!=" })
+    }
+
+    fn writes_return_slot(&self, address: usize) -> bool {
+        let instruction = self.analysis.instruction(address);
+        write_index(instruction.opcode).is_some_and(|index| {
+            instruction.modes[index] == Mode::Relative && self.analysis.program()[address + 1 + index].is_zero()
+        })
+    }
+
+    /// The pseudocode for an instruction that is not a jump.
+    fn statement(&self, address: usize, depth: &mut Option<i64>) -> Option<String> {
+        let instruction = self.analysis.instruction(address);
+        let operand = |index| self.operand(address, index, *depth);
+        let line = match instruction.opcode {
+            Opcode::Add => format!("{} = {};", operand(2), add(operand(0), operand(1))),
+            Opcode::Multiply => format!("{} = {};", operand(2), multiply(operand(0), operand(1))),
+            Opcode::StoreLessThan => format!("{} = {} < {};", operand(2), operand(0), operand(1)),
+            Opcode::StoreEqual => format!("{} = {} == {};", operand(2), operand(0), operand(1)),
+            Opcode::Input => format!("{} = input();", operand(0)),
+            Opcode::Output => format!("output({});", operand(0)),
+            Opcode::Halt => "halt;".to_string(),
+            Opcode::Extension(extension) => {
+                let operands: Vec<String> = (0..extension.parameters.len()).map(operand).collect();
+                format!("{}({});", extension.mnemonic.to_lowercase(), operands.join(", "))
+            },
+            Opcode::JumpNotZero | Opcode::JumpZero => return None,
+            Opcode::AdjustRelativeBase => {
+                let value = self.analysis.program()[address + 1].to_i64();
+                return match (instruction.modes[0], value) {
+                    (Mode::Immediate, Some(value)) => {
+                        *depth = depth.map(|d| d + value);
+                        None
+                    },
+                    _ => {
+                        let line = format!("rb += {};", operand(0));
+                        *depth = None;
+                        Some(line)
+                    }
+                };
+            }
+        };
+        Some(line)
+    }
+
+    fn operand(&self, address: usize, index: usize, depth: Option<i64>) -> String {
+        let cell = address + 1 + index;
+        let value = &self.analysis.program()[cell];
+        let mode = self.analysis.instruction(address).modes[index];
+        if self.analysis.is_written(cell) {
+            return match mode {
+                Mode::Position => format!("mem[v{}]", cell),
+                Mode::Immediate => format!("v{}", cell),
+                Mode::Relative => format!("mem[rb + v{}]", cell)
+            };
+        }
+        match (mode, value.to_i64()) {
+            (Mode::Immediate, _) => value.to_string(),
+            (Mode::Position, Some(cell)) if cell >= 0 => format!("v{}", cell),
+            (Mode::Relative, Some(offset)) => self.slot(depth, offset),
+            _ => format!("mem[{}]", value)
+        }
+    }
+
+    /// The name of a relative cell, by its place in the frame.
+    fn slot(&self, depth: Option<i64>, offset: i64) -> String {
+        let depth = match depth {
+            Some(depth) => depth,
+            None if offset < 0 => return format!("mem[rb{}]", offset),
+            None => return format!("mem[rb+{}]", offset)
+        };
+        let slot = depth + offset;
+        // The relative base starts at 0, so the entry point's cells are absolute
+        if self.function.entry == 0 && slot >= 0 {
+            return format!("v{}", slot);
+        }
+        match slot {
+            0 => "ret".to_string(),
+            _ if slot < 0 => format!("caller{}", slot),
+            _ if depth > 0 && slot >= depth => format!("out{}", slot - depth),
+            _ if slot as usize <= self.parameters.get(&self.function.entry).copied().unwrap_or(0) => format!("p{}", slot),
+            _ => format!("local{}", slot)
+        }
+    }
+}
+
+fn add(a: String, b: String) -> String {
+    match (a.as_str(), b.as_str()) {
+        ("0", _) => b,
+        (_, "0") => a,
+        (_, _) if b.starts_with('-') && b[1..].chars().all(|c| c.is_ascii_digit()) => format!("{} - {}", a, &b[1..]),
+        _ => format!("{} + {}", a, b)
+    }
+}
+
+fn multiply(a: String, b: String) -> String {
+    match (a.as_str(), b.as_str()) {
+        ("1", _) => b,
+        (_, "1") => a,
+        ("-1", _) => format!("-{}", b),
+        (_, "-1") => format!("-{}", a),
+        _ => format!("{} * {}", a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntCode;
+
+    fn decompiled(program: &str) -> String {
+        decompile(IntCode::string_to_program(program).unwrap().memory())
+    }
+
+    #[test]
+    fn branches_should_become_if_else() {
+        let expected = "fn main() {
+    v100 = input();
+    if v100 == 0 {
+        output(0);
+    } else {
+        output(1);
+    }
+    halt;
+}
+";
+        assert_eq!(expected, decompiled("3,100,1005,100,10,104,0,1105,1,12,104,1,99"));
+    }
+
+    #[test]
+    fn back_edges_should_become_loops() {
+        let expected = "fn main() {
+    v100 = input();
+    loop {
+        v101 = v100 == 0;
+        if v101 != 0 {
+            break;
+        }
+        output(v100);
+        v100 = v100 - 1;
+    }
+    output(-1);
+    halt;
+}
+";
+        assert_eq!(expected, decompiled("3,100,1008,100,0,101,1005,101,18,4,100,1001,100,-1,100,1105,1,2,104,-1,99"));
+    }
+
+    #[test]
+    fn stack_frames_should_become_functions() {
+        let expected = "fn main() {
+    v101 = 7;
+    f16(v101);
+    output(v101);
+    halt;
+}
+
+fn f16(p1) {
+    p1 = 1 + p1;
+    return;
+}
+";
+        assert_eq!(expected, decompiled("109,100,21101,0,7,1,21101,0,13,0,1105,1,16,204,1,99,109,2,22101,1,-1,-1,109,-2,2105,1,0"));
+    }
+
+    #[test]
+    fn patched_parameters_should_read_the_patched_cell() {
+        assert_eq!("fn main() {\n    v5 = 5;\n    output(v5);\n    halt;\n}\n", decompiled("1101,5,0,5,104,0,99"));
+    }
+
+    #[test]
+    fn puzzle_programs_should_lift_their_functions() {
+        let boost = IntCode::file_to_program("../day9/src/day9.txt").unwrap();
+        let text = decompile(boost.memory());
+        assert!(text.contains("fn f922(p1) {\n    v63 = p1 < 3;\n"));
+
+        let robot = IntCode::file_to_program("../day11/src/day11.txt").unwrap();
+        let text = decompile(robot.memory());
+        assert!(text.contains("fn f556(p1, p2, p3) {"));
+        assert!(text.contains("(*v514)(out1);"));
+        assert_eq!(4, text.matches("fn ").count());
+    }
+}
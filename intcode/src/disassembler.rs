@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::Number;
+use crate::extension::InstructionSet;
+use crate::instruction::{Instruction, Mode, Parameter};
+
+/// One line of a static listing.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement<C> {
+    Instruction { address: usize, instruction: Instruction, parameters: Vec<C> },
+    Data { address: usize, values: Vec<C> }
+}
+
+impl<C> Statement<C> {
+    pub fn address(&self) -> usize {
+        match self {
+            Statement::Instruction { address, .. } | Statement::Data { address, .. } => *address
+        }
+    }
+}
+
+pub fn format_parameter<C: Number>(mode: Mode, value: &C) -> String {
+    match mode {
+        Mode::Position => format!("[{}]", value),
+        Mode::Immediate => format!("#{}", value),
+        Mode::Relative if *value < C::zero() => format!("[rb{}]", value),
+        Mode::Relative => format!("[rb+{}]", value)
+    }
+}
+
+impl<C: Number> fmt::Display for Statement<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Statement::Instruction { address, instruction, parameters } => {
+                let parameters: Vec<String> = instruction.modes.iter().zip(parameters)
+                    .map(|(mode, value)| format_parameter(*mode, value))
+                    .collect();
+                let text = match (instruction.opcode.parameters().last(), parameters.split_last()) {
+                    (Some(Parameter::Write), Some((written, read))) if !read.is_empty() => format!("{} -> {}", read.join(", "), written),
+                    _ => parameters.join(", ")
+                };
+                let line = format!("{:5}: {:15} {}", address, instruction.opcode.mnemonic(), text);
+                f.write_str(line.trim_end())
+            },
+            Statement::Data { address, values } => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "{:5}: {:15} {}", address, ".data", values.join(", "))
+            }
+        }
+    }
+}
+
+pub(crate) fn decode_at<C: Number>(program: &[C], address: usize) -> Option<Instruction> {
+    complete(program, address, Instruction::decode(program[address].to_i64()?)?)
+}
+
+fn complete<C>(program: &[C], address: usize, instruction: Instruction) -> Option<Instruction> {
+    if address + instruction.size() > program.len() { return None; }
+    Some(instruction)
+}
+
+/// Walks the whole program image from address 0 without executing it.
+/// Cells that do not decode as a complete, canonical instruction are
+/// collected into data statements.
+pub fn disassemble<C: Number>(program: &[C]) -> Vec<Statement<C>> {
+    disassemble_with(program, &InstructionSet::new())
+}
+
+/// Like disassemble, decoding the extensions in instructions too.
+pub fn disassemble_with<C: Number>(program: &[C], instructions: &InstructionSet<C>) -> Vec<Statement<C>> {
+    let mut statements = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        let instruction = program[address].to_i64().and_then(|word| instructions.decode(word));
+        match instruction.and_then(|instruction| complete(program, address, instruction)) {
+            Some(instruction) => {
+                let len = instruction.size();
+                let parameters = program[address + 1..address + len].to_vec();
+                statements.push(Statement::Instruction { address, instruction, parameters });
+                address += len;
+            },
+            None => {
+                match statements.last_mut() {
+                    Some(Statement::Data { values, .. }) => values.push(program[address].clone()),
+                    _ => statements.push(Statement::Data { address, values: vec![program[address].clone()] })
+                }
+                address += 1;
+            }
+        }
+    }
+    statements
+}
+
+/// The address-annotated listing of the whole program, one statement per line.
+pub fn listing<C: Number>(program: &[C]) -> String {
+    listing_with(program, &InstructionSet::new())
+}
+
+/// Like listing, with the mnemonics of the extensions in instructions.
+pub fn listing_with<C: Number>(program: &[C], instructions: &InstructionSet<C>) -> String {
+    disassemble_with(program, instructions).iter().map(|s| s.to_string() + "\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntCode;
+
+    #[test]
+    fn listing_should_show_modes_and_data() {
+        let program = IntCode::string_to_program("1,9,10,3,2,3,11,0,99,30,40,50").unwrap().memory().to_vec();
+        let expected = "    0: ADD             [9], [10] -> [3]
+    4: MULTIPLY        [3], [11] -> [0]
+    8: HALT
+    9: .data           30, 40, 50
+";
+        assert_eq!(expected, listing(&program));
+    }
+
+    #[test]
+    fn listing_should_show_immediate_and_relative_parameters() {
+        let program = IntCode::string_to_program("109,1,204,-1,21101,3,4,5,99").unwrap().memory().to_vec();
+        let expected = "    0: ADJUST          #1
+    2: OUTPUT          [rb-1]
+    4: ADD             #3, #4 -> [rb+5]
+    8: HALT
+";
+        assert_eq!(expected, listing(&program));
+    }
+
+    #[test]
+    fn truncated_instruction_at_end_should_be_data() {
+        let program: Vec<i64> = vec![99, 1101, 1];
+        let actual = disassemble(&program);
+
+        assert_eq!(Statement::Data { address: 1, values: vec![1101, 1] }, actual[1]);
+    }
+
+    #[test]
+    fn invalid_mode_should_be_data() {
+        let program: Vec<i64> = vec![11101, 99];
+        let actual = disassemble(&program);
+
+        assert_eq!(Statement::Data { address: 0, values: vec![11101] }, actual[0]);
+        assert_eq!(1, actual[1].address());
+    }
+
+    #[test]
+    fn live_disassembly_should_not_grow_memory() {
+        let p = IntCode::string_to_program("204,1000,99").unwrap();
+        p.disassemble().unwrap();
+
+        assert_eq!(3, p.memory().len());
+    }
+
+    #[test]
+    fn day9_program_should_disassemble_every_cell() {
+        let program = IntCode::file_to_program("../day9/src/day9.txt").unwrap().memory().to_vec();
+        let statements = disassemble(&program);
+        let covered: usize = statements.iter().map(|s| match s {
+            Statement::Instruction { instruction, .. } => instruction.size(),
+            Statement::Data { values, .. } => values.len()
+        }).sum();
+
+        assert_eq!(program.len(), covered);
+    }
+}
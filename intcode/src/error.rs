@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use crate::Cell;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum IntCodeError<C = Cell> {
+    InvalidOpcode { pc: usize, instruction: C },
+    InvalidMode { pc: usize, instruction: C, mode: i64 },
+    WriteInImmediateMode { pc: usize, instruction: C },
+    NegativeAddress { pc: usize, instruction: C, address: C },
+    AddressOutOfRange { pc: usize, instruction: C, address: C },
+    Overflow { pc: usize, instruction: C },
+    MemoryLimit { pc: usize, instruction: C, address: usize, limit: usize },
+    InstructionLimit { pc: usize, instruction: C, limit: u64 },
+    OutputLimit { pc: usize, instruction: C, limit: u64 },
+    TimeLimit { pc: usize, instruction: C, limit: Duration },
+    InputStarvation { pc: usize, instruction: C },
+    Deadlock { blocked: Vec<usize> },
+    SymbolicValue { pc: usize, instruction: C },
+    SearchLimit { what: &'static str, limit: usize },
+    Extension { pc: usize, instruction: C, message: String },
+    InvalidExtension(String),
+    Parse { offset: usize, token: String },
+    NonAscii { offset: usize, character: char },
+    InvalidSnapshot(String),
+    InvalidSession(String),
+    Io(String)
+}
+
+impl<C> IntCodeError<C> {
+    /// True when a limit stopped the machine. It stops before the
+    /// instruction, so it continues where it left off once the limit is
+    /// raised.
+    pub fn is_limit(&self) -> bool {
+        matches!(self, IntCodeError::MemoryLimit { .. } | IntCodeError::InstructionLimit { .. }
+            | IntCodeError::OutputLimit { .. } | IntCodeError::TimeLimit { .. })
+    }
+}
+
+impl<C: fmt::Display> fmt::Display for IntCodeError<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntCodeError::InvalidOpcode { pc, instruction } =>
+                write!(f, "Invalid op-code {} at pc {}", instruction, pc),
+            IntCodeError::InvalidMode { pc, instruction, mode } =>
+                write!(f, "Invalid parameter mode {} in instruction {} at pc {}", mode, instruction, pc),
+            IntCodeError::WriteInImmediateMode { pc, instruction } =>
+                write!(f, "Write parameter in immediate mode in instruction {} at pc {}", instruction, pc),
+            IntCodeError::NegativeAddress { pc, instruction, address } =>
+                write!(f, "Negative address {} in instruction {} at pc {}", address, instruction, pc),
+            IntCodeError::AddressOutOfRange { pc, instruction, address } =>
+                write!(f, "Address {} out of range in instruction {} at pc {}", address, instruction, pc),
+            IntCodeError::Overflow { pc, instruction } =>
+                write!(f, "Arithmetic overflow in instruction {} at pc {}", instruction, pc),
+            IntCodeError::MemoryLimit { pc, instruction, address, limit } =>
+                write!(f, "Memory limit of {} cells reached writing address {} in instruction {} at pc {}", limit, address, instruction, pc),
+            IntCodeError::InstructionLimit { pc, instruction, limit } =>
+                write!(f, "Instruction limit of {} reached before instruction {} at pc {}", limit, instruction, pc),
+            IntCodeError::OutputLimit { pc, instruction, limit } =>
+                write!(f, "Output limit of {} reached before instruction {} at pc {}", limit, instruction, pc),
+            IntCodeError::TimeLimit { pc, instruction, limit } =>
+                write!(f, "Time limit of {:?} reached before instruction {} at pc {}", limit, instruction, pc),
+            IntCodeError::InputStarvation { pc, instruction } =>
+                write!(f, "Not enough input data for instruction {} at pc {}", instruction, pc),
+            IntCodeError::Deadlock { blocked } =>
+                write!(f, "Deadlock, machines {:?} are waiting for input that never comes", blocked),
+            IntCodeError::SymbolicValue { pc, instruction } =>
+                write!(f, "Value computed from symbols has no usable concrete value in instruction {} at pc {}", instruction, pc),
+            IntCodeError::SearchLimit { what, limit } =>
+                write!(f, "Symbolic search gave up after {} {}", limit, what),
+            IntCodeError::Extension { pc, instruction, message } =>
+                write!(f, "{} in instruction {} at pc {}", message, instruction, pc),
+            IntCodeError::InvalidExtension(message) => write!(f, "Invalid extension: {}", message),
+            IntCodeError::Parse { offset, token } =>
+                write!(f, "Invalid program value '{}' at offset {}", token, offset),
+            IntCodeError::NonAscii { offset, character } =>
+                write!(f, "Non-ASCII character '{}' at offset {} in input text", character, offset),
+            IntCodeError::InvalidSnapshot(message) => write!(f, "Invalid snapshot: {}", message),
+            IntCodeError::InvalidSession(message) => write!(f, "Invalid session: {}", message),
+            IntCodeError::Io(message) => write!(f, "I/O error: {}", message)
+        }
+    }
+}
+
+impl<C: fmt::Debug + fmt::Display> Error for IntCodeError<C> {}
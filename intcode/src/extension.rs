@@ -0,0 +1,270 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Cell, IntCodeError, Number};
+use crate::instruction::{Extension, Instruction, Opcode, Parameter, OPCODES};
+
+/// What an extension instruction sees when it executes.
+pub struct Operands<'a, C = Cell> {
+    pub pc: usize,
+    pub relative_base: &'a C,
+    /// The values of the read parameters, in order.
+    pub values: &'a [C]
+}
+
+/// What an extension instruction does. Without a jump the machine moves
+/// on to the next instruction.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Effect<C = Cell> {
+    /// One value for each written parameter, in order.
+    pub writes: Vec<C>,
+    /// Continue at this address instead.
+    pub jump: Option<C>,
+    pub relative_base: Option<C>,
+    pub output: Option<C>
+}
+
+impl<C> Default for Effect<C> {
+    fn default() -> Self {
+        Effect { writes: Vec::new(), jump: None, relative_base: None, output: None }
+    }
+}
+
+/// Executes an extension instruction, an error message fails the machine.
+pub type Execute<C> = dyn Fn(&Operands<C>) -> Result<Effect<C>, String> + Send + Sync;
+
+/// The instructions a machine runs: the standard ones and any registered
+/// extensions, like a modulo or an explicit call.
+///
+/// An extension has an unused opcode, a mnemonic and up to three
+/// parameters. The machine resolves the parameters in their modes as for
+/// any instruction, read parameters to values and written ones to
+/// addresses, and the closure says what to write and where to continue.
+/// The assembler and disassembler take an instruction set to use the
+/// mnemonics, the compiled tier interprets extensions and the static
+/// analyses treat them as invalid.
+#[derive(Clone)]
+pub struct InstructionSet<C = Cell> {
+    extensions: Vec<(Extension, Arc<Execute<C>>)>
+}
+
+impl<C> Default for InstructionSet<C> {
+    fn default() -> Self {
+        InstructionSet { extensions: Vec::new() }
+    }
+}
+
+impl<C> fmt::Debug for InstructionSet<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.extensions.iter().map(|(extension, _)| extension)).finish()
+    }
+}
+
+impl<C: Number> InstructionSet<C> {
+    /// The standard instructions only.
+    pub fn new() -> Self {
+        InstructionSet::default()
+    }
+
+    /// Adds an instruction with an opcode from 1 to 98 that is not taken. The
+    /// mnemonic is made of ASCII letters, digits and underscores.
+    pub fn register(&mut self, code: i64, mnemonic: &'static str, parameters: &'static [Parameter],
+                    execute: impl Fn(&Operands<C>) -> Result<Effect<C>, String> + Send + Sync + 'static) -> Result<Opcode, IntCodeError<C>> {
+        let invalid = |message: String| Err(IntCodeError::InvalidExtension(message));
+        if !(1..=98).contains(&code) { return invalid(format!("opcode {} is not between 1 and 98", code)); }
+        if let Some(opcode) = self.opcode(code) { return invalid(format!("opcode {} is taken by {}", code, opcode)); }
+        if let Some(opcode) = self.from_mnemonic(mnemonic) { return invalid(format!("mnemonic {} is taken by opcode {}", mnemonic, opcode.code())); }
+        if mnemonic.is_empty() || !mnemonic.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return invalid(format!("'{}' cannot be a mnemonic", mnemonic));
+        }
+        if parameters.len() > 3 { return invalid(format!("{} has {} parameters, at most 3 are supported", mnemonic, parameters.len())); }
+        let extension = Extension { code, mnemonic, parameters };
+        self.extensions.push((extension, Arc::new(execute)));
+        Ok(Opcode::Extension(extension))
+    }
+
+    pub fn opcode(&self, code: i64) -> Option<Opcode> {
+        Opcode::from_code(code).or_else(|| self.extension(code).map(|(extension, _)| Opcode::Extension(*extension)))
+    }
+
+    pub fn from_mnemonic(&self, mnemonic: &str) -> Option<Opcode> {
+        Opcode::from_mnemonic(mnemonic).or_else(|| self.extensions.iter()
+            .find(|(extension, _)| extension.mnemonic.eq_ignore_ascii_case(mnemonic))
+            .map(|(extension, _)| Opcode::Extension(*extension)))
+    }
+
+    /// Like Instruction::decode, accepting the extensions.
+    pub fn decode(&self, word: i64) -> Option<Instruction> {
+        Instruction::decode_with(word, |code| self.opcode(code))
+    }
+
+    /// The standard opcodes followed by the extensions.
+    pub fn opcodes(&self) -> Vec<Opcode> {
+        OPCODES.iter().copied().chain(self.extensions.iter().map(|(extension, _)| Opcode::Extension(*extension))).collect()
+    }
+
+    pub(crate) fn extension(&self, code: i64) -> Option<&(Extension, Arc<Execute<C>>)> {
+        self.extensions.iter().find(|(extension, _)| extension.code == code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntCode, IntCodeState, Limits};
+    use crate::assembler;
+    use crate::compiler::{Compiled, CompiledMachine};
+    use crate::disassembler;
+    use crate::instruction::Parameter::{Read, Write};
+    use crate::profile::Profiler;
+    use crate::symbolic::{End, Symbolic};
+    use crate::trace::{RingBuffer, Tracer};
+
+    fn arithmetic() -> InstructionSet {
+        let mut set = InstructionSet::new();
+        set.register(10, "MODULO", &[Read, Read, Write], |op: &Operands| match op.values[1] {
+            0 => Err("Modulo by zero".to_string()),
+            divisor => Ok(Effect { writes: vec![op.values[0].rem_euclid(divisor)], ..Effect::default() })
+        }).unwrap();
+        set.register(11, "DIVIDE", &[Read, Read, Write], |op: &Operands| {
+            let quotient = op.values[0].checked_div(op.values[1]).ok_or("Division by zero")?;
+            Ok(Effect { writes: vec![quotient], ..Effect::default() })
+        }).unwrap();
+        set.register(12, "AND", &[Read, Read, Write], |op| Ok(Effect { writes: vec![op.values[0] & op.values[1]], ..Effect::default() })).unwrap();
+        set
+    }
+
+    /// CALL pushes the return address to the written parameter and jumps,
+    /// RET jumps to the address read.
+    fn calls() -> InstructionSet {
+        let mut set = InstructionSet::new();
+        set.register(20, "CALL", &[Read, Write], |op| Ok(Effect { writes: vec![op.pc as Cell + 3], jump: Some(op.values[0]), ..Effect::default() })).unwrap();
+        set.register(21, "RET", &[Read], |op| Ok(Effect { jump: Some(op.values[0]), ..Effect::default() })).unwrap();
+        set
+    }
+
+    fn machine(source: &str, set: InstructionSet) -> IntCode {
+        let mut machine = IntCode::new(assembler::assemble_with(source, &set).unwrap());
+        machine.set_instruction_set(Arc::new(set));
+        machine
+    }
+
+    #[test]
+    fn should_run_registered_arithmetic() {
+        let source = "
+                    INPUT [value]
+                    MODULO [value], #7 -> [result]
+                    OUTPUT [result]
+                    DIVIDE [value], #7 -> [result]
+                    OUTPUT [result]
+                    AND [value], #12 -> [result]
+                    OUTPUT [result]
+                    HALT
+            value:  .data 0
+            result: .data 0
+        ";
+        let mut p = machine(source, arithmetic());
+        p.add_input(45);
+        assert_eq!(vec![3, 6, 12], p.run_program().unwrap());
+        let mut p = machine("MODULO #1, [zero] -> [zero]\nHALT\nzero: .data 0", arithmetic());
+        assert_eq!(Err(IntCodeError::Extension { pc: 0, instruction: 110, message: "Modulo by zero".to_string() }), p.run_program());
+    }
+
+    #[test]
+    fn should_call_and_return() {
+        let source = "
+                    ADJUST #100
+                    INPUT [rb+1]
+                    CALL #square -> [rb+0]
+                    OUTPUT [rb+1]
+                    HALT
+            square: MULTIPLY [rb+1], [rb+1] -> [rb+1]
+                    RET [rb+0]
+        ";
+        let mut p = machine(source, calls());
+        let program = p.memory().to_vec();
+        p.add_input(12);
+        assert_eq!(vec![144], p.run_program().unwrap());
+
+        // The compiled tier leaves extensions to the interpreter
+        let mut p = machine(source, calls());
+        p.add_input(12);
+        let code = Compiled::new(&p);
+        assert_eq!(vec![144], CompiledMachine::new(&code, p).run_program().unwrap());
+
+        let listing = disassembler::listing_with(&program, &calls());
+        assert!(listing.contains("    4: CALL            #10 -> [rb+0]"), "{}", listing);
+        assert!(listing.contains("   14: RET             [rb+0]"), "{}", listing);
+        assert_eq!(program, assembler::assemble_with::<Cell>(&listing, &calls()).unwrap());
+    }
+
+    #[test]
+    fn unregistered_opcodes_should_stay_invalid() {
+        let source = "1120,5,0,99";
+        let mut p = IntCode::string_to_program(source).unwrap();
+        assert_eq!(Err(IntCodeError::InvalidOpcode { pc: 0, instruction: 1120 }), p.run_program());
+        assert!(assembler::assemble::<Cell>("CALL #5 -> [0]").is_err());
+        assert!(disassembler::listing::<Cell>(&[1120, 5, 0, 99]).contains(".data"));
+    }
+
+    #[test]
+    fn should_show_live_disassembly_of_extensions() {
+        let mut p = machine("CALL #3 -> [rb+0]\nHALT", calls());
+        assert_eq!("    0: CALL            3 -> 0+0 (0)", p.disassemble().unwrap());
+        assert_eq!(Ok(None), p.step());
+        assert_eq!((3, 3), (p.pc(), p.peek(0)));
+        assert_eq!(Ok(Some(IntCodeState::Done)), p.step());
+    }
+
+    #[test]
+    fn traces_profiles_and_symbolic_runs_should_know_extensions() {
+        let source = "ADJUST #100\nCALL #sub -> [rb+0]\nHALT\nsub: RET [rb+0]";
+        let mut tracer = Tracer::new(RingBuffer::new(10));
+        tracer.run_program(&mut machine(source, calls())).unwrap();
+        let mnemonics: Vec<_> = tracer.sink().records().iter().map(|r| r.opcode().unwrap().mnemonic()).collect();
+        assert_eq!(vec!["ADJUST", "CALL", "RET", "HALT"], mnemonics);
+
+        let mut p = machine(source, calls());
+        let mut profiler = Profiler::new(&p);
+        profiler.run_program(&mut p).unwrap();
+        assert!(profiler.to_json().contains("\"CALL\":1,\"RET\":1"), "{}", profiler.to_json());
+        assert_eq!(profiler.steps(), profiler.opcodes().into_iter().map(|op| profiler.opcode(op)).sum::<u64>());
+
+        let paths = Symbolic::new(&machine(source, calls())).explore().unwrap();
+        assert!(matches!(&paths[0].end, End::Error(IntCodeError::Extension { pc: 2, .. })), "{:?}", paths[0].end);
+    }
+
+    #[test]
+    fn outputting_extensions_should_respect_the_output_limit() {
+        let mut set = InstructionSet::new();
+        set.register(40, "SHOW", &[Read], |op| Ok(Effect { output: Some(op.values[0]), ..Effect::default() })).unwrap();
+        let mut p = machine("SHOW #1\nSHOW #2\nHALT", set);
+        p.set_limits(Limits { outputs: Some(1), ..Limits::default() });
+        assert_eq!(Ok(IntCodeState::Output(1)), p.run_slice());
+        assert_eq!(Err(IntCodeError::OutputLimit { pc: 2, instruction: 140, limit: 1 }), p.run_slice());
+
+        // It stopped before the instruction and continues with a fresh budget
+        p.set_limits(Limits { outputs: Some(1), ..Limits::default() });
+        assert_eq!(Ok(vec![2]), p.run_program());
+    }
+
+    #[test]
+    fn should_reject_conflicting_registrations() {
+        let mut set = calls();
+        let ignore = |_: &Operands| Ok(Effect::default());
+        assert!(set.register(1, "PLUS", &[], ignore).is_err());
+        assert!(set.register(99, "STOP", &[], ignore).is_err());
+        assert!(set.register(20, "JSR", &[], ignore).is_err());
+        assert!(set.register(30, "call", &[], ignore).is_err());
+        assert!(set.register(30, "ADD", &[], ignore).is_err());
+        assert!(set.register(100, "BIG", &[], ignore).is_err());
+        assert!(set.register(30, "WIDE", &[Read, Read, Read, Write], ignore).is_err());
+        assert!(set.register(30, ".data", &[], ignore).is_err());
+        for mnemonic in ["", "A-B", "X[1]", "ONE TWO", "#IMM", "CAFÉ"] {
+            assert!(set.register(30, mnemonic, &[], ignore).is_err(), "{}", mnemonic);
+        }
+        assert!(set.register(31, "SET_2", &[], ignore).is_ok());
+        assert_eq!(Some(Opcode::Extension(Extension { code: 30, mnemonic: "NOP", parameters: &[] })), set.register(30, "NOP", &[], ignore).ok());
+        assert_eq!(14, set.opcodes().len());
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number};
+use crate::compiler;
+use crate::instruction::{Instruction, Mode, Opcode, Parameter, OPCODES};
+use crate::symbolic::{self, End};
+
+/// Cells after the code that the generated programs read and write.
+const DATA: usize = 8;
+
+/// A program and the input it is given.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Case {
+    pub program: Vec<Cell>,
+    pub input: Vec<Cell>
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "program {}", join(&self.program))?;
+        write!(f, "input {}", join(&self.input))
+    }
+}
+
+/// How a run stopped.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Ending {
+    Halted,
+    NeedInput,
+    StepLimit,
+    /// An arithmetic result did not fit in a cell.
+    Overflow,
+    Error(String)
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ending::Halted => f.write_str("halt"),
+            Ending::NeedInput => f.write_str("waiting for input"),
+            Ending::StepLimit => f.write_str("step limit"),
+            Ending::Overflow => f.write_str("overflow"),
+            Ending::Error(message) => f.write_str(message)
+        }
+    }
+}
+
+fn ending<C: Number>(error: IntCodeError<C>) -> Ending {
+    match error {
+        IntCodeError::Overflow { .. } => Ending::Overflow,
+        error => Ending::Error(error.to_string())
+    }
+}
+
+/// What a backend did with a case. Values are kept as text so that
+/// backends with different cell types can be compared.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Outcome {
+    pub outputs: Vec<String>,
+    pub ending: Ending,
+    /// Instructions executed.
+    pub steps: usize,
+    /// Every non-zero cell by address.
+    pub memory: BTreeMap<usize, String>,
+    /// The pc and relative base, for backends that have them.
+    pub registers: Option<(usize, String)>
+}
+
+impl Outcome {
+    /// The first way other differs from this outcome.
+    pub fn difference(&self, other: &Outcome) -> Option<String> {
+        if self.outputs != other.outputs {
+            return Some(format!("outputs {} instead of {}", join(&other.outputs), join(&self.outputs)));
+        }
+        if self.ending != other.ending {
+            return Some(format!("ends with {} instead of {}", other.ending, self.ending));
+        }
+        if self.steps != other.steps {
+            return Some(format!("stops after {} steps instead of {}", other.steps, self.steps));
+        }
+        if let (Some(expected), Some(actual)) = (&self.registers, &other.registers) {
+            if expected != actual {
+                return Some(format!("stops at pc {} with relative base {} instead of pc {} with relative base {}",
+                                    actual.0, actual.1, expected.0, expected.1));
+            }
+        }
+        let zero = "0".to_string();
+        self.memory.keys().chain(other.memory.keys())
+            .map(|address| (address, self.memory.get(address).unwrap_or(&zero), other.memory.get(address).unwrap_or(&zero)))
+            .filter(|(_, expected, actual)| expected != actual)
+            .min_by_key(|(address, _, _)| **address)
+            .map(|(address, expected, actual)| format!("cell {} is {} instead of {}", address, actual, expected))
+    }
+}
+
+/// One way of running programs.
+pub trait Backend {
+    fn name(&self) -> &str;
+
+    /// True when cells are wider than Cell, so that a program overflowing
+    /// on the other backends runs on.
+    fn wide(&self) -> bool {
+        false
+    }
+
+    /// Runs case for at most max_steps instructions.
+    fn run(&self, case: &Case, max_steps: usize) -> Outcome;
+}
+
+fn load<C: Number>(case: &Case) -> IntCode<C> {
+    let mut machine = IntCode::from_program(case.program.iter().map(|v| C::from_i64(*v)).collect());
+    case.input.iter().for_each(|v| machine.add_input(C::from_i64(*v)));
+    machine
+}
+
+/// Steps until the machine stops or has executed max_steps instructions.
+fn execute<C: Number>(max_steps: usize, mut step: impl FnMut() -> Result<Option<IntCodeState<C>>, IntCodeError<C>>) -> (Vec<String>, Ending, usize) {
+    let mut outputs = Vec::new();
+    let mut steps = 0;
+    let ending = loop {
+        if steps == max_steps { break Ending::StepLimit; }
+        match step() {
+            Ok(None) => {},
+            Ok(Some(IntCodeState::Output(value))) => outputs.push(value.to_string()),
+            Ok(Some(IntCodeState::NeedInput)) => break Ending::NeedInput,
+            Ok(Some(IntCodeState::Done)) => break Ending::Halted,
+            Err(error) => break ending(error)
+        }
+        steps += 1;
+    };
+    (outputs, ending, steps)
+}
+
+fn outcome<C: Number>(machine: &IntCode<C>, (outputs, ending, steps): (Vec<String>, Ending, usize)) -> Outcome {
+    let snapshot = machine.snapshot();
+    let pages = snapshot.pages.iter().flat_map(|(start, cells)| cells.iter().enumerate().map(move |(offset, v)| (start + offset, v)));
+    let memory = snapshot.memory.iter().enumerate().chain(pages)
+        .filter(|(_, v)| !v.is_zero())
+        .map(|(address, v)| (address, v.to_string()))
+        .collect();
+    Outcome { outputs, ending, steps, memory, registers: Some((snapshot.pc, snapshot.relative_base.to_string())) }
+}
+
+/// The interpreter, with or without its decode cache.
+pub struct Interpreted {
+    pub decode_cache: bool
+}
+
+impl Backend for Interpreted {
+    fn name(&self) -> &str {
+        if self.decode_cache { "decode cache" } else { "interpreter" }
+    }
+
+    fn run(&self, case: &Case, max_steps: usize) -> Outcome {
+        let mut machine = load::<Cell>(case);
+        machine.set_decode_cache(self.decode_cache);
+        let result = execute(max_steps, || machine.step());
+        outcome(&machine, result)
+    }
+}
+
+/// The compiled tier.
+pub struct Compiled;
+
+impl Backend for Compiled {
+    fn name(&self) -> &str {
+        "compiled"
+    }
+
+    fn run(&self, case: &Case, max_steps: usize) -> Outcome {
+        let machine = load::<Cell>(case);
+        let code = compiler::Compiled::new(&machine);
+        let mut compiled = compiler::CompiledMachine::new(&code, machine);
+        let result = execute(max_steps, || compiled.step());
+        outcome(compiled.machine(), result)
+    }
+}
+
+/// The interpreter with a wider cell type.
+pub struct Wide<C> {
+    name: &'static str,
+    cells: PhantomData<C>
+}
+
+impl<C: Number> Wide<C> {
+    pub fn new(name: &'static str) -> Self {
+        Wide { name, cells: PhantomData }
+    }
+}
+
+impl<C: Number> Backend for Wide<C> {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn wide(&self) -> bool {
+        true
+    }
+
+    fn run(&self, case: &Case, max_steps: usize) -> Outcome {
+        let mut machine = load::<C>(case);
+        let result = execute(max_steps, || machine.step());
+        outcome(&machine, result)
+    }
+}
+
+/// The symbolic executor without any symbols, which follows a single path.
+pub struct Symbolic;
+
+impl Backend for Symbolic {
+    fn name(&self) -> &str {
+        "symbolic"
+    }
+
+    fn run(&self, case: &Case, max_steps: usize) -> Outcome {
+        let mut symbolic = symbolic::Symbolic::new(&load::<Cell>(case));
+        symbolic.set_max_steps(max_steps);
+        let failed = |message: String| Outcome { outputs: Vec::new(), ending: Ending::Error(message), steps: 0, memory: BTreeMap::new(), registers: None };
+        let path = match symbolic.explore() {
+            Ok(mut paths) if paths.len() == 1 => paths.remove(0),
+            Ok(paths) => return failed(format!("{} paths without symbols", paths.len())),
+            Err(error) => return failed(error.to_string())
+        };
+        let ending = match path.end.clone() {
+            End::Halted => Ending::Halted,
+            End::NeedInput => Ending::NeedInput,
+            End::StepLimit => Ending::StepLimit,
+            End::Error(error) => ending(error)
+        };
+        let image = (0..case.program.len()).map(|address| (address, path.cell(address)));
+        let memory = image.chain(path.memory().beyond().map(|(address, v)| (address, v.clone())))
+            .filter(|(_, v)| v.constant().is_none_or(|v| !v.is_zero()))
+            .map(|(address, v)| (address, v.to_string()))
+            .collect();
+        Outcome { outputs: path.outputs.iter().map(|v| v.to_string()).collect(), ending, steps: path.steps, memory, registers: None }
+    }
+}
+
+/// Every backend in the crate, the reference interpreter first.
+pub fn backends() -> Vec<Box<dyn Backend>> {
+    #[allow(unused_mut)]
+    let mut backends: Vec<Box<dyn Backend>> = vec![
+        Box::new(Interpreted { decode_cache: false }),
+        Box::new(Interpreted { decode_cache: true }),
+        Box::new(Compiled),
+        Box::new(Symbolic),
+        Box::new(Wide::<i128>::new("i128"))
+    ];
+    #[cfg(feature = "bigint")]
+    backends.push(Box::new(Wide::<num_bigint::BigInt>::new("bigint")));
+    backends
+}
+
+/// A backend that does not agree with the reference on a case.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Divergence {
+    pub case: Case,
+    pub reference: String,
+    pub backend: String,
+    pub difference: String
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} differs from {}: {}", self.backend, self.reference, self.difference)?;
+        write!(f, "{}", self.case)
+    }
+}
+
+/// SplitMix64, seeded so that a run can be repeated.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+
+    /// Mostly small values, sometimes one large enough for a few
+    /// multiplications to overflow.
+    fn value(&mut self) -> Cell {
+        match self.below(10) {
+            0 => self.range(-(1 << 40), 1 << 40),
+            _ => self.range(-20, 20)
+        }
+    }
+}
+
+/// Generates random programs, runs them on every backend and compares what
+/// they did: outputs, how they stopped, registers and memory.
+///
+/// Generated programs are sequences of valid instructions followed by a few
+/// data cells. Jumps mostly go to instruction starts and writes mostly go
+/// to the data, but they can also land in the code, so programs loop,
+/// modify themselves and fail in every way the machine can. Runs are cut
+/// off after a step limit.
+///
+/// A program that overflows is compared on the wide backends up to the
+/// instruction that overflows.
+pub struct Fuzzer {
+    rng: Rng,
+    backends: Vec<Box<dyn Backend>>,
+    max_steps: usize
+}
+
+impl Fuzzer {
+    pub fn new(seed: u64) -> Self {
+        Fuzzer { rng: Rng(seed), backends: backends(), max_steps: 1000 }
+    }
+
+    /// Replaces the backends, the first one is the reference.
+    pub fn set_backends(&mut self, backends: Vec<Box<dyn Backend>>) {
+        self.backends = backends;
+    }
+
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+    }
+
+    /// A random program and input.
+    pub fn generate(&mut self) -> Case {
+        let rng = &mut self.rng;
+        let count = 1 + rng.below(24);
+        let opcodes: Vec<Opcode> = (0..count)
+            .map(|i| match (i + 1 == count && rng.below(5) > 0) || rng.below(20) == 0 {
+                true => Opcode::Halt,
+                false => OPCODES[rng.below(OPCODES.len() - 1)]
+            })
+            .collect();
+        let starts: Vec<usize> = opcodes.iter().scan(0, |pc, opcode| { *pc += opcode.size(); Some(*pc - opcode.size()) }).collect();
+        let code: usize = opcodes.iter().map(|opcode| opcode.size()).sum();
+        let mut program = Vec::with_capacity(code + DATA);
+        for opcode in opcodes {
+            let modes: Vec<Mode> = opcode.parameters().iter()
+                .map(|parameter| match (parameter, rng.below(3) as i64) {
+                    (Parameter::Write, 1) => Mode::Position,
+                    (_, digit) => Mode::from_digit(digit).unwrap()
+                })
+                .collect();
+            program.push(Instruction { opcode, modes: modes.clone() }.encode());
+            for (index, mode) in modes.into_iter().enumerate() {
+                program.push(match (mode, opcode) {
+                    (Mode::Position, _) if rng.below(4) == 0 => rng.below(code + DATA) as Cell,
+                    (Mode::Position, _) => (code + rng.below(DATA)) as Cell,
+                    (Mode::Relative, _) => rng.range(-2, 12),
+                    (Mode::Immediate, Opcode::JumpNotZero) | (Mode::Immediate, Opcode::JumpZero) if index == 1 => starts[rng.below(starts.len())] as Cell,
+                    (Mode::Immediate, Opcode::AdjustRelativeBase) => rng.range(-3, 10),
+                    (Mode::Immediate, _) => rng.value()
+                });
+            }
+        }
+        program.extend((0..DATA).map(|_| rng.value()));
+        let input = (0..rng.below(4)).map(|_| rng.range(-5, 20)).collect();
+        Case { program, input }
+    }
+
+    /// The first backend that does not agree with the reference on case.
+    pub fn check(&self, case: &Case) -> Option<Divergence> {
+        let (reference, others) = self.backends.split_first()?;
+        let expected = reference.run(case, self.max_steps);
+        let prefix = match expected.ending {
+            Ending::Overflow => Some(reference.run(case, expected.steps)),
+            _ => None
+        };
+        others.iter().find_map(|backend| {
+            let (expected, max_steps) = match &prefix {
+                Some(prefix) if backend.wide() => (prefix, expected.steps),
+                _ => (&expected, self.max_steps)
+            };
+            expected.difference(&backend.run(case, max_steps)).map(|difference| Divergence {
+                case: case.clone(),
+                reference: reference.name().to_string(),
+                backend: backend.name().to_string(),
+                difference
+            })
+        })
+    }
+
+    /// Smaller variants of case: with inputs, runs of cells or single cells
+    /// removed, or with values closer to zero.
+    fn candidates(case: &Case) -> Vec<Case> {
+        let mut candidates = Vec::new();
+        for i in 0..case.input.len() {
+            let mut input = case.input.clone();
+            input.remove(i);
+            candidates.push(Case { program: case.program.clone(), input });
+        }
+        let mut size = case.program.len() / 2;
+        while size > 0 {
+            for start in (0..case.program.len() - size + 1).step_by(size) {
+                let mut program = case.program.clone();
+                program.drain(start..start + size);
+                candidates.push(Case { program, input: case.input.clone() });
+            }
+            size /= 2;
+        }
+        let smaller = |v: Cell| vec![0, v / 2].into_iter().filter(move |s| *s != v).collect::<Vec<_>>();
+        for (i, v) in case.program.iter().enumerate() {
+            for s in smaller(*v) {
+                let mut program = case.program.clone();
+                program[i] = s;
+                candidates.push(Case { program, input: case.input.clone() });
+            }
+        }
+        for (i, v) in case.input.iter().enumerate() {
+            for s in smaller(*v) {
+                let mut input = case.input.clone();
+                input[i] = s;
+                candidates.push(Case { program: case.program.clone(), input });
+            }
+        }
+        candidates.dedup();
+        candidates
+    }
+
+    /// Shrinks the case of a divergence for as long as some backend still
+    /// disagrees with the reference. Every step makes the case shorter or
+    /// its values closer to zero, so it ends.
+    pub fn minimize(&self, divergence: Divergence) -> Divergence {
+        let mut smallest = divergence;
+        while let Some(smaller) = Fuzzer::candidates(&smallest.case).iter().find_map(|case| self.check(case)) {
+            smallest = smaller;
+        }
+        smallest
+    }
+
+    /// Checks random cases and gives the first divergence, minimized.
+    pub fn run(&mut self, iterations: usize) -> Option<Divergence> {
+        for _ in 0..iterations {
+            let case = self.generate();
+            if let Some(divergence) = self.check(&case) {
+                return Some(self.minimize(divergence));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_programs_should_be_valid() {
+        let mut fuzzer = Fuzzer::new(7);
+        for _ in 0..200 {
+            let case = fuzzer.generate();
+            let mut pc = 0;
+            while pc < case.program.len() - DATA {
+                let instruction = Instruction::decode(case.program[pc]).unwrap();
+                pc += instruction.size();
+            }
+            assert_eq!(case.program.len() - DATA, pc);
+        }
+    }
+
+    #[test]
+    fn backends_should_agree() {
+        let mut fuzzer = Fuzzer::new(2019);
+        if let Some(divergence) = fuzzer.run(2000) {
+            panic!("{}", divergence);
+        }
+    }
+
+    /// The interpreter, except that it drops the sign of negative outputs.
+    struct Unsigned;
+
+    impl Backend for Unsigned {
+        fn name(&self) -> &str {
+            "unsigned"
+        }
+
+        fn run(&self, case: &Case, max_steps: usize) -> Outcome {
+            let mut outcome = Interpreted { decode_cache: false }.run(case, max_steps);
+            outcome.outputs.iter_mut().for_each(|v| *v = v.trim_start_matches('-').to_string());
+            outcome
+        }
+    }
+
+    #[test]
+    fn divergence_should_be_minimized() {
+        let mut fuzzer = Fuzzer::new(1);
+        fuzzer.set_backends(vec![Box::new(Interpreted { decode_cache: false }), Box::new(Unsigned)]);
+        let divergence = fuzzer.run(1000).unwrap();
+        assert_eq!("unsigned", divergence.backend);
+        assert!(divergence.case.program.len() <= 3, "{}", divergence);
+        assert!(divergence.case.input.len() <= 1, "{}", divergence);
+        assert_eq!(Some(divergence.clone()), fuzzer.check(&divergence.case));
+    }
+
+    #[test]
+    fn overflow_should_be_compared_up_to_the_overflowing_instruction() {
+        // Squares a cell until it no longer fits, the wide backend keeps going
+        let case = Case { program: vec![2, 9, 9, 9, 1105, 1, 0, 99, 0, 3], input: vec![] };
+        let fuzzer = Fuzzer::new(0);
+        let narrow = Interpreted { decode_cache: false }.run(&case, 1000);
+        assert_eq!(Ending::Overflow, narrow.ending);
+        assert!(Wide::<i128>::new("i128").run(&case, 1000).steps > narrow.steps);
+        assert_eq!(None, fuzzer.check(&case));
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::VecDeque;
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number, Observer, Snapshot};
+
+/// How a single step changed the machine, enough to undo and redo it.
+#[derive(Debug, Clone)]
+struct Delta<C> {
+    pc: usize,
+    next_pc: usize,
+    relative_base: C,
+    next_relative_base: C,
+    writes: Vec<(usize, C, C)>,
+    input: Option<C>,
+    /// Made by History::poke rather than by executing an instruction.
+    poke: bool
+}
+
+/// A full snapshot followed by the deltas of the steps taken after it.
+#[derive(Debug, Clone)]
+struct Segment<C> {
+    start: u64,
+    snapshot: Snapshot<C>,
+    deltas: Vec<Delta<C>>
+}
+
+impl<C> Segment<C> {
+    fn end(&self) -> u64 {
+        self.start + self.deltas.len() as u64
+    }
+}
+
+/// Collects the writes and input of one step and forwards everything to
+/// the caller's observer.
+struct Recorder<'a, C, O> {
+    writes: Vec<(usize, C, C)>,
+    input: Option<C>,
+    observer: &'a mut O
+}
+
+impl<'a, C: Number, O: Observer<C>> Observer<C> for Recorder<'a, C, O> {
+    fn instruction(&mut self, pc: usize, instruction: &C) {
+        self.observer.instruction(pc, instruction);
+    }
+
+    fn parameter(&mut self, value: &C) {
+        self.observer.parameter(value);
+    }
+
+    fn read(&mut self, address: usize, value: &C) {
+        self.observer.read(address, value);
+    }
+
+    fn write(&mut self, address: usize, old: &C, new: &C) {
+        self.writes.push((address, old.clone(), new.clone()));
+        self.observer.write(address, old, new);
+    }
+
+    fn input(&mut self, value: &C) {
+        self.input = Some(value.clone());
+        self.observer.input(value);
+    }
+
+    fn output(&mut self, value: &C) {
+        self.observer.output(value);
+    }
+}
+
+/// Runs a machine while keeping an undo log, so that it can be stepped
+/// backwards. The log is split into segments of `interval` steps that each
+/// start with a full snapshot. Only the last `max_segments` segments are
+/// kept, which bounds memory use and how far back the machine can go.
+///
+/// Steps that halt or wait for input change nothing and are not recorded.
+/// Writes made with poke are recorded as steps of their own.
+/// Going back does not take back output already produced. Stepping forward
+/// from the past executes the instruction again and discards the recorded
+/// future.
+#[derive(Debug, Clone)]
+pub struct History<C = Cell> {
+    machine: IntCode<C>,
+    segments: VecDeque<Segment<C>>,
+    position: u64,
+    interval: usize,
+    max_segments: usize
+}
+
+impl<C: Number> History<C> {
+    pub fn new(machine: IntCode<C>) -> Self {
+        History::with_limits(machine, 1000, 100)
+    }
+
+    pub fn with_limits(machine: IntCode<C>, interval: usize, max_segments: usize) -> Self {
+        History { machine, segments: VecDeque::new(), position: 0, interval: interval.max(1), max_segments: max_segments.max(1) }
+    }
+
+    pub fn machine(&self) -> &IntCode<C> {
+        &self.machine
+    }
+
+    /// Gives direct access to the machine. Changes made through it cannot
+    /// be undone, so the recorded history is dropped.
+    pub fn machine_mut(&mut self) -> &mut IntCode<C> {
+        self.segments.clear();
+        &mut self.machine
+    }
+
+    /// Writes value to address like IntCode::poke, recorded as a step so
+    /// that going back undoes it.
+    pub fn poke(&mut self, address: usize, value: C) {
+        if self.position < self.end() { self.truncate(); }
+        let snapshot = self.next_snapshot();
+        let old = self.machine.peek(address);
+        self.machine.poke(address, value.clone());
+        let pc = self.machine.pc();
+        let relative_base = self.machine.relative_base().clone();
+        self.record(snapshot, Delta {
+            pc, next_pc: pc,
+            relative_base: relative_base.clone(), next_relative_base: relative_base,
+            writes: vec![(address, old, value)], input: None, poke: true
+        });
+    }
+
+    pub fn add_input(&mut self, input: C) {
+        self.machine.add_input(input);
+    }
+
+    /// Number of steps recorded since the start, counting those dropped.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The earliest position the machine can go back to.
+    pub fn earliest(&self) -> u64 {
+        self.segments.front().map_or(self.position, |s| s.start)
+    }
+
+    fn end(&self) -> u64 {
+        self.segments.back().map_or(self.position, |s| s.end())
+    }
+
+    pub fn step(&mut self) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        self.step_with(&mut ())
+    }
+
+    /// Like IntCode::step_with, recording the step.
+    pub fn step_with(&mut self, observer: &mut impl Observer<C>) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        if self.position < self.end() { self.truncate(); }
+        let snapshot = self.next_snapshot();
+        let pc = self.machine.pc();
+        let relative_base = self.machine.relative_base().clone();
+        let mut recorder = Recorder { writes: Vec::new(), input: None, observer };
+        let state = self.machine.step_with(&mut recorder)?;
+        if let Some(IntCodeState::Done) | Some(IntCodeState::NeedInput) = state { return Ok(state); }
+
+        let next_pc = self.machine.pc();
+        let next_relative_base = self.machine.relative_base().clone();
+        self.record(snapshot, Delta {
+            pc, next_pc, relative_base, next_relative_base,
+            writes: recorder.writes, input: recorder.input, poke: false
+        });
+        Ok(state)
+    }
+
+    /// The snapshot to start a new segment with, taken before the step
+    /// that will be recorded next, if the current segment is full.
+    fn next_snapshot(&self) -> Option<Snapshot<C>> {
+        match self.segments.back() {
+            Some(segment) if segment.deltas.len() < self.interval => None,
+            _ => Some(self.machine.snapshot())
+        }
+    }
+
+    fn record(&mut self, snapshot: Option<Snapshot<C>>, delta: Delta<C>) {
+        if let Some(snapshot) = snapshot {
+            self.segments.push_back(Segment { start: self.position, snapshot, deltas: Vec::new() });
+            if self.segments.len() > self.max_segments { self.segments.pop_front(); }
+        }
+        self.segments.back_mut().unwrap().deltas.push(delta);
+        self.position += 1;
+    }
+
+    pub fn run_slice(&mut self) -> Result<IntCodeState<C>, IntCodeError<C>> {
+        loop {
+            if let Some(state) = self.step()? { return Ok(state); }
+        }
+    }
+
+    /// Drops everything recorded after the current position.
+    fn truncate(&mut self) {
+        let position = self.position;
+        while self.segments.back().is_some_and(|s| s.start >= position) {
+            self.segments.pop_back();
+        }
+        if let Some(segment) = self.segments.back_mut() {
+            segment.deltas.truncate((position - segment.start) as usize);
+        }
+    }
+
+    fn delta(&self, step: u64) -> &Delta<C> {
+        let segment = self.segments.iter().rev().find(|s| s.start <= step && step < s.end()).unwrap();
+        &segment.deltas[(step - segment.start) as usize]
+    }
+
+    /// Moves to a recorded position. Nearby positions are reached by undoing
+    /// or redoing single steps, distant ones from the closest snapshot.
+    /// Returns false, without moving, if the position is not recorded.
+    pub fn go_to(&mut self, target: u64) -> bool {
+        if target < self.earliest() || target > self.end() { return false; }
+        if target + (self.interval as u64) < self.position {
+            let segment = self.segments.iter().rev().find(|s| s.start <= target).unwrap();
+            let mut input: VecDeque<C> = (segment.start..self.position)
+                .filter_map(|step| self.delta(step).input.clone())
+                .collect();
+            input.extend(self.machine.pending_input().iter().cloned());
+            let mut snapshot = segment.snapshot.clone();
+            snapshot.input = input.into();
+            self.position = segment.start;
+            self.machine.restore_from(snapshot);
+        }
+        while self.position > target {
+            let delta = self.delta(self.position - 1).clone();
+            undo(&mut self.machine, &delta);
+            self.position -= 1;
+        }
+        while self.position < target {
+            let delta = self.delta(self.position).clone();
+            redo(&mut self.machine, &delta);
+            self.position += 1;
+        }
+        true
+    }
+
+    /// Undoes up to n steps and returns how many were undone.
+    pub fn step_back(&mut self, n: usize) -> usize {
+        let target = self.position.saturating_sub(n as u64).max(self.earliest());
+        let steps = self.position - target;
+        self.go_to(target);
+        steps as usize
+    }
+
+    /// Goes back to the most recent step that executed the instruction at
+    /// pc, leaving the machine about to execute it again. Returns false,
+    /// without moving, if no such step is recorded.
+    pub fn run_back_to(&mut self, pc: usize) -> bool {
+        let found = (self.earliest()..self.position).rev().find(|step| {
+            let delta = self.delta(*step);
+            !delta.poke && delta.pc == pc
+        });
+        match found {
+            Some(step) => self.go_to(step),
+            None => false
+        }
+    }
+}
+
+fn undo<C: Number>(machine: &mut IntCode<C>, delta: &Delta<C>) {
+    for (address, old, _) in delta.writes.iter().rev() {
+        machine.poke(*address, old.clone());
+        machine.release_memory(*address);
+    }
+    machine.set_registers(delta.pc, delta.relative_base.clone());
+    if let Some(input) = &delta.input { machine.input_mut().push_front(input.clone()); }
+}
+
+fn redo<C: Number>(machine: &mut IntCode<C>, delta: &Delta<C>) {
+    for (address, _, new) in &delta.writes {
+        machine.poke(*address, new.clone());
+    }
+    machine.set_registers(delta.next_pc, delta.next_relative_base.clone());
+    if delta.input.is_some() { machine.input_mut().pop_front(); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::{assembler, Limits};
+    use crate::extension::{Effect, InstructionSet, Operands};
+    use crate::instruction::Parameter;
+
+    // Reads a number, counts it down to zero, outputs each value and halts.
+    const COUNTDOWN: &str = "3,100,4,100,1001,100,-1,100,1005,100,2,99";
+
+    fn countdown(interval: usize, max_segments: usize) -> History {
+        let mut p = IntCode::string_to_program(COUNTDOWN).unwrap();
+        p.add_input(5);
+        History::with_limits(p, interval, max_segments)
+    }
+
+    fn run_to_halt(h: &mut History) -> Vec<Cell> {
+        let mut output = Vec::new();
+        while let IntCodeState::Output(value) = h.run_slice().unwrap() {
+            output.push(value);
+        }
+        output
+    }
+
+    #[test]
+    fn step_back_should_restore_earlier_states() {
+        let mut h = countdown(1000, 10);
+        let mut states = vec![h.machine().snapshot()];
+        while h.step().unwrap() != Some(IntCodeState::Done) {
+            states.push(h.machine().snapshot());
+        }
+
+        for expected in states.iter().rev().skip(1) {
+            assert_eq!(1, h.step_back(1));
+            assert_eq!(*expected, h.machine().snapshot());
+        }
+        assert_eq!(0, h.step_back(1));
+    }
+
+    #[test]
+    fn going_back_should_return_consumed_input() {
+        let mut h = countdown(1000, 10);
+        h.step().unwrap();
+        assert!(h.machine().pending_input().is_empty());
+
+        h.step_back(1);
+        assert_eq!(Some(&5), h.machine().pending_input().front());
+    }
+
+    #[test]
+    fn distant_jumps_should_use_snapshots() {
+        let mut reference = countdown(1000, 10);
+        run_to_halt(&mut reference);
+        let mut h = countdown(3, 100);
+        run_to_halt(&mut h);
+        let end = h.position();
+
+        for target in (0..end).rev() {
+            assert!(reference.go_to(target));
+            assert!(h.go_to(end));
+            assert!(h.go_to(target));
+            assert_eq!(reference.machine().snapshot(), h.machine().snapshot());
+        }
+    }
+
+    #[test]
+    fn distant_jumps_should_keep_the_instruction_set() {
+        let mut set = InstructionSet::new();
+        set.register(50, "DEC", &[Parameter::Read, Parameter::Write], |op: &Operands| Ok(Effect { writes: vec![op.values[0] - 1], ..Effect::default() })).unwrap();
+        let source = "
+                    INPUT [n]
+            loop:   OUTPUT [n]
+                    DEC [n] -> [n]
+                    JUMP_NOT_ZERO [n], #loop
+                    HALT
+            n:      .data 0
+        ";
+        let mut p = IntCode::new(assembler::assemble_with(source, &set).unwrap());
+        p.set_instruction_set(Arc::new(set));
+        p.add_input(4);
+        let mut h = History::with_limits(p, 2, 100);
+        assert_eq!(vec![4, 3, 2, 1], run_to_halt(&mut h));
+
+        assert!(h.go_to(1));
+        assert!(h.machine().instruction_set().is_some());
+        assert_eq!(vec![4, 3, 2, 1], run_to_halt(&mut h));
+    }
+
+    #[test]
+    fn distant_jumps_should_keep_the_limits() {
+        let mut h = countdown(2, 100);
+        h.machine_mut().set_limits(Limits { instructions: Some(6), ..Limits::default() });
+        let mut steps = 0;
+        while h.step().is_ok() { steps += 1; }
+        assert_eq!(6, steps);
+
+        assert!(h.go_to(0));
+        assert!(matches!(h.step(), Err(IntCodeError::InstructionLimit { limit: 6, .. })));
+    }
+
+    #[test]
+    fn run_back_to_should_stop_at_the_last_execution_of_pc() {
+        let mut h = countdown(1000, 10);
+        run_to_halt(&mut h);
+
+        assert!(h.run_back_to(2));
+        assert_eq!(2, h.machine().pc());
+        assert_eq!(1, h.machine().peek(100));
+        assert!(h.run_back_to(2));
+        assert_eq!(2, h.machine().peek(100));
+        assert!(!h.run_back_to(50));
+    }
+
+    #[test]
+    fn going_back_should_undo_pokes() {
+        let mut h = countdown(2, 10);
+        h.step().unwrap();
+        h.poke(100, 3);
+        h.poke(200, 7);
+        assert_eq!(vec![3, 2, 1], run_to_halt(&mut h));
+
+        assert!(h.run_back_to(2));
+        assert_eq!(7, h.machine().peek(200));
+        assert!(h.go_to(2));
+        assert_eq!(3, h.machine().peek(100));
+        assert_eq!(0, h.machine().peek(200));
+        assert_eq!(1, h.step_back(1));
+        assert_eq!(5, h.machine().peek(100));
+        assert!(h.go_to(3));
+        assert!(!h.run_back_to(2));
+    }
+
+    #[test]
+    fn old_segments_should_be_dropped() {
+        let mut h = countdown(4, 2);
+        run_to_halt(&mut h);
+
+        assert!(h.position() - h.earliest() <= 8);
+        assert_eq!(h.position() - h.earliest(), h.step_back(100) as u64);
+        assert!(!h.go_to(0));
+    }
+
+    #[test]
+    fn stepping_after_going_back_should_replace_the_future() {
+        let mut h = countdown(2, 10);
+        run_to_halt(&mut h);
+        h.go_to(1);
+        h.machine_mut().poke(100, 1);
+        assert_eq!(1, h.earliest());
+
+        assert_eq!(vec![1], run_to_halt(&mut h));
+        h.step_back(2);
+        assert_eq!(1, h.machine().peek(100));
+    }
+}
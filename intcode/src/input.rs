@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, StdinLock};
+
+use crate::{IntCode, IntCodeError, IntCodeState, Number};
+
+/// Where a machine gets input from once its own queue is empty, see
+/// IntCode::run_slice_from.
+pub trait InputProvider<C> {
+    /// The next value, or None if there is none yet and the machine should
+    /// wait for input as without a provider.
+    fn next_input(&mut self) -> Result<Option<C>, IntCodeError<C>>;
+}
+
+/// Runs a slice with run_slice, asking the provider for input whenever
+/// the machine waits for it. Shared by the runners' run_slice_from.
+pub(crate) fn run_slice_from<C: Number>(machine: &mut IntCode<C>, input: &mut impl InputProvider<C>,
+                                mut run_slice: impl FnMut(&mut IntCode<C>) -> Result<IntCodeState<C>, IntCodeError<C>>) -> Result<IntCodeState<C>, IntCodeError<C>> {
+    loop {
+        match run_slice(machine)? {
+            IntCodeState::NeedInput => match input.next_input()? {
+                Some(value) => machine.add_input(value),
+                None => return Ok(IntCodeState::NeedInput)
+            },
+            state => return Ok(state)
+        }
+    }
+}
+
+/// A fixed queue of values.
+impl<C> InputProvider<C> for VecDeque<C> {
+    fn next_input(&mut self) -> Result<Option<C>, IntCodeError<C>> {
+        Ok(self.pop_front())
+    }
+}
+
+/// Asks the closure for each value when the program reads it, like a
+/// camera looking at the panel under the robot.
+pub struct Callback<F>(pub F);
+
+impl<C, F: FnMut() -> Option<C>> InputProvider<C> for Callback<F> {
+    fn next_input(&mut self) -> Result<Option<C>, IntCodeError<C>> {
+        Ok((self.0)())
+    }
+}
+
+/// Always the same value, for programs that poll with -1 meaning no input.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DefaultValue<C>(pub C);
+
+impl<C: Clone> InputProvider<C> for DefaultValue<C> {
+    fn next_input(&mut self) -> Result<Option<C>, IntCodeError<C>> {
+        Ok(Some(self.0.clone()))
+    }
+}
+
+/// Values read as they are needed, separated by commas or white space.
+/// None at the end of the input.
+pub struct Reader<R> {
+    reader: R,
+    pending: VecDeque<(usize, String)>,
+    offset: usize
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Reader { reader, pending: VecDeque::new(), offset: 0 }
+    }
+
+    fn next_token(&mut self) -> io::Result<Option<(usize, String)>> {
+        while self.pending.is_empty() {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 { return Ok(None); }
+            let start = self.offset;
+            self.offset += line.len();
+            let mut token_start = 0;
+            for (i, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+                if c == ',' || c.is_whitespace() {
+                    if i > token_start { self.pending.push_back((start + token_start, line[token_start..i].to_string())); }
+                    token_start = i + c.len_utf8();
+                }
+            }
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+impl Reader<StdinLock<'static>> {
+    pub fn stdin() -> Self {
+        Reader::new(io::stdin().lock())
+    }
+}
+
+impl Reader<BufReader<File>> {
+    /// Replays the values in a file.
+    pub fn open(file_name: &str) -> io::Result<Self> {
+        Ok(Reader::new(BufReader::new(File::open(file_name)?)))
+    }
+}
+
+impl<C: Number, R: BufRead> InputProvider<C> for Reader<R> {
+    fn next_input(&mut self) -> Result<Option<C>, IntCodeError<C>> {
+        match self.next_token().map_err(|e| IntCodeError::Io(e.to_string()))? {
+            None => Ok(None),
+            Some((offset, token)) => token.parse().map(Some).map_err(|_| IntCodeError::Parse { offset, token })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cell;
+
+    /// Echoes each input doubled until it reads 0.
+    const DOUBLER: &str = "3,20,1006,20,14,1002,20,2,20,4,20,1105,1,0,99";
+
+    #[test]
+    fn queue_should_come_after_the_machines_own_input() {
+        let mut p = IntCode::string_to_program(DOUBLER).unwrap();
+        p.add_input(1);
+        let mut queue: VecDeque<Cell> = vec![2, 3].into();
+        assert_eq!(Ok(IntCodeState::Output(2)), p.run_slice_from(&mut queue));
+        assert_eq!(Ok(IntCodeState::Output(4)), p.run_slice_from(&mut queue));
+        assert_eq!(Ok(IntCodeState::Output(6)), p.run_slice_from(&mut queue));
+        assert_eq!(Ok(IntCodeState::NeedInput), p.run_slice_from(&mut queue));
+        queue.push_back(0);
+        assert_eq!(Ok(vec![]), p.run_program_from(&mut queue));
+    }
+
+    #[test]
+    fn callback_should_be_asked_on_demand() {
+        let mut p = IntCode::string_to_program(DOUBLER).unwrap();
+        let mut asked = 0;
+        let outputs = p.run_program_from(&mut Callback(|| {
+            asked += 1;
+            Some(if asked < 4 { asked * 10 } else { 0 })
+        }));
+        assert_eq!(Ok(vec![20, 40, 60]), outputs);
+        assert_eq!(4, asked);
+
+        let mut p = IntCode::string_to_program(DOUBLER).unwrap();
+        let actual = p.run_program_from(&mut Callback(|| None));
+        assert_eq!(Err(IntCodeError::InputStarvation { pc: 0, instruction: 3 }), actual);
+    }
+
+    #[test]
+    fn default_value_should_never_run_out() {
+        // Adds the inputs until it reads -1
+        let mut p = IntCode::string_to_program("3,30,1008,30,-1,31,1005,31,16,1,30,32,32,1105,1,0,4,32,99").unwrap();
+        p.add_input(5);
+        p.add_input(7);
+        assert_eq!(Ok(vec![12]), p.run_program_from(&mut DefaultValue(-1)));
+    }
+
+    #[test]
+    fn reader_should_parse_commas_and_white_space() {
+        let mut reader = Reader::new("1, 2\n\n  3,,-4\r\n5".as_bytes());
+        let mut values: Vec<Cell> = Vec::new();
+        while let Some(value) = reader.next_input().unwrap() { values.push(value); }
+        assert_eq!(vec![1, 2, 3, -4, 5], values);
+
+        let mut reader = Reader::new("1\n2 x3".as_bytes());
+        assert_eq!(Ok(Some(1 as Cell)), reader.next_input());
+        assert_eq!(Ok(Some(2 as Cell)), reader.next_input());
+        assert_eq!(Err(IntCodeError::<Cell>::Parse { offset: 4, token: "x3".to_string() }), reader.next_input());
+    }
+
+    #[test]
+    fn should_replay_input_from_a_file() {
+        let file_name = std::env::temp_dir().join(format!("intcode-input-test-{}", std::process::id()));
+        std::fs::write(&file_name, "4\n5\n6\n0\n").unwrap();
+        let mut replay = Reader::open(file_name.to_str().unwrap()).unwrap();
+        let mut p = IntCode::string_to_program(DOUBLER).unwrap();
+        assert_eq!(Ok(vec![8, 10, 12]), p.run_program_from(&mut replay));
+        std::fs::remove_file(&file_name).unwrap();
+        assert!(Reader::open(file_name.to_str().unwrap()).is_err());
+    }
+}
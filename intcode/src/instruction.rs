@@ -0,0 +1,206 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Opcode {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpNotZero,
+    JumpZero,
+    StoreLessThan,
+    StoreEqual,
+    AdjustRelativeBase,
+    Halt,
+    /// An instruction registered in an InstructionSet.
+    Extension(Extension)
+}
+
+/// The shape of a registered instruction, see InstructionSet.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Extension {
+    pub code: i64,
+    pub mnemonic: &'static str,
+    pub parameters: &'static [Parameter]
+}
+
+/// How an instruction uses one of its parameters.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Parameter {
+    Read,
+    Write
+}
+
+use Parameter::{Read, Write};
+
+pub const OPCODES: [Opcode; 10] = [
+    Opcode::Add, Opcode::Multiply, Opcode::Input, Opcode::Output, Opcode::JumpNotZero,
+    Opcode::JumpZero, Opcode::StoreLessThan, Opcode::StoreEqual, Opcode::AdjustRelativeBase, Opcode::Halt];
+
+impl Opcode {
+    pub fn from_code(code: i64) -> Option<Self> {
+        OPCODES.iter().copied().find(|op| op.code() == code)
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        OPCODES.iter().copied().find(|op| op.mnemonic().eq_ignore_ascii_case(mnemonic))
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Multiply => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpNotZero => 5,
+            Opcode::JumpZero => 6,
+            Opcode::StoreLessThan => 7,
+            Opcode::StoreEqual => 8,
+            Opcode::AdjustRelativeBase => 9,
+            Opcode::Halt => 99,
+            Opcode::Extension(extension) => extension.code
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "ADD",
+            Opcode::Multiply => "MULTIPLY",
+            Opcode::Input => "INPUT",
+            Opcode::Output => "OUTPUT",
+            Opcode::JumpNotZero => "JUMP_NOT_ZERO",
+            Opcode::JumpZero => "JUMP_ZERO",
+            Opcode::StoreLessThan => "STORE_LESS_THAN",
+            Opcode::StoreEqual => "STORE_EQUAL",
+            Opcode::AdjustRelativeBase => "ADJUST",
+            Opcode::Halt => "HALT",
+            Opcode::Extension(extension) => extension.mnemonic
+        }
+    }
+
+    pub fn parameters(self) -> &'static [Parameter] {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::StoreLessThan | Opcode::StoreEqual => &[Read, Read, Write],
+            Opcode::Input => &[Write],
+            Opcode::Output | Opcode::AdjustRelativeBase => &[Read],
+            Opcode::JumpNotZero | Opcode::JumpZero => &[Read, Read],
+            Opcode::Halt => &[],
+            Opcode::Extension(extension) => extension.parameters
+        }
+    }
+
+    /// Number of cells the instruction occupies, including the instruction itself.
+    pub fn size(self) -> usize {
+        1 + self.parameters().len()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative
+}
+
+impl Mode {
+    pub fn from_digit(digit: i64) -> Option<Self> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None
+        }
+    }
+
+    pub fn digit(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2
+        }
+    }
+}
+
+/// A decoded instruction word: the opcode and one mode per parameter.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub modes: Vec<Mode>
+}
+
+impl Instruction {
+    /// Decodes an instruction word. Only canonical encodings are accepted:
+    /// a valid opcode, a valid mode for every parameter, no immediate mode
+    /// for written parameters and no mode digits beyond the last parameter.
+    pub fn decode(word: i64) -> Option<Self> {
+        Instruction::decode_with(word, Opcode::from_code)
+    }
+
+    /// Like decode, looking up opcodes with opcode.
+    pub(crate) fn decode_with(word: i64, opcode: impl Fn(i64) -> Option<Opcode>) -> Option<Self> {
+        if word < 0 { return None; }
+        let opcode = opcode(word % 100)?;
+        let mut digits = word / 100;
+        let mut modes = Vec::new();
+        for parameter in opcode.parameters() {
+            let mode = Mode::from_digit(digits % 10)?;
+            if *parameter == Write && mode == Mode::Immediate { return None; }
+            modes.push(mode);
+            digits /= 10;
+        }
+        if digits != 0 { return None; }
+        Some(Instruction { opcode, modes })
+    }
+
+    pub fn encode(&self) -> i64 {
+        self.modes.iter().rev().fold(0, |acc, mode| acc * 10 + mode.digit()) * 100 + self.opcode.code()
+    }
+
+    pub fn size(&self) -> usize {
+        self.opcode.size()
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_should_split_opcode_and_modes() {
+        let actual = Instruction::decode(1002).unwrap();
+
+        assert_eq!(Opcode::Multiply, actual.opcode);
+        assert_eq!(vec![Mode::Position, Mode::Immediate, Mode::Position], actual.modes);
+    }
+
+    #[test]
+    fn decode_should_reject_non_canonical_words() {
+        assert_eq!(None, Instruction::decode(42));
+        assert_eq!(None, Instruction::decode(-1));
+        assert_eq!(None, Instruction::decode(304));
+        assert_eq!(None, Instruction::decode(11101));
+        assert_eq!(None, Instruction::decode(10099));
+        assert_eq!(None, Instruction::decode(1203));
+    }
+
+    #[test]
+    fn encode_should_reverse_decode() {
+        for word in &[1, 2, 3, 203, 4, 104, 204, 1105, 2206, 21107, 1008, 109, 99] {
+            assert_eq!(*word, Instruction::decode(*word).unwrap().encode());
+        }
+    }
+
+    #[test]
+    fn mnemonics_should_round_trip() {
+        for op in OPCODES.iter() {
+            assert_eq!(Some(*op), Opcode::from_mnemonic(op.mnemonic()));
+            assert_eq!(Some(*op), Opcode::from_code(op.code()));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+mod error;
+mod limits;
+mod machine;
+mod memory;
+mod number;
+mod observer;
+mod regression_tests;
+mod snapshot;
+
+pub mod analysis;
+pub mod ascii;
+pub mod assembler;
+pub mod channels;
+pub mod compiler;
+pub mod debugger;
+pub mod decompiler;
+pub mod disassembler;
+pub mod extension;
+pub mod fuzz;
+pub mod history;
+pub mod input;
+pub mod instruction;
+pub mod network;
+pub mod profile;
+pub mod session;
+pub mod symbolic;
+pub mod topology;
+pub mod trace;
+
+pub use error::*;
+pub use limits::{Budget, Limits};
+pub use machine::*;
+pub use memory::{MemoryStats, PAGE_SIZE};
+pub use number::*;
+pub use observer::*;
+pub use snapshot::*;
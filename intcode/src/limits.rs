@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use crate::IntCodeState;
+
+/// The clock is read before every this many instructions.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// Resource limits for a run, see IntCode::set_limits. A limit of None
+/// is no limit.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Limits {
+    pub instructions: Option<u64>,
+    /// Memory cells as counted by MemoryStats, the same as
+    /// IntCode::set_memory_limit.
+    pub memory: Option<usize>,
+    pub outputs: Option<u64>,
+    /// Wall-clock time from when the limits are set. It is checked every
+    /// 1024 instructions, so a run can go a little over.
+    pub time: Option<Duration>
+}
+
+/// What a run has used of its limits since they were set.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Budget {
+    pub limits: Limits,
+    pub instructions: u64,
+    pub outputs: u64,
+    pub started: Instant
+}
+
+/// The limit that stops the next instruction.
+pub(crate) enum Exhausted {
+    Instructions(u64),
+    Outputs(u64),
+    Time(Duration)
+}
+
+impl Budget {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Budget { limits, instructions: 0, outputs: 0, started: Instant::now() }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Checks whether the next instruction, an output or not, may execute.
+    pub(crate) fn exhausted(&self, output: bool) -> Option<Exhausted> {
+        if let Some(limit) = self.limits.instructions.filter(|limit| self.instructions >= *limit) {
+            return Some(Exhausted::Instructions(limit));
+        }
+        if let Some(limit) = self.limits.outputs.filter(|limit| output && self.outputs >= *limit) {
+            return Some(Exhausted::Outputs(limit));
+        }
+        match self.limits.time {
+            Some(limit) if self.instructions.is_multiple_of(CLOCK_INTERVAL) && self.elapsed() >= limit => Some(Exhausted::Time(limit)),
+            _ => None
+        }
+    }
+
+    /// Counts an instruction that executed. One waiting for input did not.
+    pub(crate) fn record<C>(&mut self, state: &Option<IntCodeState<C>>) {
+        match state {
+            Some(IntCodeState::NeedInput) => {},
+            Some(IntCodeState::Output(_)) => {
+                self.instructions += 1;
+                self.outputs += 1;
+            },
+            _ => self.instructions += 1
+        }
+    }
+}
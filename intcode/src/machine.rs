@@ -0,0 +1,935 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::{Budget, IntCodeError, Limits, MemoryStats, Number, Observer, Overflow, Snapshot};
+use crate::extension::{InstructionSet, Operands};
+use crate::input::{self, InputProvider};
+use crate::instruction::{Opcode, Parameter};
+use crate::limits::Exhausted;
+use crate::memory::Memory;
+
+pub type Cell = i64;
+const ADD: i64 = 1;
+const MULTIPLY: i64 = 2;
+const INPUT: i64 = 3;
+const OUTPUT: i64 = 4;
+const JUMP_NOT_ZERO: i64 = 5;
+const JUMP_ZERO: i64 = 6;
+const STORE_LESS_THAN: i64 = 7;
+const STORE_EQUAL: i64 = 8;
+const ADJUST_RELATIVE_BASE: i64 = 9;
+const HALT: i64 = 99;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum IntCodeState<C = Cell> {
+    Done,
+    Output(C),
+    NeedInput
+}
+
+/// The opcode and parameter modes of an instruction word, small enough to
+/// keep one per memory cell.
+#[derive(Debug, Clone, Copy)]
+struct Decoded {
+    opcode: i8,
+    modes: [i8; 3]
+}
+
+impl Decoded {
+    fn of<C: Number>(word: &C) -> Self {
+        // An instruction too large for an i64 can never be valid, -1 makes it fall through to the error
+        match word.to_i64() {
+            Some(i) => Decoded {
+                opcode: (i % 100) as i8,
+                modes: [(i / 100 % 10) as i8, (i / 1000 % 10) as i8, (i / 10000 % 10) as i8]
+            },
+            None => Decoded { opcode: -1, modes: [-1; 3] }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IntCode<C = Cell> {
+    memory: Memory<C>,
+    pc: usize,
+    input: VecDeque<C>,
+    relative_base: C,
+    overflow: Overflow,
+    /// Decoded instructions by address, an entry is dropped when its cell is written.
+    decoded: Vec<Option<Decoded>>,
+    use_cache: bool,
+    extensions: Option<Arc<InstructionSet<C>>>,
+    budget: Option<Budget>
+}
+
+impl IntCode {
+    pub fn new(program: Vec<Cell>) -> Self {
+        IntCode::from_program(program)
+    }
+
+    pub fn file_to_program(file_name: &str) -> Result<Self, IntCodeError> {
+        IntCode::from_file(file_name)
+    }
+
+    pub fn string_to_program(buf: &str) -> Result<Self, IntCodeError> {
+        IntCode::from_string(buf)
+    }
+}
+
+impl<C: Number> IntCode<C> {
+    pub fn from_program(program: Vec<C>) -> Self {
+        // Decoding the whole image up front means clones of a loaded program start with a warm cache
+        let decoded = program.iter().map(|word| Some(Decoded::of(word))).collect();
+        IntCode {
+            memory: Memory::new(program), pc: 0, input: VecDeque::new(), relative_base: C::zero(), overflow: Overflow::default(),
+            decoded, use_cache: true, extensions: None, budget: None
+        }
+    }
+
+    pub fn from_file(file_name: &str) -> Result<Self, IntCodeError<C>> {
+        let mut buf = String::new();
+        File::open(file_name)
+            .and_then(|mut file| file.read_to_string(&mut buf))
+            .map_err(|e| IntCodeError::Io(format!("{}: {}", file_name, e)))?;
+        IntCode::from_string(&buf)
+    }
+
+    pub fn from_string(buf: &str) -> Result<Self, IntCodeError<C>> {
+        let mut program = Vec::new();
+        let mut offset = 0;
+        for token in buf.split_terminator(',') {
+            match token.trim().parse() {
+                Ok(value) => program.push(value),
+                Err(_) => return Err(IntCodeError::Parse { offset, token: token.to_string() })
+            }
+            offset += token.len() + 1;
+        }
+        Ok(IntCode::from_program(program))
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot<C>) -> Self {
+        let mut memory = Memory::new(snapshot.memory);
+        // The first page may overlap the end of the image, those cells are unused
+        for (start, cells) in snapshot.pages {
+            for (offset, value) in cells.into_iter().enumerate() {
+                if let Some(address) = start.checked_add(offset).filter(|address| *address >= memory.image().len()) {
+                    memory.set(address, value, false);
+                }
+            }
+        }
+        IntCode {
+            memory,
+            pc: snapshot.pc,
+            input: snapshot.input.into(),
+            relative_base: snapshot.relative_base,
+            overflow: snapshot.overflow,
+            decoded: Vec::new(),
+            use_cache: true,
+            extensions: None,
+            budget: None
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot<C> {
+        Snapshot {
+            memory: self.memory.image().to_vec(),
+            pages: self.memory.pages().into_iter().map(|(start, cells)| (start, cells.to_vec())).collect(),
+            pc: self.pc,
+            relative_base: self.relative_base.clone(),
+            input: self.input.iter().cloned().collect(),
+            overflow: self.overflow
+        }
+    }
+
+    /// Puts the machine in the state of the snapshot. Unlike from_snapshot
+    /// it keeps the machine's configuration: the decode cache setting, the
+    /// instruction set and the limits with what is used of them.
+    pub fn restore_from(&mut self, snapshot: Snapshot<C>) {
+        let mut restored = IntCode::from_snapshot(snapshot);
+        restored.use_cache = self.use_cache;
+        restored.extensions = self.extensions.take();
+        restored.set_memory_limit(self.memory_limit());
+        restored.budget = self.budget.take();
+        *self = restored;
+    }
+
+    /// Turns the decoded instruction cache on or off, it is on by default.
+    /// Without it every instruction word is decoded each time it executes.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.use_cache = enabled;
+        self.decoded.clear();
+    }
+
+    /// Lets the machine run the extensions registered in instructions.
+    /// Snapshots do not include them.
+    pub fn set_instruction_set(&mut self, instructions: Arc<InstructionSet<C>>) {
+        self.extensions = Some(instructions);
+    }
+
+    pub fn instruction_set(&self) -> Option<&InstructionSet<C>> {
+        self.extensions.as_deref()
+    }
+
+    /// The opcode with code, including extensions.
+    pub(crate) fn opcode_of(&self, code: i64) -> Option<Opcode> {
+        match &self.extensions {
+            Some(extensions) => extensions.opcode(code),
+            None => Opcode::from_code(code)
+        }
+    }
+
+    /// Selects what ADD and MULTIPLY do on overflow. Address and relative base
+    /// arithmetic always traps.
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    pub fn add_input(&mut self, input: C) {
+        self.input.push_back(input);
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.opcode() == HALT
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> &C {
+        &self.relative_base
+    }
+
+    pub fn pending_input(&self) -> &VecDeque<C> {
+        &self.input
+    }
+
+    pub(crate) fn input_mut(&mut self) -> &mut VecDeque<C> {
+        &mut self.input
+    }
+
+    /// The loaded image. Cells beyond it live in sparse pages, use peek to
+    /// read them.
+    pub fn memory(&self) -> &[C] {
+        self.memory.image()
+    }
+
+    pub fn memory_stats(&self) -> MemoryStats {
+        self.memory.stats()
+    }
+
+    /// Limits the cells the program may allocate, image included. A write
+    /// that needs more fails with IntCodeError::MemoryLimit. There is no
+    /// limit by default.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory.set_limit(limit);
+    }
+
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory.limit()
+    }
+
+    /// Limits the run from here on, with nothing used yet. An exhausted
+    /// limit fails the machine before the instruction that would exceed
+    /// it, see IntCodeError::is_limit, and setting the limits again starts
+    /// a fresh budget to continue with. Snapshots do not include them.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.set_memory_limit(limits.memory);
+        self.budget = match limits {
+            Limits { instructions: None, outputs: None, time: None, .. } => None,
+            _ => Some(Budget::new(limits))
+        };
+    }
+
+    /// What the run has used since the limits were set, if any are.
+    pub fn budget(&self) -> Option<&Budget> {
+        self.budget.as_ref()
+    }
+
+    /// Fails if a limit does not let the next instruction, an output or
+    /// not, execute. An input that will wait uses nothing and is let
+    /// through, so the machine reports NeedInput rather than a limit.
+    pub(crate) fn admit(&self, output: bool) -> Result<(), IntCodeError<C>> {
+        if self.input.is_empty() && self.opcode() == INPUT { return Ok(()); }
+        let exhausted = match self.budget.as_ref().and_then(|budget| budget.exhausted(output)) {
+            None => return Ok(()),
+            Some(exhausted) => exhausted
+        };
+        let (pc, instruction) = (self.pc, self.instruction());
+        Err(match exhausted {
+            Exhausted::Instructions(limit) => IntCodeError::InstructionLimit { pc, instruction, limit },
+            Exhausted::Outputs(limit) => IntCodeError::OutputLimit { pc, instruction, limit },
+            Exhausted::Time(limit) => IntCodeError::TimeLimit { pc, instruction, limit }
+        })
+    }
+
+    pub(crate) fn spend(&mut self, state: &Option<IntCodeState<C>>) {
+        if let Some(budget) = &mut self.budget { budget.record(state); }
+    }
+
+    pub(crate) fn release_memory(&mut self, address: usize) {
+        self.memory.release(address);
+    }
+
+    pub(crate) fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    pub(crate) fn set_registers(&mut self, pc: usize, relative_base: C) {
+        self.pc = pc;
+        self.relative_base = relative_base;
+    }
+
+    fn instruction(&self) -> C {
+        self.peek(self.pc)
+    }
+
+    fn decoded(&self) -> Decoded {
+        match self.decoded.get(self.pc) {
+            Some(Some(decoded)) => *decoded,
+            _ => Decoded::of(&self.instruction())
+        }
+    }
+
+    /// Only instructions in the image are cached.
+    fn cache_instruction(&mut self) {
+        let image = self.memory.image();
+        if self.pc >= image.len() { return; }
+        if self.decoded.len() < image.len() { self.decoded.resize(image.len(), None); }
+        if self.decoded[self.pc].is_none() { self.decoded[self.pc] = Some(Decoded::of(&image[self.pc])); }
+    }
+
+    fn opcode(&self) -> i64 {
+        self.decoded().opcode as i64
+    }
+
+    /// Reads memory, unallocated cells read as zero.
+    pub fn peek(&self, absolute_pos: usize) -> C {
+        self.memory.get(absolute_pos)
+    }
+
+    fn mode(&self, pos: usize) -> i64 {
+        self.decoded().modes[pos - 1] as i64
+    }
+
+    fn invalid_opcode(&self) -> IntCodeError<C> {
+        IntCodeError::InvalidOpcode { pc: self.pc, instruction: self.instruction() }
+    }
+
+    fn invalid_mode(&self, pos: usize) -> IntCodeError<C> {
+        IntCodeError::InvalidMode { pc: self.pc, instruction: self.instruction(), mode: self.mode(pos) }
+    }
+
+    pub(crate) fn overflow_error(&self) -> IntCodeError<C> {
+        IntCodeError::Overflow { pc: self.pc, instruction: self.instruction() }
+    }
+
+    pub(crate) fn address(&self, address: C) -> Result<usize, IntCodeError<C>> {
+        if address < C::zero() {
+            return Err(IntCodeError::NegativeAddress { pc: self.pc, instruction: self.instruction(), address });
+        }
+        match address.to_i64() {
+            Some(a) if a as u64 <= usize::MAX as u64 => Ok(a as usize),
+            _ => Err(IntCodeError::AddressOutOfRange { pc: self.pc, instruction: self.instruction(), address })
+        }
+    }
+
+    pub(crate) fn relative_address(&self, offset: &C) -> Result<usize, IntCodeError<C>> {
+        let address = offset.checked_add(&self.relative_base).ok_or_else(|| self.overflow_error())?;
+        self.address(address)
+    }
+
+    pub(crate) fn add(&self, a: &C, b: &C) -> Result<C, IntCodeError<C>> {
+        match self.overflow {
+            Overflow::Trap => a.checked_add(b).ok_or_else(|| self.overflow_error()),
+            Overflow::Wrap => Ok(a.wrapping_add(b)),
+            Overflow::Saturate => Ok(a.saturating_add(b))
+        }
+    }
+
+    pub(crate) fn multiply(&self, a: &C, b: &C) -> Result<C, IntCodeError<C>> {
+        match self.overflow {
+            Overflow::Trap => a.checked_mul(b).ok_or_else(|| self.overflow_error()),
+            Overflow::Wrap => Ok(a.wrapping_mul(b)),
+            Overflow::Saturate => Ok(a.saturating_mul(b))
+        }
+    }
+
+    fn read(&self, address: usize, observer: &mut impl Observer<C>) -> C {
+        let value = self.peek(address);
+        observer.read(address, &value);
+        value
+    }
+
+    pub(crate) fn write(&mut self, address: usize, value: C, observer: &mut impl Observer<C>) -> Result<(), IntCodeError<C>> {
+        let old = self.peek(address);
+        if !self.memory.set(address, value.clone(), true) {
+            let limit = self.memory.limit().unwrap_or_default();
+            return Err(IntCodeError::MemoryLimit { pc: self.pc, instruction: self.instruction(), address, limit });
+        }
+        if let Some(decoded) = self.decoded.get_mut(address) { *decoded = None; }
+        observer.write(address, &old, &value);
+        Ok(())
+    }
+
+    fn p(&self, pos: usize, observer: &mut impl Observer<C>) -> Result<C, IntCodeError<C>> {
+        let immediate = self.peek(self.pc + pos);
+        let result = match self.mode(pos) {
+            0 => {
+                let address = self.address(immediate)?;
+                self.read(address, observer)
+            },
+            1 => immediate,
+            2 => {
+                let address = self.relative_address(&immediate)?;
+                self.read(address, observer)
+            },
+            _ => return Err(self.invalid_mode(pos))
+        };
+        observer.parameter(&result);
+        Ok(result)
+    }
+
+    fn p_w(&self, pos: usize) -> Result<usize, IntCodeError<C>> {
+        let immediate = self.peek(self.pc + pos);
+        match self.mode(pos) {
+            0 => self.address(immediate),
+            1 => Err(IntCodeError::WriteInImmediateMode { pc: self.pc, instruction: self.instruction() }),
+            2 => self.relative_address(&immediate),
+            _ => Err(self.invalid_mode(pos))
+        }
+    }
+
+    /// Writes memory, ignoring the memory limit.
+    pub fn poke(&mut self, pos: usize, value: C) {
+        self.memory.set(pos, value, false);
+        if let Some(decoded) = self.decoded.get_mut(pos) { *decoded = None; }
+    }
+
+    fn bool_write(&mut self, pos: usize, value: bool, observer: &mut impl Observer<C>) -> Result<(), IntCodeError<C>> {
+        self.write(pos, C::from_i64(value as i64), observer)
+    }
+
+    pub fn run_program(&mut self) -> Result<Vec<C>, IntCodeError<C>> {
+        self.run_program_from(&mut VecDeque::new())
+    }
+
+    /// Like run_program, asking the provider for input once the queue is
+    /// empty. It fails with InputStarvation when the provider has none.
+    pub fn run_program_from(&mut self, input: &mut impl InputProvider<C>) -> Result<Vec<C>, IntCodeError<C>> {
+        let mut output = Vec::new();
+        loop {
+            match self.run_slice_from(input)? {
+                IntCodeState::Done => return Ok(output),
+                IntCodeState::NeedInput =>
+                    return Err(IntCodeError::InputStarvation { pc: self.pc, instruction: self.instruction() }),
+                IntCodeState::Output(result) => output.push(result)
+            }
+        }
+    }
+
+    pub fn run_slice(&mut self) -> Result<IntCodeState<C>, IntCodeError<C>> {
+        loop {
+            if let Some(state) = self.step_with(&mut ())? { return Ok(state); }
+        }
+    }
+
+    /// Like run_slice, asking the provider for input once the queue is
+    /// empty. Returns NeedInput only when the provider has none.
+    pub fn run_slice_from(&mut self, input: &mut impl InputProvider<C>) -> Result<IntCodeState<C>, IntCodeError<C>> {
+        input::run_slice_from(self, input, IntCode::run_slice)
+    }
+
+    /// Executes a single instruction. Returns the state if the instruction
+    /// produced output, halted or is waiting for input, otherwise None.
+    pub fn step(&mut self) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        self.step_with(&mut ())
+    }
+
+    /// Like step, but reports the instruction, its parameters, every data
+    /// read and write and any input or output to the observer.
+    pub fn step_with(&mut self, observer: &mut impl Observer<C>) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        if self.use_cache { self.cache_instruction(); }
+        if self.budget.is_none() { return self.execute(observer); }
+        self.admit(self.opcode() == OUTPUT)?;
+        let state = self.execute(observer)?;
+        self.spend(&state);
+        Ok(state)
+    }
+
+    fn execute(&mut self, observer: &mut impl Observer<C>) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        observer.instruction(self.pc, &self.instruction());
+        match self.opcode() {
+            ADD => { 
+                let p1 = self.p(1, observer)?; 
+                let p2 = self.p(2, observer)?; 
+                let p3 = self.p_w(3)?; 
+                let sum = self.add(&p1, &p2)?;
+                self.write(p3, sum, observer)?; 
+                self.pc += 4;
+            },
+            MULTIPLY => { 
+                let p1 = self.p(1, observer)?; 
+                let p2 = self.p(2, observer)?; 
+                let p3 = self.p_w(3)?; 
+                let product = self.multiply(&p1, &p2)?;
+                self.write(p3, product, observer)?; 
+                self.pc += 4; 
+            },
+            INPUT => match self.input.pop_front() {
+                None => return Ok(Some(IntCodeState::NeedInput)),
+                Some(val) => {
+                    observer.input(&val);
+                    let p1 = self.p_w(1)?;
+                    self.write(p1, val, observer)?; 
+                    self.pc += 2;
+                }
+            },
+            OUTPUT => { 
+                let value = self.p(1, observer)?;
+                observer.output(&value);
+                self.pc += 2;
+                return Ok(Some(IntCodeState::Output(value)));
+            },
+            JUMP_NOT_ZERO => if !self.p(1, observer)?.is_zero() { 
+                let target = self.p(2, observer)?;
+                self.pc = self.address(target)?; 
+            } else { 
+                self.pc += 3 
+            },
+            JUMP_ZERO => if self.p(1, observer)?.is_zero() { 
+                let target = self.p(2, observer)?;
+                self.pc = self.address(target)?; 
+            } else { 
+                self.pc += 3 
+            },
+            STORE_LESS_THAN => { 
+                let p1 = self.p(1, observer)?; 
+                let p2 = self.p(2, observer)?; 
+                let p3 = self.p_w(3)?; 
+                self.bool_write(p3, p1 < p2, observer)?; 
+                self.pc += 4; 
+            },
+            STORE_EQUAL => { 
+                let p1 = self.p(1, observer)?; 
+                let p2 = self.p(2, observer)?; 
+                let p3 = self.p_w(3)?; 
+                self.bool_write(p3, p1 == p2, observer)?; 
+                self.pc += 4; 
+            },
+            ADJUST_RELATIVE_BASE => {
+                let p1 = self.p(1, observer)?;
+                self.relative_base = p1.checked_add(&self.relative_base).ok_or_else(|| self.overflow_error())?; 
+                self.pc += 2; 
+            }
+            HALT => return Ok(Some(IntCodeState::Done)),
+            _ => return self.extension(observer)
+        }
+        Ok(None)
+    }
+
+    /// Executes a registered extension instruction.
+    fn extension(&mut self, observer: &mut impl Observer<C>) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        let extensions = self.extensions.clone().ok_or_else(|| self.invalid_opcode())?;
+        let (extension, execute) = extensions.extension(self.opcode()).ok_or_else(|| self.invalid_opcode())?;
+        let mut values = Vec::new();
+        let mut addresses = Vec::new();
+        for (i, parameter) in extension.parameters.iter().enumerate() {
+            match parameter {
+                Parameter::Read => values.push(self.p(i + 1, observer)?),
+                Parameter::Write => addresses.push(self.p_w(i + 1)?)
+            }
+        }
+        let failed = |message| IntCodeError::Extension { pc: self.pc, instruction: self.instruction(), message };
+        let effect = execute(&Operands { pc: self.pc, relative_base: &self.relative_base, values: &values }).map_err(failed)?;
+        if effect.writes.len() != addresses.len() {
+            return Err(failed(format!("{} wrote {} values to {} parameters", extension.mnemonic, effect.writes.len(), addresses.len())));
+        }
+        // Only now is it known whether the instruction outputs, nothing has changed yet
+        if effect.output.is_some() { self.admit(true)?; }
+        let next = match effect.jump {
+            Some(target) => self.address(target)?,
+            None => self.pc + 1 + extension.parameters.len()
+        };
+        for (address, value) in addresses.into_iter().zip(effect.writes) {
+            self.write(address, value, observer)?;
+        }
+        if let Some(relative_base) = effect.relative_base { self.relative_base = relative_base; }
+        self.pc = next;
+        Ok(effect.output.map(|value| {
+            observer.output(&value);
+            IntCodeState::Output(value)
+        }))
+    }
+
+    fn disassemble_read_parameter(&self, pos: usize) -> Result<String, IntCodeError<C>> {
+        let immediate = self.peek(self.pc + pos);
+        let result = match self.mode(pos) {
+            1 => immediate.to_string(),
+            0 => format!("[{}] ({})", immediate, self.p(pos, &mut ())?),
+            2 => {
+                let p = self.p(pos, &mut ())?;
+                format!("[{} + {}] ({})", immediate, self.relative_base, p)
+            },
+            _ => return Err(self.invalid_mode(pos))
+        };
+        Ok(result)
+    }
+
+    fn disassemble_write_parameter(&self, pos: usize) -> Result<String, IntCodeError<C>> {
+        let immediate = self.peek(self.pc + pos);
+        let address = self.p_w(pos)?;
+        let result = if self.mode(pos) == 2 {
+            format!("{}+{} ({})", immediate, self.relative_base, address)
+        } else {
+            immediate.to_string()
+        };
+        Ok(result)
+    }
+
+    /// Describes the instruction at pc using the live machine state.
+    pub fn disassemble(&self) -> Result<String, IntCodeError<C>> {
+        let s = match self.opcode() {
+            ADD => {
+                let p1 = self.p(1, &mut ())?; 
+                let p2 = self.p(2, &mut ())?; 
+                format!("ADD             {}, {} = {} -> {}",
+                    self.disassemble_read_parameter(1)?, 
+                    self.disassemble_read_parameter(2)?, 
+                    self.add(&p1, &p2)?,
+                    self.disassemble_write_parameter(3)?)
+            },
+            MULTIPLY => {
+                let p1 = self.p(1, &mut ())?; 
+                let p2 = self.p(2, &mut ())?; 
+                format!("MULTIPLY        {}, {} = {} -> {}", 
+                    self.disassemble_read_parameter(1)?, 
+                    self.disassemble_read_parameter(2)?, 
+                    self.multiply(&p1, &p2)?,
+                    self.disassemble_write_parameter(3)?)
+            },
+            INPUT => match self.input.front().cloned() {
+                    None => format!("INPUT           NO_DATA -> {} NOP", self.disassemble_read_parameter(1)?),
+                    Some(data) => format!("INPUT           {} -> {}", data, self.disassemble_read_parameter(1)?)
+                },
+            OUTPUT => format!("OUTPUT          {}", self.disassemble_read_parameter(1)?),
+            JUMP_NOT_ZERO => {
+                format!("JUMP_NOT_ZERO   {} = {} TO {}", 
+                    self.disassemble_read_parameter(1)?,
+                    !self.p(1, &mut ())?.is_zero(),
+                    self.disassemble_read_parameter(2)?)
+            },
+            JUMP_ZERO => {
+                format!("JUMP_ZERO       {} = {} TO {}", 
+                self.disassemble_read_parameter(1)?, 
+                self.p(1, &mut ())?.is_zero(),
+                self.disassemble_read_parameter(2)?)
+            },
+            STORE_LESS_THAN => {
+                let p1 = self.p(1, &mut ())?; 
+                let p2 = self.p(2, &mut ())?; 
+                let result = (p1 < p2) as i64;
+                format!("STORE_LESS_THAN {}, {} = {} -> {}", 
+                    self.disassemble_read_parameter(1)?, 
+                    self.disassemble_read_parameter(2)?,
+                    result,
+                    self.disassemble_write_parameter(3)?)
+            },
+            STORE_EQUAL => {
+                let p1 = self.p(1, &mut ())?; 
+                let p2 = self.p(2, &mut ())?; 
+                let result = (p1 == p2) as i64;
+                format!("STORE_EQUAL     {}, {} = {} -> {}", 
+                    self.disassemble_read_parameter(1)?, 
+                    self.disassemble_read_parameter(2)?, 
+                    result, 
+                    self.disassemble_write_parameter(3)?)
+            },
+            ADJUST_RELATIVE_BASE => {
+                let p1 = self.disassemble_read_parameter(1)?;
+                let result = self.p(1, &mut ())?.checked_add(&self.relative_base).ok_or_else(|| self.overflow_error())?;
+                format!("ADJUST          {}, {} = {}", self.relative_base, p1, result) 
+            },
+            HALT => "HALT".to_string(),
+            code => match self.opcode_of(code) {
+                Some(Opcode::Extension(extension)) => {
+                    let parameters = extension.parameters.iter().enumerate()
+                        .map(|(i, parameter)| match parameter {
+                            Parameter::Read => self.disassemble_read_parameter(i + 1),
+                            Parameter::Write => self.disassemble_write_parameter(i + 1)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let text = match (extension.parameters.last(), parameters.split_last()) {
+                        (Some(Parameter::Write), Some((written, read))) if !read.is_empty() => format!("{} -> {}", read.join(", "), written),
+                        _ => parameters.join(", ")
+                    };
+                    format!("{:15} {}", extension.mnemonic, text)
+                },
+                _ => return Err(self.invalid_opcode())
+            }
+        };
+        Ok(format!("{:5}: {}", self.pc, s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn restore_from_should_keep_the_configuration() {
+        let mut p = IntCode::string_to_program("1101,1,2,5,99,0").unwrap();
+        let snapshot = p.snapshot();
+        p.set_decode_cache(false);
+        p.set_instruction_set(Arc::new(InstructionSet::new()));
+        p.set_memory_limit(Some(1000));
+        assert_eq!(Ok(None), p.step());
+
+        p.restore_from(snapshot.clone());
+        assert_eq!(snapshot, p.snapshot());
+        assert!(!p.use_cache && p.instruction_set().is_some());
+        assert_eq!(Some(1000), p.memory_limit());
+
+        p.set_limits(Limits { instructions: Some(1), ..Limits::default() });
+        assert_eq!(Ok(None), p.step());
+        p.restore_from(snapshot);
+        assert_eq!(Some(1), p.budget().map(|budget| budget.instructions));
+    }
+
+    #[test]
+    fn part1_test_relative_addressing_mode() {
+        let mut p = IntCode::string_to_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99").unwrap();
+        let actual = &p.run_program().unwrap();
+
+        assert_eq!(vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99], *actual);
+    }
+
+    #[test]
+    fn part1_output_16_digit_number() {
+        let mut p = IntCode::string_to_program("1102,34915192,34915192,7,4,7,99,0").unwrap();
+        let actual = p.run_program().unwrap()[0];
+
+        assert_eq!(1_219_070_632_396_864, actual);
+    }
+
+    #[test]
+    fn part1_output_1125899906842624() {
+        let mut p = IntCode::string_to_program("104,1125899906842624,99").unwrap();
+        let actual = p.run_program().unwrap()[0];
+
+        assert_eq!(1125899906842624, actual);
+    }
+
+    #[test]
+    fn invalid_opcode_should_report_pc_and_instruction() {
+        let mut p = IntCode::string_to_program("1101,1,1,5,42,0").unwrap();
+        let actual = p.run_slice();
+
+        assert_eq!(Err(IntCodeError::InvalidOpcode { pc: 4, instruction: 42 }), actual);
+    }
+
+    #[test]
+    fn invalid_parameter_mode_should_be_an_error() {
+        let mut p = IntCode::string_to_program("304,0,99").unwrap();
+        let actual = p.run_slice();
+
+        assert_eq!(Err(IntCodeError::InvalidMode { pc: 0, instruction: 304, mode: 3 }), actual);
+    }
+
+    #[test]
+    fn write_in_immediate_mode_should_be_an_error() {
+        let mut p = IntCode::string_to_program("11101,1,1,0,99").unwrap();
+        let actual = p.run_slice();
+
+        assert_eq!(Err(IntCodeError::WriteInImmediateMode { pc: 0, instruction: 11101 }), actual);
+    }
+
+    #[test]
+    fn negative_address_should_be_an_error() {
+        let mut p = IntCode::string_to_program("109,-10,204,3,99").unwrap();
+        let actual = p.run_slice();
+
+        assert_eq!(Err(IntCodeError::NegativeAddress { pc: 2, instruction: 204, address: -7 }), actual);
+    }
+
+    #[test]
+    fn jump_to_negative_address_should_be_an_error() {
+        let mut p = IntCode::string_to_program("1105,1,-1").unwrap();
+        let actual = p.run_slice();
+
+        assert_eq!(Err(IntCodeError::NegativeAddress { pc: 0, instruction: 1105, address: -1 }), actual);
+    }
+
+    #[test]
+    fn running_past_end_of_program_should_be_invalid_opcode() {
+        let mut p = IntCode::string_to_program("1101,1,1,5").unwrap();
+        let actual = p.run_slice();
+
+        assert_eq!(Err(IntCodeError::InvalidOpcode { pc: 4, instruction: 0 }), actual);
+    }
+
+    #[test]
+    fn run_program_without_input_should_report_input_starvation() {
+        let mut p = IntCode::string_to_program("104,7,3,0,99").unwrap();
+        let actual = p.run_program();
+
+        assert_eq!(Err(IntCodeError::InputStarvation { pc: 2, instruction: 3 }), actual);
+    }
+
+    #[test]
+    fn parse_error_should_report_offset_and_token() {
+        let actual = IntCode::string_to_program("1,2,x3,99").map(|p| p.memory().to_vec());
+
+        assert_eq!(Err(IntCodeError::Parse { offset: 4, token: "x3".to_string() }), actual);
+    }
+
+    #[test]
+    fn parse_should_accept_trailing_newline() {
+        let p = IntCode::string_to_program("1,2,99\n").unwrap();
+
+        assert_eq!(vec![1, 2, 99], p.memory());
+    }
+
+    #[test]
+    fn missing_file_should_be_io_error() {
+        let actual = IntCode::file_to_program("no/such/file.txt").map(|p| p.memory().to_vec());
+
+        assert!(matches!(actual, Err(IntCodeError::Io(_))));
+    }
+
+    #[test]
+    fn far_addresses_should_not_allocate_until_written() {
+        let mut p = IntCode::string_to_program("109,1099511627776,204,0,21101,6,7,0,204,0,99").unwrap();
+
+        assert_eq!(vec![0, 13], p.run_program().unwrap());
+        assert_eq!(1, p.memory_stats().pages);
+        assert_eq!(11, p.memory().len());
+    }
+
+    #[test]
+    fn instruction_limit_should_stop_a_run_that_never_halts() {
+        let mut p = IntCode::string_to_program("1105,1,0").unwrap();
+        p.set_limits(Limits { instructions: Some(1000), ..Limits::default() });
+        let actual = p.run_program();
+        assert_eq!(Err(IntCodeError::InstructionLimit { pc: 0, instruction: 1105, limit: 1000 }), actual);
+        assert!(actual.unwrap_err().is_limit());
+        assert_eq!(1000, p.budget().unwrap().instructions);
+    }
+
+    #[test]
+    fn run_should_continue_after_raising_a_limit() {
+        let mut expected = IntCode::file_to_program("../day9/src/day9.txt").unwrap();
+        expected.add_input(1);
+        let mut p = expected.clone();
+        let limits = Limits { instructions: Some(100), ..Limits::default() };
+        p.set_limits(limits);
+        let mut output = Vec::new();
+        let mut exhausted = 0;
+        loop {
+            match p.run_slice() {
+                Ok(IntCodeState::Output(value)) => output.push(value),
+                Ok(IntCodeState::Done) => break,
+                Err(e) if e.is_limit() => {
+                    exhausted += 1;
+                    p.set_limits(limits);
+                },
+                other => panic!("{:?}", other)
+            }
+        }
+        assert!(exhausted > 1);
+        assert_eq!(expected.run_program().unwrap(), output);
+    }
+
+    #[test]
+    fn output_limit_should_stop_before_the_output() {
+        let mut p = IntCode::string_to_program("104,1,104,2,104,3,99").unwrap();
+        p.set_limits(Limits { outputs: Some(2), ..Limits::default() });
+        assert_eq!(Ok(IntCodeState::Output(1)), p.run_slice());
+        assert_eq!(Ok(IntCodeState::Output(2)), p.run_slice());
+        assert_eq!(Err(IntCodeError::OutputLimit { pc: 4, instruction: 104, limit: 2 }), p.run_slice());
+        assert_eq!((2, 2), (p.budget().unwrap().instructions, p.budget().unwrap().outputs));
+        p.set_limits(Limits { outputs: Some(1), ..Limits::default() });
+        assert_eq!(Ok(vec![3]), p.run_program());
+    }
+
+    #[test]
+    fn waiting_for_input_should_not_use_the_budget() {
+        let mut p = IntCode::string_to_program("3,5,4,5,99,0").unwrap();
+        p.set_limits(Limits { instructions: Some(2), ..Limits::default() });
+        assert_eq!(Ok(IntCodeState::NeedInput), p.run_slice());
+        assert_eq!(Ok(IntCodeState::NeedInput), p.run_slice());
+        p.add_input(7);
+        assert_eq!(Ok(IntCodeState::Output(7)), p.run_slice());
+        assert_eq!(Err(IntCodeError::InstructionLimit { pc: 4, instruction: 99, limit: 2 }), p.run_slice());
+    }
+
+    #[test]
+    fn waiting_for_input_should_come_before_an_exhausted_budget() {
+        let mut p = IntCode::string_to_program("104,1,3,7,4,7,99,0").unwrap();
+        p.set_limits(Limits { instructions: Some(1), ..Limits::default() });
+        assert_eq!(Ok(IntCodeState::Output(1)), p.run_slice());
+        assert_eq!(Ok(IntCodeState::NeedInput), p.run_slice());
+        p.add_input(5);
+        assert_eq!(Err(IntCodeError::InstructionLimit { pc: 2, instruction: 3, limit: 1 }), p.run_slice());
+    }
+
+    #[test]
+    fn time_limit_should_stop_a_run_that_never_halts() {
+        let mut p = IntCode::string_to_program("1105,1,0").unwrap();
+        let limit = Duration::from_millis(20);
+        p.set_limits(Limits { time: Some(limit), ..Limits::default() });
+        assert_eq!(Err(IntCodeError::TimeLimit { pc: 0, instruction: 1105, limit }), p.run_program());
+        assert!(p.budget().unwrap().elapsed() >= limit);
+    }
+
+    #[test]
+    fn memory_limit_should_be_one_of_the_limits() {
+        let mut p = IntCode::string_to_program("1101,1,1,5000,99").unwrap();
+        p.set_limits(Limits { memory: Some(1000), ..Limits::default() });
+        assert_eq!(None, p.budget());
+        let actual = p.run_program();
+        assert!(actual.unwrap_err().is_limit());
+        p.set_limits(Limits::default());
+        assert_eq!(Ok(vec![]), p.run_program());
+        assert_eq!(2, p.peek(5000));
+    }
+
+    #[test]
+    fn exceeding_the_memory_limit_should_be_an_error() {
+        let mut p = IntCode::string_to_program("1101,1,1,5000,99").unwrap();
+        p.set_memory_limit(Some(1000));
+        let actual = p.run_slice();
+
+        assert_eq!(Err(IntCodeError::MemoryLimit { pc: 0, instruction: 1101, address: 5000, limit: 1000 }), actual);
+        assert_eq!(0, p.memory_stats().pages);
+    }
+
+    // Outputs 7, then patches its first instruction from OUTPUT #7 to
+    // OUTPUT [7] and runs it again, which outputs the 4 stored at address 7.
+    const SELF_MODIFYING: &str = "104,7,1005,17,16,1101,0,4,0,1101,1,0,17,1106,0,0,99,0";
+
+    #[test]
+    fn writes_should_invalidate_decoded_instructions() {
+        let mut p = IntCode::string_to_program(SELF_MODIFYING).unwrap();
+
+        assert_eq!(vec![7, 4], p.run_program().unwrap());
+    }
+
+    #[test]
+    fn decode_cache_should_not_change_results() {
+        for file_name in &["../day5/src/day5.txt", "../day9/src/day9.txt"] {
+            let program = IntCode::file_to_program(file_name).unwrap();
+            let mut cached = program.clone();
+            let mut uncached = program.clone();
+            uncached.set_decode_cache(false);
+            cached.add_input(1);
+            uncached.add_input(1);
+
+            assert_eq!(uncached.run_program().unwrap(), cached.run_program().unwrap());
+            assert_eq!(uncached.memory(), cached.memory());
+        }
+    }
+}
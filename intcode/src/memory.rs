@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Cell, Number};
+
+/// Cells per page of the sparse memory beyond the loaded image.
+pub const PAGE_SIZE: usize = 1024;
+
+/// How much memory a machine has allocated.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MemoryStats {
+    /// Cells in the loaded image.
+    pub image: usize,
+    /// Pages allocated beyond the image.
+    pub pages: usize,
+    /// Total cells allocated, image and pages.
+    pub cells: usize,
+    pub limit: Option<usize>
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} cells: image {}, {} page(s) of {}", self.cells, self.image, self.pages, PAGE_SIZE)?;
+        match self.limit {
+            Some(limit) => write!(f, ", limit {}", limit),
+            None => write!(f, ", no limit")
+        }
+    }
+}
+
+/// Pages within this many of the image are kept in one vector with it, the
+/// rest are hashed. Most programs keep their stack and heap right after the
+/// image, where a hash lookup per access would be noticeable.
+const NEAR_PAGES: usize = 64;
+
+/// Machine memory: the loaded image is a dense vector, everything beyond it
+/// lives in pages that are allocated on the first non-zero write. Reading
+/// never allocates, unallocated cells read as zero.
+///
+/// Pages are aligned to the end of the image. The first NEAR_PAGES extend
+/// the image vector, allocating any below them too, pages further out are
+/// hashed by number.
+#[derive(Debug, Clone)]
+pub(crate) struct Memory<C = Cell> {
+    dense: Vec<C>,
+    image_len: usize,
+    far: HashMap<usize, Vec<C>>,
+    limit: Option<usize>
+}
+
+impl<C: Number> Memory<C> {
+    pub fn new(image: Vec<C>) -> Self {
+        Memory { image_len: image.len(), dense: image, far: HashMap::new(), limit: None }
+    }
+
+    pub fn image(&self) -> &[C] {
+        &self.dense[..self.image_len]
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    fn near_end(&self) -> usize {
+        self.image_len + NEAR_PAGES * PAGE_SIZE
+    }
+
+    fn near_pages(&self) -> usize {
+        (self.dense.len() - self.image_len) / PAGE_SIZE
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let pages = self.near_pages() + self.far.len();
+        MemoryStats { image: self.image_len, pages, cells: self.image_len + pages * PAGE_SIZE, limit: self.limit }
+    }
+
+    /// The far page number and offset of an address beyond the near pages.
+    fn far_page(&self, address: usize) -> (usize, usize) {
+        let offset = address - self.near_end();
+        (offset / PAGE_SIZE, offset % PAGE_SIZE)
+    }
+
+    pub fn get(&self, address: usize) -> C {
+        match self.dense.get(address) {
+            Some(value) => value.clone(),
+            None => self.get_sparse(address)
+        }
+    }
+
+    #[cold]
+    fn get_sparse(&self, address: usize) -> C {
+        if address < self.near_end() { return C::zero(); }
+        let (page, offset) = self.far_page(address);
+        self.far.get(&page).map_or_else(C::zero, |cells| cells[offset].clone())
+    }
+
+    /// Writes the cell, returning false without writing when that needs new
+    /// pages and the limit does not allow it.
+    pub fn set(&mut self, address: usize, value: C, limited: bool) -> bool {
+        match self.dense.get_mut(address) {
+            Some(cell) => {
+                *cell = value;
+                true
+            },
+            None => self.set_sparse(address, value, limited)
+        }
+    }
+
+    #[cold]
+    fn set_sparse(&mut self, address: usize, value: C, limited: bool) -> bool {
+        let far_page = if address < self.near_end() { None } else { Some(self.far_page(address)) };
+        if let Some(cells) = far_page.and_then(|(page, _)| self.far.get_mut(&page)) {
+            cells[far_page.unwrap().1] = value;
+            return true;
+        }
+        if value.is_zero() { return true; }
+        let new_pages = match far_page {
+            Some(_) => 1,
+            None => (address - self.dense.len()) / PAGE_SIZE + 1
+        };
+        if limited && self.limit.is_some_and(|limit| self.stats().cells + new_pages * PAGE_SIZE > limit) { return false; }
+        match far_page {
+            Some((page, offset)) => {
+                let mut cells = vec![C::zero(); PAGE_SIZE];
+                cells[offset] = value;
+                self.far.insert(page, cells);
+            },
+            None => {
+                self.dense.resize(self.dense.len() + new_pages * PAGE_SIZE, C::zero());
+                self.dense[address] = value;
+            }
+        }
+        true
+    }
+
+    /// Frees the page holding address if it only holds zeros. Near pages
+    /// are only freed from the end.
+    pub fn release(&mut self, address: usize) {
+        if address < self.image_len { return; }
+        if address < self.near_end() {
+            while self.dense.len() > self.image_len && self.dense[self.dense.len() - PAGE_SIZE..].iter().all(|c| c.is_zero()) {
+                self.dense.truncate(self.dense.len() - PAGE_SIZE);
+            }
+            return;
+        }
+        let (page, _) = self.far_page(address);
+        if self.far.get(&page).is_some_and(|cells| cells.iter().all(|c| c.is_zero())) {
+            self.far.remove(&page);
+        }
+    }
+
+    /// The allocated pages by start address, in address order.
+    pub fn pages(&self) -> Vec<(usize, &[C])> {
+        let near = self.dense[self.image_len..].chunks(PAGE_SIZE).enumerate()
+            .map(|(page, cells)| (self.image_len + page * PAGE_SIZE, cells));
+        let mut far: Vec<_> = self.far.iter()
+            .map(|(page, cells)| (self.near_end() + page * PAGE_SIZE, cells.as_slice()))
+            .collect();
+        far.sort_by_key(|(address, _)| *address);
+        near.chain(far).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_beyond_the_image_should_not_allocate() {
+        let memory: Memory = Memory::new(vec![1, 2, 3]);
+
+        assert_eq!(0, memory.get(1 << 40));
+        assert_eq!(MemoryStats { image: 3, pages: 0, cells: 3, limit: None }, memory.stats());
+    }
+
+    #[test]
+    fn near_writes_should_extend_the_image_and_far_writes_allocate_one_page() {
+        let mut memory: Memory = Memory::new(vec![1, 2, 3]);
+        assert!(memory.set(1 << 40, 7, true));
+        assert!(memory.set((1 << 40) + 1, 8, true));
+        assert!(memory.set(2000, 0, true));
+        assert_eq!(1, memory.stats().pages);
+        assert!(memory.set(2000, 9, true));
+
+        assert_eq!(7, memory.get(1 << 40));
+        assert_eq!(8, memory.get((1 << 40) + 1));
+        assert_eq!(9, memory.get(2000));
+        assert_eq!(vec![1, 2, 3], memory.image());
+        let pages: Vec<usize> = memory.pages().iter().map(|(start, _)| *start).collect();
+        assert_eq!(vec![3, 3 + PAGE_SIZE], pages[..2]);
+        assert!((pages[2]..pages[2] + PAGE_SIZE).contains(&(1 << 40)));
+    }
+
+    #[test]
+    fn limit_should_refuse_new_pages() {
+        let mut memory: Memory = Memory::new(vec![0; 10]);
+        memory.set_limit(Some(10 + PAGE_SIZE));
+
+        assert!(memory.set(100, 1, true));
+        assert!(!memory.set(5000, 1, true));
+        assert!(!memory.set(1 << 40, 1, true));
+        assert!(memory.set(101, 1, true));
+        assert!(memory.set(5000, 1, false));
+        assert_eq!("5130 cells: image 10, 5 page(s) of 1024, limit 1034", memory.stats().to_string());
+    }
+
+    #[test]
+    fn release_should_only_free_zero_pages() {
+        let mut memory: Memory = Memory::new(vec![]);
+        memory.set(5000, 1, true);
+        memory.set(1 << 40, 1, true);
+        memory.release(5000);
+        memory.release(1 << 40);
+        assert_eq!(6, memory.stats().pages);
+
+        memory.set(5000, 0, true);
+        memory.set(1 << 40, 0, true);
+        memory.release(5000);
+        memory.release(1 << 40);
+        assert_eq!(0, memory.stats().pages);
+    }
+}
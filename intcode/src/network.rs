@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number};
+
+/// Packets sent here go to the NAT instead of a machine.
+pub const NAT_ADDRESS: usize = 255;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Packet<C = Cell> {
+    pub source: usize,
+    pub destination: C,
+    pub x: C,
+    pub y: C
+}
+
+impl<C: Number> fmt::Display for Packet<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {} ({}, {})", self.source, self.destination, self.x, self.y)
+    }
+}
+
+/// Everything that happened on the network, in order.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event<C = Cell> {
+    Sent { round: u64, packet: Packet<C> },
+    /// Sent to an address that is neither a running machine nor the NAT.
+    Dropped { round: u64, packet: Packet<C> },
+    /// A whole round passed without any packet being sent or received.
+    Idle { round: u64 },
+    /// The NAT woke machine 0 with the last packet it received.
+    NatDelivered { round: u64, packet: Packet<C> }
+}
+
+impl<C: Number> fmt::Display for Event<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Sent { round, packet } => write!(f, "round {}: sent {}", round, packet),
+            Event::Dropped { round, packet } => write!(f, "round {}: dropped {}", round, packet),
+            Event::Idle { round } => write!(f, "round {}: idle", round),
+            Event::NatDelivered { round, packet } => write!(f, "round {}: NAT delivered {}", round, packet)
+        }
+    }
+}
+
+/// N machines exchanging (destination, x, y) packets, with a NAT at
+/// NAT_ADDRESS.
+///
+/// Scheduling is deterministic: every round each machine in address order
+/// is handed all packets queued for it, or -1 when there are none, and runs
+/// until it wants more input. Packets are queued at their destination as
+/// soon as they are sent, so a machine later in the round sees them at once.
+pub struct Network<C = Cell> {
+    machines: Vec<IntCode<C>>,
+    queues: Vec<VecDeque<Packet<C>>>,
+    partial: Vec<Vec<C>>,
+    nat: Option<Packet<C>>,
+    round: u64,
+    log: Vec<Event<C>>
+}
+
+impl<C: Number> Network<C> {
+    /// Boots size copies of the program, each with its address as first input.
+    pub fn new(program: &IntCode<C>, size: usize) -> Self {
+        let machines = (0..size).map(|address| {
+            let mut machine = program.clone();
+            machine.add_input(C::from_i64(address as i64));
+            machine
+        }).collect();
+        Network {
+            machines,
+            queues: vec![VecDeque::new(); size],
+            partial: vec![Vec::new(); size],
+            nat: None,
+            round: 0,
+            log: Vec::new()
+        }
+    }
+
+    pub fn machines(&self) -> &[IntCode<C>] {
+        &self.machines
+    }
+
+    /// The last packet the NAT received.
+    pub fn nat(&self) -> Option<&Packet<C>> {
+        self.nat.as_ref()
+    }
+
+    pub fn log(&self) -> &[Event<C>] {
+        &self.log
+    }
+
+    fn route(&mut self, packet: Packet<C>) -> Event<C> {
+        let round = self.round;
+        match packet.destination.to_i64() {
+            Some(d) if d == NAT_ADDRESS as i64 => {
+                self.nat = Some(packet.clone());
+                Event::Sent { round, packet }
+            },
+            Some(d) if d >= 0 && (d as usize) < self.machines.len() && !self.machines[d as usize].is_done() => {
+                self.queues[d as usize].push_back(packet.clone());
+                Event::Sent { round, packet }
+            },
+            _ => Event::Dropped { round, packet }
+        }
+    }
+
+    /// Gives the machine its queued packets, or -1, and runs it until it
+    /// needs more input. Returns whether it received or sent anything. A
+    /// halted machine is skipped and packets still queued for it dropped.
+    fn run_machine(&mut self, address: usize, events: &mut Vec<Event<C>>) -> Result<bool, IntCodeError<C>> {
+        if self.machines[address].is_done() {
+            let round = self.round;
+            events.extend(self.queues[address].drain(..).map(|packet| Event::Dropped { round, packet }));
+            return Ok(false);
+        }
+        let mut active = !self.queues[address].is_empty();
+        if !active { self.machines[address].add_input(C::from_i64(-1)); }
+        while let Some(packet) = self.queues[address].pop_front() {
+            self.machines[address].add_input(packet.x);
+            self.machines[address].add_input(packet.y);
+        }
+        loop {
+            match self.machines[address].run_slice()? {
+                IntCodeState::Output(value) => {
+                    self.partial[address].push(value);
+                    if self.partial[address].len() == 3 {
+                        let partial = &mut self.partial[address];
+                        let (y, x, destination) = (partial.pop().unwrap(), partial.pop().unwrap(), partial.pop().unwrap());
+                        events.push(self.route(Packet { source: address, destination, x, y }));
+                        active = true;
+                    }
+                },
+                IntCodeState::NeedInput | IntCodeState::Done => return Ok(active)
+            }
+        }
+    }
+
+    /// Runs one round and returns its events. When nothing happens in a
+    /// round the network is idle, and the NAT delivers its last packet to
+    /// machine 0 for the next round.
+    pub fn round(&mut self) -> Result<Vec<Event<C>>, IntCodeError<C>> {
+        self.round += 1;
+        let mut events = Vec::new();
+        let mut active = false;
+        for address in 0..self.machines.len() {
+            active |= self.run_machine(address, &mut events)?;
+        }
+        if !active {
+            events.push(Event::Idle { round: self.round });
+            if let Some(nat) = &self.nat {
+                let packet = Packet { source: NAT_ADDRESS, destination: C::zero(), x: nat.x.clone(), y: nat.y.clone() };
+                if let Some(queue) = self.queues.first_mut() { queue.push_back(packet.clone()); }
+                events.push(Event::NatDelivered { round: self.round, packet });
+            }
+        }
+        self.log.extend(events.iter().cloned());
+        Ok(events)
+    }
+
+    /// Runs rounds until stop returns true for an event, which is returned,
+    /// or until max_rounds have run.
+    pub fn run_until(&mut self, max_rounds: u64, mut stop: impl FnMut(&Event<C>) -> bool) -> Result<Option<Event<C>>, IntCodeError<C>> {
+        for _ in 0..max_rounds {
+            if let Some(event) = self.round()?.into_iter().find(|e| stop(e)) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+
+    // Machine 0 boots by sending (5, 0) to machine 1. Every machine adds one
+    // to y of any packet it receives and passes it on to the next address,
+    // except machine 2 which sends it to the NAT.
+    const RELAY: &str = "
+                INPUT [addr]
+                JUMP_NOT_ZERO [addr], #loop
+                OUTPUT #1
+                OUTPUT #5
+                OUTPUT #0
+        loop:   INPUT [x]
+                STORE_EQUAL [x], #-1 -> [tmp]
+                JUMP_NOT_ZERO [tmp], #loop
+                INPUT [y]
+                ADD [y], #1 -> [y]
+                ADD [addr], #1 -> [dest]
+                STORE_EQUAL [addr], #2 -> [tmp]
+                JUMP_ZERO [tmp], #send
+                ADD #255, #0 -> [dest]
+        send:   OUTPUT [dest]
+                OUTPUT [x]
+                OUTPUT [y]
+                JUMP_ZERO #0, #loop
+        addr:   .data 0
+        x:      .data 0
+        y:      .data 0
+        dest:   .data 0
+        tmp:    .data 0
+    ";
+
+    fn relay(size: usize) -> Network {
+        Network::new(&IntCode::new(assembler::assemble(RELAY).unwrap()), size)
+    }
+
+    #[test]
+    fn packets_should_reach_later_machines_in_the_same_round() {
+        let mut network = relay(3);
+        let events = network.round().unwrap();
+
+        let log: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+        assert_eq!(vec!["round 1: sent 0 -> 1 (5, 0)", "round 1: sent 1 -> 2 (5, 1)", "round 1: sent 2 -> 255 (5, 2)"], log);
+        assert_eq!(Some(&Packet { source: 2, destination: 255, x: 5, y: 2 }), network.nat());
+    }
+
+    #[test]
+    fn idle_network_should_be_woken_by_the_nat() {
+        let mut network = relay(3);
+        network.round().unwrap();
+        let events = network.round().unwrap();
+
+        assert_eq!(vec![
+            Event::Idle { round: 2 },
+            Event::NatDelivered { round: 2, packet: Packet { source: NAT_ADDRESS, destination: 0, x: 5, y: 2 } }
+        ], events);
+        assert_eq!(Event::Sent { round: 3, packet: Packet { source: 0, destination: 1, x: 5, y: 3 } }, network.round().unwrap()[0]);
+    }
+
+    #[test]
+    fn run_until_should_stop_at_the_matching_event() {
+        let mut network = relay(3);
+        let mut delivered = Vec::new();
+        let stop = network.run_until(100, |e| match e {
+            Event::NatDelivered { packet, .. } => {
+                delivered.push(packet.y);
+                packet.y >= 8
+            },
+            _ => false
+        }).unwrap();
+
+        assert_eq!(vec![2, 5, 8], delivered);
+        assert!(matches!(stop, Some(Event::NatDelivered { round: 6, .. })));
+        assert_eq!(None, network.run_until(0, |_| true).unwrap());
+    }
+
+    #[test]
+    fn packets_to_unknown_addresses_should_be_dropped() {
+        let mut network = relay(2);
+        let events = network.round().unwrap();
+
+        assert_eq!(Event::Dropped { round: 1, packet: Packet { source: 1, destination: 2, x: 5, y: 1 } }, events[1]);
+        assert_eq!(2, network.log().len());
+    }
+
+    #[test]
+    fn halted_machines_should_be_skipped_and_their_packets_dropped() {
+        // Machine 1 halts at boot, machine 0 sends it a packet every round
+        let source = "
+                    INPUT [addr]
+                    JUMP_NOT_ZERO [addr], #halt
+            loop:   INPUT [x]
+                    OUTPUT #1
+                    OUTPUT #7
+                    OUTPUT #8
+                    JUMP_ZERO #0, #loop
+            halt:   HALT
+            addr:   .data 0
+            x:      .data 0
+        ";
+        let mut network = Network::new(&IntCode::new(assembler::assemble(source).unwrap()), 2);
+        let packet = Packet { source: 0, destination: 1, x: 7, y: 8 };
+        assert_eq!(vec![Event::Sent { round: 1, packet: packet.clone() }], network.round().unwrap());
+        assert!(network.machines()[1].is_done());
+        let pending = network.machines()[1].pending_input().len();
+
+        assert_eq!(vec![Event::Dropped { round: 2, packet: packet.clone() }], network.round().unwrap());
+        assert_eq!(vec![Event::Dropped { round: 3, packet }], network.round().unwrap());
+        assert_eq!(pending, network.machines()[1].pending_input().len());
+    }
+
+    #[test]
+    fn scheduling_should_be_deterministic() {
+        let mut first = relay(3);
+        let mut second = relay(3);
+        first.run_until(20, |_| false).unwrap();
+        second.run_until(20, |_| false).unwrap();
+
+        assert_eq!(first.log(), second.log());
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+/// What ADD and MULTIPLY do when the result does not fit in the cell type.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Overflow {
+    #[default]
+    Trap,
+    Wrap,
+    Saturate
+}
+
+/// A memory cell value the Intcode machine can compute with.
+pub trait Number: Clone + Debug + Display + PartialEq + PartialOrd + FromStr {
+    fn from_i64(value: i64) -> Self;
+    /// None when the value does not fit in an i64.
+    fn to_i64(&self) -> Option<i64>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn saturating_add(&self, other: &Self) -> Self;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+    fn saturating_mul(&self, other: &Self) -> Self;
+
+    fn zero() -> Self { Self::from_i64(0) }
+
+    fn is_zero(&self) -> bool { *self == Self::zero() }
+}
+
+macro_rules! primitive_number {
+    ($t:ty) => {
+        impl Number for $t {
+            fn from_i64(value: i64) -> Self { value.into() }
+            fn to_i64(&self) -> Option<i64> { std::convert::TryFrom::try_from(*self).ok() }
+
+            fn checked_add(&self, other: &Self) -> Option<Self> { <$t>::checked_add(*self, *other) }
+            fn wrapping_add(&self, other: &Self) -> Self { <$t>::wrapping_add(*self, *other) }
+            fn saturating_add(&self, other: &Self) -> Self { <$t>::saturating_add(*self, *other) }
+
+            fn checked_mul(&self, other: &Self) -> Option<Self> { <$t>::checked_mul(*self, *other) }
+            fn wrapping_mul(&self, other: &Self) -> Self { <$t>::wrapping_mul(*self, *other) }
+            fn saturating_mul(&self, other: &Self) -> Self { <$t>::saturating_mul(*self, *other) }
+        }
+    };
+}
+
+primitive_number!(i64);
+primitive_number!(i128);
+
+// An arbitrary-precision integer never overflows, so every policy behaves the same.
+#[cfg(feature = "bigint")]
+impl Number for num_bigint::BigInt {
+    fn from_i64(value: i64) -> Self { value.into() }
+    fn to_i64(&self) -> Option<i64> { num_traits::ToPrimitive::to_i64(self) }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> { Some(self + other) }
+    fn wrapping_add(&self, other: &Self) -> Self { self + other }
+    fn saturating_add(&self, other: &Self) -> Self { self + other }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> { Some(self * other) }
+    fn wrapping_mul(&self, other: &Self) -> Self { self * other }
+    fn saturating_mul(&self, other: &Self) -> Self { self * other }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const SQUARE_2_POW_50: &str = "1102,1125899906842624,1125899906842624,7,4,7,99,0";
+
+    #[test]
+    fn i64_overflow_should_trap_by_default() {
+        let mut p = IntCode::string_to_program(SQUARE_2_POW_50).unwrap();
+        let actual = p.run_program();
+
+        assert_eq!(Err(IntCodeError::Overflow { pc: 0, instruction: 1102 }), actual);
+    }
+
+    #[test]
+    fn i64_overflow_should_wrap_when_asked_to() {
+        let mut p = IntCode::string_to_program("1101,9223372036854775807,1,7,4,7,99,0").unwrap();
+        p.set_overflow(Overflow::Wrap);
+        let actual = p.run_program().unwrap()[0];
+
+        assert_eq!(i64::MIN, actual);
+    }
+
+    #[test]
+    fn i64_overflow_should_saturate_when_asked_to() {
+        let mut p = IntCode::string_to_program(SQUARE_2_POW_50).unwrap();
+        p.set_overflow(Overflow::Saturate);
+        let actual = p.run_program().unwrap()[0];
+
+        assert_eq!(i64::MAX, actual);
+    }
+
+    #[test]
+    fn i128_cells_should_hold_the_full_product() {
+        let mut p = IntCode::<i128>::from_string(SQUARE_2_POW_50).unwrap();
+        let actual = p.run_program().unwrap()[0];
+
+        assert_eq!(1i128 << 100, actual);
+    }
+
+    #[test]
+    fn i128_relative_mode_program_should_behave_like_i64() {
+        let mut p = IntCode::<i128>::from_string("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99").unwrap();
+        let actual = p.run_program().unwrap();
+
+        assert_eq!(vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99], actual);
+    }
+
+    #[test]
+    fn relative_base_overflow_should_trap_regardless_of_policy() {
+        let mut p = IntCode::string_to_program("109,9223372036854775807,109,1,99").unwrap();
+        p.set_overflow(Overflow::Wrap);
+        let actual = p.run_slice();
+
+        assert_eq!(Err(IntCodeError::Overflow { pc: 2, instruction: 109 }), actual);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_cells_should_never_overflow() {
+        use num_bigint::BigInt;
+
+        let mut p = IntCode::<BigInt>::from_string("1102,1125899906842624,1125899906842624,11,2,11,11,11,4,11,99,0").unwrap();
+        let actual = p.run_program().unwrap();
+
+        assert_eq!(vec![BigInt::from(1) << 100 << 100], actual);
+    }
+}
@@ -0,0 +1,15 @@
+/// Receives what IntCode::step_with does while executing one instruction:
+/// the instruction word, each parameter value as resolved, the data reads
+/// and writes, and any input consumed or output produced. Instruction
+/// fetches and immediate parameters are not reported as reads.
+pub trait Observer<C> {
+    fn instruction(&mut self, _pc: usize, _instruction: &C) {}
+    fn parameter(&mut self, _value: &C) {}
+    fn read(&mut self, _address: usize, _value: &C) {}
+    fn write(&mut self, _address: usize, _old: &C, _new: &C) {}
+    fn input(&mut self, _value: &C) {}
+    fn output(&mut self, _value: &C) {}
+}
+
+/// The observer used by the plain run methods, it ignores everything.
+impl<C> Observer<C> for () {}
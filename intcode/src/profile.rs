@@ -0,0 +1,421 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number, Observer};
+use crate::disassembler::{self, Statement};
+use crate::extension::InstructionSet;
+use crate::instruction::{Mode, Opcode};
+
+/// Loops with a smaller share of all steps are left out of the summary.
+const HOT: f64 = 0.01;
+
+/// Width of the bar of a loop taking every step.
+const BAR: f64 = 40.0;
+
+/// Collects the observer callbacks of one step, like the tracer's recorder.
+struct Counter<C> {
+    pc: usize,
+    instruction: C,
+    reads: Vec<usize>,
+    writes: Vec<usize>
+}
+
+impl<C: Number> Observer<C> for Counter<C> {
+    fn instruction(&mut self, pc: usize, instruction: &C) {
+        self.pc = pc;
+        self.instruction = instruction.clone();
+    }
+
+    fn read(&mut self, address: usize, _value: &C) {
+        self.reads.push(address);
+    }
+
+    fn write(&mut self, address: usize, _old: &C, _new: &C) {
+        self.writes.push(address);
+    }
+}
+
+/// How much of the loaded image a run used.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Coverage {
+    /// Instructions in the listing of the image.
+    pub instructions: usize,
+    /// Those of them that were executed.
+    pub executed: usize,
+    pub cells: usize,
+    /// Image cells executed as part of an instruction, read or written.
+    pub touched: usize
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 { 100.0 } else { 100.0 * part as f64 / whole as f64 }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Executed {} of {} instructions ({:.1}%), touched {} of {} cells ({:.1}%)",
+               self.executed, self.instructions, percent(self.executed, self.instructions),
+               self.touched, self.cells, percent(self.touched, self.cells))
+    }
+}
+
+/// A loop closed by a taken backward jump to a constant target.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Loop {
+    /// The jump target.
+    pub head: usize,
+    /// The jump instruction.
+    pub tail: usize,
+    /// Times the backward jump was taken.
+    pub iterations: u64,
+    /// Instructions executed between head and tail, not counting calls
+    /// made from the loop.
+    pub steps: u64
+}
+
+/// Runs a machine while counting executions per pc and per opcode, data
+/// reads and writes per address and how often each conditional jump was
+/// taken. A step that stops to wait for input or fails is not counted.
+///
+/// The counts are reported against the listing of the image the profiler
+/// was made for: coverage, an annotated disassembly, a summary of the
+/// hottest loops and exports as JSON and in the lcov format.
+pub struct Profiler<C = Cell> {
+    image: Vec<C>,
+    instructions: InstructionSet<C>,
+    steps: u64,
+    /// Executions and instruction size by pc.
+    executions: BTreeMap<usize, (u64, usize)>,
+    opcodes: HashMap<Opcode, u64>,
+    reads: BTreeMap<usize, u64>,
+    writes: BTreeMap<usize, u64>,
+    /// Taken and not taken counts by conditional jump.
+    branches: BTreeMap<usize, (u64, u64)>,
+    /// Taken backward jumps by head and tail.
+    back_edges: BTreeMap<(usize, usize), u64>
+}
+
+fn count(counts: &BTreeMap<usize, u64>, address: usize) -> u64 {
+    counts.get(&address).copied().unwrap_or(0)
+}
+
+impl<C: Number> Profiler<C> {
+    /// A profiler for machines running the image loaded in machine.
+    pub fn new(machine: &IntCode<C>) -> Self {
+        Profiler {
+            image: machine.memory().to_vec(),
+            instructions: machine.instruction_set().cloned().unwrap_or_default(),
+            steps: 0,
+            executions: BTreeMap::new(),
+            opcodes: HashMap::new(),
+            reads: BTreeMap::new(),
+            writes: BTreeMap::new(),
+            branches: BTreeMap::new(),
+            back_edges: BTreeMap::new()
+        }
+    }
+
+    /// Like IntCode::step, counting the instruction.
+    pub fn step(&mut self, machine: &mut IntCode<C>) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        let mut counter = Counter { pc: 0, instruction: C::zero(), reads: Vec::new(), writes: Vec::new() };
+        let state = machine.step_with(&mut counter)?;
+        if state == Some(IntCodeState::NeedInput) { return Ok(state); }
+        let Counter { pc, instruction, reads, writes } = counter;
+        // Every instruction that completes has a valid opcode, though its
+        // word need not be canonical
+        let word = instruction.to_i64().expect("executed instruction");
+        let opcode = machine.opcode_of(word % 100).expect("executed opcode");
+        self.steps += 1;
+        self.executions.entry(pc).or_insert((0, opcode.size())).0 += 1;
+        *self.opcodes.entry(opcode).or_insert(0) += 1;
+        reads.into_iter().for_each(|address| *self.reads.entry(address).or_insert(0) += 1);
+        writes.into_iter().for_each(|address| *self.writes.entry(address).or_insert(0) += 1);
+        if let Opcode::JumpNotZero | Opcode::JumpZero = opcode {
+            let taken = machine.pc() != pc + 3;
+            let branch = self.branches.entry(pc).or_insert((0, 0));
+            if taken { branch.0 += 1; } else { branch.1 += 1; }
+            let constant_target = Mode::from_digit(word / 1000 % 10) == Some(Mode::Immediate);
+            if taken && constant_target && machine.pc() <= pc {
+                *self.back_edges.entry((machine.pc(), pc)).or_insert(0) += 1;
+            }
+        }
+        Ok(state)
+    }
+
+    /// Like IntCode::run_slice, counting every instruction.
+    pub fn run_slice(&mut self, machine: &mut IntCode<C>) -> Result<IntCodeState<C>, IntCodeError<C>> {
+        loop {
+            if let Some(state) = self.step(machine)? { return Ok(state); }
+        }
+    }
+
+    /// Like IntCode::run_program, counting every instruction.
+    pub fn run_program(&mut self, machine: &mut IntCode<C>) -> Result<Vec<C>, IntCodeError<C>> {
+        let mut output = Vec::new();
+        loop {
+            match self.run_slice(machine)? {
+                IntCodeState::Done => return Ok(output),
+                IntCodeState::NeedInput => return Err(IntCodeError::InputStarvation {
+                    pc: machine.pc(), instruction: machine.peek(machine.pc())
+                }),
+                IntCodeState::Output(value) => output.push(value)
+            }
+        }
+    }
+
+    /// Instructions executed.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Times the instruction at pc was executed.
+    pub fn executions(&self, pc: usize) -> u64 {
+        self.executions.get(&pc).map_or(0, |(count, _)| *count)
+    }
+
+    /// The opcodes the profiled machines can execute, the standard ones
+    /// followed by any registered extensions.
+    pub fn opcodes(&self) -> Vec<Opcode> {
+        self.instructions.opcodes()
+    }
+
+    /// Times an instruction with opcode was executed.
+    pub fn opcode(&self, opcode: Opcode) -> u64 {
+        self.opcodes.get(&opcode).copied().unwrap_or(0)
+    }
+
+    /// Data reads from address, instruction fetches and immediate
+    /// parameters are not counted.
+    pub fn reads(&self, address: usize) -> u64 {
+        count(&self.reads, address)
+    }
+
+    pub fn writes(&self, address: usize) -> u64 {
+        count(&self.writes, address)
+    }
+
+    /// Times the conditional jump at pc was taken and not taken.
+    pub fn branch(&self, pc: usize) -> (u64, u64) {
+        self.branches.get(&pc).copied().unwrap_or((0, 0))
+    }
+
+    pub fn coverage(&self) -> Coverage {
+        let mut touched = vec![false; self.image.len()];
+        let executed = self.executions.iter().flat_map(|(pc, (_, size))| *pc..pc + size);
+        for address in executed.chain(self.reads.keys().copied()).chain(self.writes.keys().copied()) {
+            if let Some(cell) = touched.get_mut(address) { *cell = true; }
+        }
+        let listing = disassembler::disassemble_with(&self.image, &self.instructions);
+        let instructions: Vec<usize> = listing.iter()
+            .filter(|s| matches!(s, Statement::Instruction { .. }))
+            .map(|s| s.address())
+            .collect();
+        Coverage {
+            instructions: instructions.len(),
+            executed: instructions.iter().filter(|pc| self.executions.contains_key(pc)).count(),
+            cells: self.image.len(),
+            touched: touched.iter().filter(|t| **t).count()
+        }
+    }
+
+    /// Every loop that was entered, by head and then by tail.
+    pub fn loops(&self) -> Vec<Loop> {
+        self.back_edges.iter()
+            .map(|((head, tail), iterations)| Loop {
+                head: *head, tail: *tail, iterations: *iterations,
+                steps: self.executions.range(head..=tail).map(|(_, (count, _))| count).sum()
+            })
+            .collect()
+    }
+
+    /// The loops taking at least 1% of all steps, each line a bar in
+    /// proportion to its share with loops nested inside indented below it.
+    pub fn hot_loops(&self) -> String {
+        let mut loops: Vec<Loop> = self.loops().into_iter()
+            .filter(|l| l.steps as f64 >= HOT * self.steps as f64)
+            .collect();
+        loops.sort_by_key(|l| (l.head, std::cmp::Reverse(l.tail)));
+        let mut text = String::new();
+        let mut enclosing: Vec<Loop> = Vec::new();
+        for l in loops {
+            while enclosing.last().is_some_and(|outer| outer.tail < l.tail) { enclosing.pop(); }
+            let share = l.steps as f64 / self.steps as f64;
+            let bar = "#".repeat(((share * BAR).round() as usize).max(1));
+            text += &format!("{:5.1}% {}{} {}..{}: {} iterations, {} steps\n",
+                             100.0 * share, "  ".repeat(enclosing.len()), bar, l.head, l.tail, l.iterations, l.steps);
+            enclosing.push(l);
+        }
+        text
+    }
+
+    /// Executions, reads and writes of the cells of a statement.
+    fn statement_counts(&self, statement: &Statement<C>) -> (u64, u64, u64) {
+        let cells = match statement {
+            Statement::Instruction { address, instruction, .. } => *address..address + instruction.size(),
+            Statement::Data { address, values } => *address..address + values.len()
+        };
+        let executions = self.executions.range(cells.clone()).map(|(_, (count, _))| count).sum();
+        (executions, self.reads.range(cells.clone()).map(|(_, n)| n).sum(), self.writes.range(cells).map(|(_, n)| n).sum())
+    }
+
+    /// The listing of the image with executions, reads and writes in front
+    /// of every statement, and how often conditional jumps were taken.
+    pub fn annotated(&self) -> String {
+        let column = |n: u64| if n == 0 { format!("{:>9}", "-") } else { format!("{:>9}", n) };
+        let mut text = format!("{:>9} {:>9} {:>9}\n", "executed", "reads", "writes");
+        for statement in disassembler::disassemble_with(&self.image, &self.instructions) {
+            let (executions, reads, writes) = self.statement_counts(&statement);
+            text += &format!("{} {} {}  {}", column(executions), column(reads), column(writes), statement);
+            if let Statement::Instruction { address, instruction, .. } = &statement {
+                if let Opcode::JumpNotZero | Opcode::JumpZero = instruction.opcode {
+                    let (taken, not_taken) = self.branch(*address);
+                    if taken + not_taken > 0 { text += &format!("  ; taken {} of {}", taken, taken + not_taken); }
+                }
+            }
+            text += "\n";
+        }
+        text
+    }
+
+    /// The counts as a JSON object.
+    pub fn to_json(&self) -> String {
+        let pairs = |counts: &mut dyn Iterator<Item = (usize, u64)>| counts.map(|(a, n)| format!("[{},{}]", a, n)).collect::<Vec<_>>().join(",");
+        let coverage = self.coverage();
+        let opcodes: Vec<String> = self.opcodes().iter().map(|op| format!("\"{}\":{}", op.mnemonic(), self.opcode(*op))).collect();
+        let branches: Vec<String> = self.branches.iter().map(|(pc, (taken, not_taken))| format!("[{},{},{}]", pc, taken, not_taken)).collect();
+        let loops: Vec<String> = self.loops().iter()
+            .map(|l| format!("{{\"head\":{},\"tail\":{},\"iterations\":{},\"steps\":{}}}", l.head, l.tail, l.iterations, l.steps))
+            .collect();
+        format!("{{\"steps\":{},\"coverage\":{{\"instructions\":{},\"executed\":{},\"cells\":{},\"touched\":{}}},\"opcodes\":{{{}}},\
+                 \"executions\":[{}],\"reads\":[{}],\"writes\":[{}],\"branches\":[{}],\"loops\":[{}]}}\n",
+                self.steps, coverage.instructions, coverage.executed, coverage.cells, coverage.touched, opcodes.join(","),
+                pairs(&mut self.executions.iter().map(|(pc, (n, _))| (*pc, *n))),
+                pairs(&mut self.reads.iter().map(|(a, n)| (*a, *n))),
+                pairs(&mut self.writes.iter().map(|(a, n)| (*a, *n))),
+                branches.join(","), loops.join(","))
+    }
+
+    /// The coverage in the lcov tracefile format, for a source file named
+    /// source holding the disassembler listing of the image. Each statement
+    /// is a line, each conditional jump has a taken and a not taken branch.
+    pub fn to_lcov(&self, source: &str) -> String {
+        let mut lines = Vec::new();
+        let mut branches = Vec::new();
+        let mut branches_hit = 0;
+        for (index, statement) in disassembler::disassemble_with(&self.image, &self.instructions).iter().enumerate() {
+            if let Statement::Instruction { address, instruction, .. } = statement {
+                let line = index + 1;
+                let executions = self.executions(*address);
+                lines.push((line, executions));
+                if let Opcode::JumpNotZero | Opcode::JumpZero = instruction.opcode {
+                    let (taken, not_taken) = self.branch(*address);
+                    let hits = |n: u64| if executions == 0 { "-".to_string() } else { n.to_string() };
+                    branches_hit += (taken > 0) as usize + (not_taken > 0) as usize;
+                    branches.push(format!("BRDA:{},0,0,{}\nBRDA:{},0,1,{}\n", line, hits(taken), line, hits(not_taken)));
+                }
+            }
+        }
+        let mut text = format!("TN:\nSF:{}\n", source);
+        branches.iter().for_each(|branch| text += branch);
+        text += &format!("BRF:{}\nBRH:{}\n", branches.len() * 2, branches_hit);
+        lines.iter().for_each(|(line, n)| text += &format!("DA:{},{}\n", line, n));
+        text += &format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), lines.iter().filter(|(_, n)| *n > 0).count());
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regression_tests::paint_hull;
+
+    fn profile(source: &str, input: &[Cell]) -> (Profiler, Vec<Cell>) {
+        let mut machine = IntCode::string_to_program(source).unwrap();
+        input.iter().for_each(|v| machine.add_input(*v));
+        let mut profiler = Profiler::new(&machine);
+        let output = profiler.run_program(&mut machine).unwrap();
+        (profiler, output)
+    }
+
+    const COUNTDOWN: &str = "1101,0,3,20,1001,20,-1,20,4,20,1005,20,4,99";
+    const BRANCH: &str = "1106,0,6,104,1,99,104,2,99";
+    const NESTED: &str = "1101,0,2,30,1101,0,3,31,1001,31,-1,31,1005,31,8,1001,30,-1,30,1005,30,4,99";
+
+    #[test]
+    fn should_count_executions_opcodes_reads_and_writes() {
+        let (profiler, output) = profile(COUNTDOWN, &[]);
+        assert_eq!(vec![2, 1, 0], output);
+        assert_eq!(11, profiler.steps());
+        assert_eq!(vec![1, 3, 3, 3, 1], [0, 4, 8, 10, 13].iter().map(|pc| profiler.executions(*pc)).collect::<Vec<_>>());
+        assert_eq!(0, profiler.executions(1));
+        assert_eq!((4, 3, 3, 1), (profiler.opcode(Opcode::Add), profiler.opcode(Opcode::Output), profiler.opcode(Opcode::JumpNotZero), profiler.opcode(Opcode::Halt)));
+        assert_eq!((9, 4), (profiler.reads(20), profiler.writes(20)));
+        assert_eq!((2, 1), profiler.branch(10));
+        assert_eq!(vec![Loop { head: 4, tail: 10, iterations: 2, steps: 9 }], profiler.loops());
+    }
+
+    #[test]
+    fn should_not_count_a_step_waiting_for_input() {
+        let mut machine = IntCode::string_to_program("3,5,4,5,99,0").unwrap();
+        let mut profiler = Profiler::new(&machine);
+        assert_eq!(IntCodeState::NeedInput, profiler.run_slice(&mut machine).unwrap());
+        assert_eq!(0, profiler.steps());
+        machine.add_input(7);
+        assert_eq!(IntCodeState::Output(7), profiler.run_slice(&mut machine).unwrap());
+        assert_eq!((1, 1, 1), (profiler.executions(0), profiler.writes(5), profiler.reads(5)));
+    }
+
+    #[test]
+    fn should_report_coverage_and_annotate_the_listing() {
+        let (profiler, _) = profile(BRANCH, &[]);
+        assert_eq!(Coverage { instructions: 5, executed: 3, cells: 9, touched: 6 }, profiler.coverage());
+        let expected = " executed     reads    writes
+        1         -         -      0: JUMP_ZERO       #0, #6  ; taken 1 of 1
+        -         -         -      3: OUTPUT          #1
+        -         -         -      5: HALT
+        1         -         -      6: OUTPUT          #2
+        1         -         -      8: HALT
+";
+        assert_eq!(expected, profiler.annotated());
+    }
+
+    #[test]
+    fn should_summarize_nested_loops() {
+        let (profiler, _) = profile(NESTED, &[]);
+        let expected = format!(" 90.0% {} 4..19: 1 iterations, 18 steps\n 60.0%   {} 8..12: 4 iterations, 12 steps\n", "#".repeat(36), "#".repeat(24));
+        assert_eq!(expected, profiler.hot_loops());
+    }
+
+    #[test]
+    fn should_export_json_and_lcov() {
+        let (profiler, _) = profile(BRANCH, &[]);
+        let json = "{\"steps\":3,\"coverage\":{\"instructions\":5,\"executed\":3,\"cells\":9,\"touched\":6},\
+            \"opcodes\":{\"ADD\":0,\"MULTIPLY\":0,\"INPUT\":0,\"OUTPUT\":1,\"JUMP_NOT_ZERO\":0,\"JUMP_ZERO\":1,\"STORE_LESS_THAN\":0,\"STORE_EQUAL\":0,\"ADJUST\":0,\"HALT\":1},\
+            \"executions\":[[0,1],[6,1],[8,1]],\"reads\":[],\"writes\":[],\"branches\":[[0,1,0]],\"loops\":[]}\n";
+        assert_eq!(json, profiler.to_json());
+        let lcov = "TN:\nSF:branch.lst\nBRDA:1,0,0,1\nBRDA:1,0,1,0\nBRF:2\nBRH:1\n\
+            DA:1,1\nDA:2,0\nDA:3,0\nDA:4,1\nDA:5,1\nLF:5\nLH:3\nend_of_record\n";
+        assert_eq!(lcov, profiler.to_lcov("branch.lst"));
+    }
+
+    #[test]
+    fn day9_boost_test_mode_should_leave_code_unexecuted() {
+        let mut machine = IntCode::file_to_program("../day9/src/day9.txt").unwrap();
+        machine.add_input(1);
+        let mut profiler = Profiler::new(&machine);
+        assert_eq!(vec![3409270027], profiler.run_program(&mut machine).unwrap());
+        let coverage = profiler.coverage();
+        assert!(coverage.executed > 0 && coverage.executed < coverage.instructions, "{}", coverage);
+        assert_eq!(profiler.steps(), profiler.opcodes().into_iter().map(|op| profiler.opcode(op)).sum::<u64>());
+    }
+
+    #[test]
+    fn day11_robot_should_run_its_hottest_loop_most() {
+        let mut machine = IntCode::file_to_program("../day11/src/day11.txt").unwrap();
+        let mut profiler = Profiler::new(&machine);
+        paint_hull(&mut machine, |machine| profiler.run_slice(machine).unwrap());
+        let hottest = profiler.loops().into_iter().max_by_key(|l| l.steps).unwrap();
+        assert!(profiler.hot_loops().contains(&format!("{}..{}", hottest.head, hottest.tail)));
+        assert!(profiler.coverage().executed < profiler.coverage().instructions);
+    }
+}
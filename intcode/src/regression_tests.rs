@@ -0,0 +1,187 @@
+/// Runs the day 11 hull painting robot, with run taking the machine to its
+/// next state, and checks that it paints the known number of panels.
+#[cfg(test)]
+pub(crate) fn paint_hull(machine: &mut crate::IntCode, mut run: impl FnMut(&mut crate::IntCode) -> crate::IntCodeState) {
+    use crate::IntCodeState;
+    let (mut panels, mut position, mut direction) = (std::collections::HashMap::new(), (0, 0), (0, -1));
+    let mut output = Vec::new();
+    loop {
+        match run(machine) {
+            IntCodeState::NeedInput => machine.add_input(*panels.get(&position).unwrap_or(&0)),
+            IntCodeState::Output(value) => output.push(value),
+            IntCodeState::Done => break
+        }
+        if let [color, turn] = output[..] {
+            panels.insert(position, color);
+            direction = if turn == 0 { (direction.1, -direction.0) } else { (-direction.1, direction.0) };
+            position = (position.0 + direction.0, position.1 + direction.1);
+            output.clear();
+        }
+    }
+    assert_eq!(2226, panels.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn given_sample_program_in_text_when_running_then_result_should_be_modified_program() {
+        let program = vec![
+            1,   9, 10,  3,
+            2,   3, 11,  0,
+            99, 30, 40, 50];
+        let mut p = IntCode::new(program);
+        p.run_slice().unwrap();
+        let final_state = vec![
+            3500, 9, 10, 70,
+            2, 3, 11, 0,
+            99, 30, 40, 50];
+        assert_eq!(p.memory(), final_state);
+    }
+
+    #[test]
+    fn given_sample_1_when_running_then_result_should_be_modified_program() {
+        let program = vec![
+            1, 0, 0,  0,
+            99];
+        let mut p = IntCode::new(program);
+        p.run_slice().unwrap();
+        let final_state = vec![
+            2, 0, 0, 0,
+            99];
+        assert_eq!(p.memory(), final_state);
+    }
+
+    #[test]
+    fn given_sample_2_when_running_then_result_should_be_modified_program() {
+        let program = vec![
+            2, 3, 0, 3,
+            99];
+        let mut p = IntCode::new(program);
+        p.run_slice().unwrap();
+        let final_state = vec![
+            2, 3, 0, 6,
+            99];
+        assert_eq!(p.memory(), final_state);
+    }
+
+    #[test]
+    fn given_sample_3_when_running_then_result_should_be_modified_program() {
+        let program = vec![
+            2, 4, 4, 5,
+            99, 0];
+        let mut p = IntCode::new(program);
+        p.run_slice().unwrap();
+        let final_state = vec![
+            2, 4, 4, 5,
+            99, 9801];
+        assert_eq!(p.memory(), final_state);
+    }
+
+    #[test]
+    fn given_sample_4_when_running_then_result_should_be_modified_program() {
+        let program = vec![
+            1, 1, 1, 4,
+            99, 5, 6, 0,
+            99];
+        let mut p = IntCode::new(program);
+        p.run_slice().unwrap();
+        let final_state = vec![
+            30, 1, 1, 4,
+            2, 5, 6, 0,
+            99];
+        assert_eq!(p.memory(), final_state);
+    }
+
+    #[test]
+    fn given_string_when_parse_to_program_then_should_split_on_comma_into_program() {
+        let p = IntCode::string_to_program("1,2,3,4").unwrap();
+        assert_eq!(p.memory(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn given_all_zero_program_when_poking_999_into_pos0_then_peeking_pos0_should_give_999() {
+        let mut p = IntCode::string_to_program("0,0,0,0,0,0").unwrap();
+        p.poke(0,999);
+        assert_eq!(999, p.peek(0));
+    }
+
+    #[test]
+    fn when_poking_999_into_pos0_then_pos0_should_hold_999() {
+        let mut p = IntCode::string_to_program("0,0,0,0,0,0").unwrap();
+        p.poke(0, 999);
+        assert_eq!(999, p.memory()[0]);
+    }
+
+    #[test]
+    fn given_opcode_3_program_with_input_52_should_write_52_to_end() {
+        let mut p = IntCode::string_to_program("3,3,99,0").unwrap();
+        p.add_input(52);
+        p.run_slice().unwrap();
+        assert_eq!(52, p.memory()[3]);
+    }
+
+    #[test]
+    fn given_mixed_parameter_mode_should_write_99_at_end() {
+        let mut p = IntCode::string_to_program("1002,4,3,4,33").unwrap();
+        p.run_slice().unwrap();
+        assert_eq!(99, p.memory()[4]);
+    }
+
+    #[test]
+    fn position_mode_jump_program_should_give_0_for_input_0() {
+        let mut p = IntCode::string_to_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9").unwrap();
+        p.add_input(0);
+        let output = p.run_program().unwrap()[0];
+        assert_eq!(0, output);
+    }
+
+    #[test]
+    fn position_mode_jump_program_should_give_1_for_input_5() {
+        let mut p = IntCode::string_to_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9").unwrap();
+        p.add_input(5);
+        let output = p.run_program().unwrap()[0];
+        assert_eq!(1, output);
+    }
+
+    #[test]
+    fn immediate_mode_jump_program_should_give_0_for_input_0() {
+        let mut p = IntCode::string_to_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").unwrap();
+        p.add_input(0);
+        let output = p.run_program().unwrap()[0];
+        assert_eq!(0, output);
+    }
+
+    #[test]
+    fn immediate_mode_jump_program_should_give_1_for_input_5() {
+        let mut p = IntCode::string_to_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").unwrap();
+        p.add_input(5);
+        let output = p.run_program().unwrap()[0];
+        assert_eq!(1, output);
+    }
+
+    #[test]
+    fn check_8_program_should_give_999_for_input_below_8() {
+        let mut p = IntCode::string_to_program("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap();
+        p.add_input(7);
+        let output = p.run_program().unwrap()[0];
+        assert_eq!(999, output);
+    }
+
+    #[test]
+    fn check_8_program_should_give_1000_for_input_8() {
+        let mut p = IntCode::string_to_program("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap();
+        p.add_input(8);
+        let output = p.run_program().unwrap()[0];
+        assert_eq!(1000, output);
+    }
+    
+    #[test]
+    fn check_8_program_should_give_1001_for_input_above_8() {
+        let mut p = IntCode::string_to_program("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap();
+        p.add_input(9);
+        let output = p.run_program().unwrap()[0];
+        assert_eq!(1001, output);
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number};
+use crate::instruction::Opcode;
+use crate::trace::{RingBuffer, TraceRecord, TraceSink, Tracer};
+
+/// First line of every session file, followed by the format version.
+const HEADER: &str = "intcode-session";
+pub const SESSION_VERSION: u32 = 1;
+
+/// What a program exchanged with the outside world in one instruction.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Exchange<C = Cell> {
+    Input(C),
+    Output(C),
+    Halt
+}
+
+impl<C: Number> Exchange<C> {
+    fn of(record: &TraceRecord<C>) -> Option<Self> {
+        match (&record.input, &record.output) {
+            (Some(value), _) => Some(Exchange::Input(value.clone())),
+            (_, Some(value)) => Some(Exchange::Output(value.clone())),
+            _ if record.opcode() == Some(Opcode::Halt) => Some(Exchange::Halt),
+            _ => None
+        }
+    }
+}
+
+impl<C: fmt::Display> fmt::Display for Exchange<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Exchange::Input(value) => write!(f, "in {}", value),
+            Exchange::Output(value) => write!(f, "out {}", value),
+            Exchange::Halt => write!(f, "halt")
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Event<C = Cell> {
+    /// Number of instructions executed before this one.
+    pub step: u64,
+    pub pc: usize,
+    pub exchange: Exchange<C>
+}
+
+impl<C: fmt::Display> fmt::Display for Event<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at step {}, pc {}", self.exchange, self.step, self.pc)
+    }
+}
+
+/// Every input a run consumed and output it produced, in order. Record one
+/// by running the machine under a Tracer with a Session as its sink, then
+/// replay it against the same program to check it still behaves the same.
+///
+/// Session files are text: a `intcode-session 1` header line followed by
+/// one event per line, `in <step> <pc> <value>`, `out <step> <pc> <value>`
+/// or `halt <step> <pc>`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Session<C = Cell> {
+    pub events: Vec<Event<C>>
+}
+
+impl<C> Default for Session<C> {
+    fn default() -> Self {
+        Session { events: Vec::new() }
+    }
+}
+
+impl<C: Number> TraceSink<C> for Session<C> {
+    fn record(&mut self, record: &TraceRecord<C>) -> io::Result<()> {
+        if let Some(exchange) = Exchange::of(record) {
+            self.events.push(Event { step: record.step, pc: record.pc, exchange });
+        }
+        Ok(())
+    }
+}
+
+/// What a replay did where it diverged.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Replayed<C = Cell> {
+    Exchange(Exchange<C>),
+    /// Wanted input that is not the next recorded event.
+    Input,
+    /// Executed the instruction of the expected event without exchanging
+    /// anything.
+    Nothing
+}
+
+/// Where a replay first did something other than the session recorded.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Divergence<C = Cell> {
+    /// Number of recorded events that were matched before.
+    pub matched: usize,
+    pub expected: Event<C>,
+    pub step: u64,
+    pub pc: usize,
+    pub actual: Replayed<C>,
+    /// The instructions executed last before the divergence, oldest first.
+    pub context: Vec<TraceRecord<C>>
+}
+
+impl<C: Number> fmt::Display for Divergence<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Diverged after {} matching events: expected {}, ", self.matched, self.expected)?;
+        match &self.actual {
+            Replayed::Exchange(exchange) => write!(f, "got {} at step {}, pc {}", exchange, self.step, self.pc)?,
+            Replayed::Input => write!(f, "got a read of input at step {}, pc {}", self.step, self.pc)?,
+            Replayed::Nothing => write!(f, "got no exchange at step {}, pc {}", self.step, self.pc)?
+        }
+        for record in &self.context {
+            write!(f, "\n{}", record)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the context of a replay and the record of the last instruction.
+struct Replay<C> {
+    context: RingBuffer<C>,
+    last: Option<TraceRecord<C>>
+}
+
+impl<C: Clone> TraceSink<C> for Replay<C> {
+    fn record(&mut self, record: &TraceRecord<C>) -> io::Result<()> {
+        self.last = Some(record.clone());
+        self.context.record(record)
+    }
+}
+
+fn invalid<C>(line: usize, message: String) -> IntCodeError<C> {
+    IntCodeError::InvalidSession(format!("line {}: {}", line, message))
+}
+
+fn parse_value<T: std::str::FromStr>(what: &str, text: Option<&str>) -> Result<T, String> {
+    let text = text.ok_or_else(|| format!("Missing {}", what))?;
+    text.parse().map_err(|_| format!("Invalid {} '{}'", what, text))
+}
+
+impl<C: Number> Session<C> {
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    /// The recorded inputs, in order.
+    pub fn inputs(&self) -> Vec<C> {
+        self.events.iter().filter_map(|event| match &event.exchange {
+            Exchange::Input(value) => Some(value.clone()),
+            _ => None
+        }).collect()
+    }
+
+    /// The recorded outputs, in order.
+    pub fn outputs(&self) -> Vec<C> {
+        self.events.iter().filter_map(|event| match &event.exchange {
+            Exchange::Output(value) => Some(value.clone()),
+            _ => None
+        }).collect()
+    }
+
+    /// Runs the machine, feeding it the recorded inputs, until every event
+    /// is matched. The first event that differs in value, step or pc, or
+    /// that has not happened by its step, is a divergence, reported with the
+    /// last `context` instructions. Inputs already queued on the machine are
+    /// checked like any other.
+    pub fn replay(&self, machine: &mut IntCode<C>, context: usize) -> Result<Option<Divergence<C>>, IntCodeError<C>> {
+        let mut tracer = Tracer::new(Replay { context: RingBuffer::new(context), last: None });
+        let mut steps = 0;
+        let mut matched = 0;
+        while let Some(expected) = self.events.get(matched) {
+            let diverged = |tracer: &Tracer<Replay<C>, C>, step, pc, actual| Ok(Some(Divergence {
+                matched, expected: expected.clone(), step, pc, actual,
+                context: tracer.sink().context.records().iter().cloned().collect()
+            }));
+            match tracer.step(machine)? {
+                Some(IntCodeState::NeedInput) => match &expected.exchange {
+                    Exchange::Input(value) if expected.step == steps && expected.pc == machine.pc() => machine.add_input(value.clone()),
+                    _ => return diverged(&tracer, steps, machine.pc(), Replayed::Input)
+                },
+                _ => {
+                    steps += 1;
+                    let record = tracer.sink().last.as_ref().expect("a step that did not wait for input is recorded");
+                    match Exchange::of(record) {
+                        Some(exchange) => {
+                            let actual = Event { step: record.step, pc: record.pc, exchange };
+                            if actual != *expected { return diverged(&tracer, actual.step, actual.pc, Replayed::Exchange(actual.exchange)); }
+                            matched += 1;
+                        },
+                        None if record.step >= expected.step => return diverged(&tracer, record.step, record.pc, Replayed::Nothing),
+                        None => {}
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", HEADER, SESSION_VERSION);
+        for event in &self.events {
+            text += &match &event.exchange {
+                Exchange::Input(value) => format!("in {} {} {}\n", event.step, event.pc, value),
+                Exchange::Output(value) => format!("out {} {} {}\n", event.step, event.pc, value),
+                Exchange::Halt => format!("halt {} {}\n", event.step, event.pc)
+            };
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, IntCodeError<C>> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+        match lines.next().map(|(_, line)| line.split_whitespace().collect::<Vec<_>>()) {
+            Some(words) if words.len() == 2 && words[0] == HEADER => {
+                if words[1] != SESSION_VERSION.to_string() {
+                    return Err(invalid(1, format!("Unsupported session version {}", words[1])));
+                }
+            },
+            _ => return Err(invalid(1, "Not an Intcode session".to_string()))
+        }
+
+        let mut events = Vec::new();
+        for (number, line) in lines {
+            let mut words = line.split_whitespace();
+            let kind = match words.next() {
+                Some(kind) => kind,
+                None => continue
+            };
+            let error = |message| invalid(number, message);
+            let step = parse_value("step", words.next()).map_err(error)?;
+            let pc = parse_value("pc", words.next()).map_err(error)?;
+            let exchange = match kind {
+                "in" => Exchange::Input(parse_value("value", words.next()).map_err(error)?),
+                "out" => Exchange::Output(parse_value("value", words.next()).map_err(error)?),
+                "halt" => Exchange::Halt,
+                _ => return Err(error(format!("Unknown event '{}'", kind)))
+            };
+            if let Some(extra) = words.next() { return Err(error(format!("Unexpected '{}'", extra))); }
+            events.push(Event { step, pc, exchange });
+        }
+        Ok(Session { events })
+    }
+
+    pub fn save(&self, file_name: &str) -> Result<(), IntCodeError<C>> {
+        fs::write(file_name, self.to_text()).map_err(|e| IntCodeError::Io(format!("{}: {}", file_name, e)))
+    }
+
+    pub fn load(file_name: &str) -> Result<Self, IntCodeError<C>> {
+        let text = fs::read_to_string(file_name).map_err(|e| IntCodeError::Io(format!("{}: {}", file_name, e)))?;
+        Session::from_text(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regression_tests::paint_hull;
+
+    /// Records the day 11 robot painting its hull.
+    fn record_day11() -> Session {
+        let mut machine = IntCode::file_to_program("../day11/src/day11.txt").unwrap();
+        let mut tracer = Tracer::new(Session::new());
+        paint_hull(&mut machine, |machine| tracer.run_slice(machine).unwrap());
+        tracer.into_sink()
+    }
+
+    #[test]
+    fn day11_run_should_replay_without_divergence() {
+        let session = record_day11();
+        assert_eq!(Some(&Exchange::Halt), session.events.last().map(|event| &event.exchange));
+        assert_eq!(2 * session.inputs().len(), session.outputs().len());
+
+        let mut machine = IntCode::file_to_program("../day11/src/day11.txt").unwrap();
+        assert_eq!(Ok(None), session.replay(&mut machine, 5));
+        assert!(machine.is_done());
+    }
+
+    #[test]
+    fn should_report_the_first_divergence_with_context() {
+        let mut session = record_day11();
+        // The color painted by the sixth move
+        let index = session.events.iter().enumerate().filter(|(_, event)| matches!(event.exchange, Exchange::Output(_))).nth(10).unwrap().0;
+        let recorded = session.events[index].clone();
+        if let Exchange::Output(value) = recorded.exchange { session.events[index].exchange = Exchange::Output(1 - value); }
+
+        let mut machine = IntCode::file_to_program("../day11/src/day11.txt").unwrap();
+        let divergence = session.replay(&mut machine, 3).unwrap().unwrap();
+        assert_eq!(index, divergence.matched);
+        assert_eq!((recorded.step, recorded.pc), (divergence.step, divergence.pc));
+        assert_eq!(3, divergence.context.len());
+        assert_eq!(recorded.step, divergence.context[2].step);
+        assert!(divergence.to_string().starts_with(&format!("Diverged after {} matching events", index)));
+    }
+
+    #[test]
+    fn should_notice_reads_the_session_does_not_have() {
+        // Reads one value and echoes it, twice
+        let program = "3,9,4,9,3,9,4,9,99,0";
+        let mut machine = IntCode::string_to_program(program).unwrap();
+        machine.add_input(7);
+        machine.add_input(8);
+        let mut tracer = Tracer::new(Session::new());
+        assert_eq!(Ok(vec![7, 8]), tracer.run_program(&mut machine));
+        let mut session = tracer.into_sink();
+        assert_eq!("intcode-session 1\nin 0 0 7\nout 1 2 7\nin 2 4 8\nout 3 6 8\nhalt 4 8\n", session.to_text());
+
+        session.events.remove(2);
+        let mut machine = IntCode::string_to_program(program).unwrap();
+        let divergence = session.replay(&mut machine, 10).unwrap().unwrap();
+        assert_eq!((2, Replayed::Input, 2, 4), (divergence.matched, divergence.actual, divergence.step, divergence.pc));
+        assert_eq!(2, divergence.context.len());
+    }
+
+    #[test]
+    fn silent_loops_should_diverge_at_the_expected_step() {
+        let session = Session { events: vec![Event { step: 3, pc: 0, exchange: Exchange::Output(1) }] };
+        let mut machine = IntCode::string_to_program("1105,1,0").unwrap();
+        let divergence = session.replay(&mut machine, 2).unwrap().unwrap();
+        assert_eq!((0, Replayed::Nothing, 3, 0), (divergence.matched, divergence.actual, divergence.step, divergence.pc));
+        assert_eq!(vec![2, 3], divergence.context.iter().map(|record| record.step).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_save_and_load_sessions() {
+        let session = record_day11();
+        let file_name = std::env::temp_dir().join(format!("intcode-session-test-{}", std::process::id()));
+        let file_name = file_name.to_str().unwrap();
+        session.save(file_name).unwrap();
+        assert_eq!(Ok(session), Session::load(file_name));
+        std::fs::remove_file(file_name).unwrap();
+
+        assert!(Session::<Cell>::from_text("intcode-snapshot 1\n").is_err());
+        assert!(Session::<Cell>::from_text("intcode-session 2\n").is_err());
+        assert!(Session::<Cell>::from_text("intcode-session 1\nout 1 2\n").is_err());
+        assert!(Session::<Cell>::from_text("intcode-session 1\nhalt 1 2 3\n").is_err());
+        assert_eq!(Ok(Session::new()), Session::<Cell>::from_text("intcode-session 1\n\n"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs;
+
+use crate::{Cell, IntCode, IntCodeError, Number, Overflow};
+
+/// First line of every snapshot file, followed by the format version.
+const HEADER: &str = "intcode-snapshot";
+pub const SNAPSHOT_VERSION: u32 = 2;
+/// Version 1 had no pages, it is still read.
+const OLDEST_VERSION: u32 = 1;
+
+/// The complete state of a paused machine. Restoring it gives a machine
+/// that continues exactly where the original left off.
+///
+/// Snapshot files are text: a `intcode-snapshot 2` header line followed by
+/// one `key value` line each for pc, relative_base, overflow, input and
+/// memory, the last two as comma separated lists. Each memory page allocated
+/// beyond the image follows as a `page <start address> <cells>` line.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot<C = Cell> {
+    /// The loaded image.
+    pub memory: Vec<C>,
+    /// Pages beyond the image by start address, see MemoryStats.
+    pub pages: Vec<(usize, Vec<C>)>,
+    pub pc: usize,
+    pub relative_base: C,
+    pub input: Vec<C>,
+    pub overflow: Overflow
+}
+
+fn join<C: Number>(values: &[C]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn invalid<C>(line: usize, message: String) -> IntCodeError<C> {
+    IntCodeError::InvalidSnapshot(format!("line {}: {}", line, message))
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, text: &str) -> Result<T, String> {
+    text.trim().parse().map_err(|_| format!("Invalid {} '{}'", key, text.trim()))
+}
+
+fn parse_list<C: Number>(key: &str, text: &str) -> Result<Vec<C>, String> {
+    if text.trim().is_empty() { return Ok(Vec::new()); }
+    text.split(',').map(|v| parse_value(key, v)).collect()
+}
+
+impl<C: Number> Snapshot<C> {
+    pub fn to_text(&self) -> String {
+        let overflow = match self.overflow {
+            Overflow::Trap => "trap",
+            Overflow::Wrap => "wrap",
+            Overflow::Saturate => "saturate"
+        };
+        let mut lines = vec![
+            format!("{} {}", HEADER, SNAPSHOT_VERSION),
+            format!("pc {}", self.pc),
+            format!("relative_base {}", self.relative_base),
+            format!("overflow {}", overflow),
+            format!("input {}", join(&self.input)),
+            format!("memory {}", join(&self.memory))
+        ];
+        lines.extend(self.pages.iter().map(|(start, cells)| format!("page {} {}", start, join(cells))));
+        lines.iter().map(|line| line.trim_end().to_string() + "\n").collect()
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, IntCodeError<C>> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+        match lines.next().map(|(_, line)| line.split_whitespace().collect::<Vec<_>>()) {
+            Some(words) if words.len() == 2 && words[0] == HEADER => {
+                if !words[1].parse().is_ok_and(|version| (OLDEST_VERSION..=SNAPSHOT_VERSION).contains(&version)) {
+                    return Err(invalid(1, format!("Unsupported snapshot version {}", words[1])));
+                }
+            },
+            _ => return Err(invalid(1, "Not an Intcode snapshot".to_string()))
+        }
+
+        let (mut pc, mut relative_base, mut overflow, mut input, mut memory) = (None, None, None, None, None);
+        let mut pages = Vec::new();
+        for (number, line) in lines {
+            if line.trim().is_empty() { continue; }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let error = |message| invalid(number, message);
+            match key {
+                "pc" => pc = Some(parse_value(key, value).map_err(error)?),
+                "relative_base" => relative_base = Some(parse_value(key, value).map_err(error)?),
+                "overflow" => overflow = Some(match value.trim() {
+                    "trap" => Overflow::Trap,
+                    "wrap" => Overflow::Wrap,
+                    "saturate" => Overflow::Saturate,
+                    other => return Err(error(format!("Invalid overflow '{}'", other)))
+                }),
+                "input" => input = Some(parse_list(key, value).map_err(error)?),
+                "memory" => memory = Some(parse_list(key, value).map_err(error)?),
+                "page" => {
+                    let (start, cells) = value.split_once(' ').unwrap_or((value, ""));
+                    let start: usize = parse_value("page address", start).map_err(error)?;
+                    let cells = parse_list(key, cells).map_err(error)?;
+                    if start.checked_add(cells.len()).is_none() { return Err(error(format!("Page at {} is out of range", start))); }
+                    pages.push((start, cells));
+                },
+                _ => return Err(error(format!("Unknown key '{}'", key)))
+            }
+        }
+        let missing = |key: &str| IntCodeError::InvalidSnapshot(format!("Missing {}", key));
+        Ok(Snapshot {
+            memory: memory.ok_or_else(|| missing("memory"))?,
+            pages,
+            pc: pc.ok_or_else(|| missing("pc"))?,
+            relative_base: relative_base.ok_or_else(|| missing("relative_base"))?,
+            input: input.ok_or_else(|| missing("input"))?,
+            overflow: overflow.ok_or_else(|| missing("overflow"))?
+        })
+    }
+
+    pub fn save(&self, file_name: &str) -> Result<(), IntCodeError<C>> {
+        fs::write(file_name, self.to_text()).map_err(|e| IntCodeError::Io(format!("{}: {}", file_name, e)))
+    }
+
+    pub fn load(file_name: &str) -> Result<Self, IntCodeError<C>> {
+        let text = fs::read_to_string(file_name).map_err(|e| IntCodeError::Io(format!("{}: {}", file_name, e)))?;
+        Snapshot::from_text(&text)
+    }
+}
+
+impl<C: Number> IntCode<C> {
+    /// Writes a snapshot of the machine to a file, see Snapshot for the format.
+    pub fn save_snapshot(&self, file_name: &str) -> Result<(), IntCodeError<C>> {
+        self.snapshot().save(file_name)
+    }
+
+    pub fn load_snapshot(file_name: &str) -> Result<Self, IntCodeError<C>> {
+        Ok(IntCode::from_snapshot(Snapshot::load(file_name)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntCodeState;
+
+    // Reads values and outputs their running sum until a zero is read
+    const SUMMER: &str = "3,100,1006,100,14,1,100,101,101,4,101,1105,1,0,99";
+
+    fn paused_machine() -> IntCode {
+        let mut p = IntCode::string_to_program(SUMMER).unwrap();
+        p.set_overflow(Overflow::Wrap);
+        p.add_input(5);
+        p.add_input(7);
+        p.add_input(3);
+        assert_eq!(IntCodeState::Output(5), p.run_slice().unwrap());
+        assert_eq!(IntCodeState::Output(12), p.run_slice().unwrap());
+        p
+    }
+
+    #[test]
+    fn text_format_should_list_every_field() {
+        let snapshot = IntCode::string_to_program("109,-3,99").unwrap().snapshot();
+
+        assert_eq!("intcode-snapshot 2\npc 0\nrelative_base 0\noverflow trap\ninput\nmemory 109,-3,99\n", snapshot.to_text());
+    }
+
+    #[test]
+    fn restored_machine_should_continue_where_the_original_paused() {
+        let mut original = paused_machine();
+        let text = original.snapshot().to_text();
+        let mut restored = IntCode::from_snapshot(Snapshot::from_text(&text).unwrap());
+
+        assert_eq!(original.snapshot(), restored.snapshot());
+        restored.add_input(0);
+        original.add_input(0);
+        assert_eq!(original.run_program().unwrap(), restored.run_program().unwrap());
+    }
+
+    #[test]
+    fn snapshot_should_round_trip_through_a_file() {
+        let file_name = std::env::temp_dir().join(format!("intcode-snapshot-test-{}", std::process::id()));
+        let file_name = file_name.to_str().unwrap();
+        let original = paused_machine();
+        original.save_snapshot(file_name).unwrap();
+        let mut restored: IntCode = IntCode::load_snapshot(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+
+        assert_eq!(vec![3], restored.pending_input().iter().cloned().collect::<Vec<_>>());
+        restored.add_input(0);
+        assert_eq!(vec![15], restored.run_program().unwrap());
+    }
+
+    #[test]
+    fn pages_should_be_listed_after_the_image() {
+        let mut p = IntCode::string_to_program("99").unwrap();
+        p.poke(1 << 40, 5);
+        let text = p.snapshot().to_text();
+
+        assert!(text.contains("\nmemory 99\npage "));
+        assert_eq!(5, IntCode::from_snapshot(Snapshot::<Cell>::from_text(&text).unwrap()).peek(1 << 40));
+    }
+
+    #[test]
+    fn unsupported_version_should_be_an_error() {
+        let actual = Snapshot::<Cell>::from_text("intcode-snapshot 3\npc 0\n");
+
+        assert_eq!(Err(IntCodeError::InvalidSnapshot("line 1: Unsupported snapshot version 3".to_string())), actual);
+        let version_1 = "intcode-snapshot 1\npc 0\nrelative_base 0\noverflow trap\ninput\nmemory 99\n";
+        assert_eq!(vec![99], Snapshot::<Cell>::from_text(version_1).unwrap().memory);
+    }
+
+    #[test]
+    fn malformed_snapshots_should_be_errors() {
+        assert!(Snapshot::<Cell>::from_text("1,2,3").is_err());
+        assert!(Snapshot::<Cell>::from_text("intcode-snapshot 1\npc 0\nrelative_base 0\noverflow trap\ninput\n").is_err());
+        assert!(Snapshot::<Cell>::from_text("intcode-snapshot 1\npc x\n").is_err());
+        assert!(Snapshot::<Cell>::from_text("intcode-snapshot 1\ncolour red\n").is_err());
+        assert!(Snapshot::<Cell>::from_text("intcode-snapshot 2\npage 18446744073709551615 1,2\n").is_err());
+    }
+}
@@ -0,0 +1,850 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, Number, Overflow};
+
+/// Most assignments tried when a value computed from symbols has to become
+/// concrete, as an address, opcode or relative base.
+const CONCRETE_LIMIT: u128 = 4096;
+
+/// Most candidate assignments tried when solving one path.
+const SEARCH_LIMIT: u128 = 10_000_000;
+
+/// A value computed from symbols while running a program.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr<C = Cell> {
+    Constant(C),
+    Symbol(usize),
+    Add(Rc<Expr<C>>, Rc<Expr<C>>),
+    Multiply(Rc<Expr<C>>, Rc<Expr<C>>),
+    LessThan(Rc<Expr<C>>, Rc<Expr<C>>),
+    Equal(Rc<Expr<C>>, Rc<Expr<C>>),
+    /// A read through an address computed from symbols, of memory as it
+    /// was when the read happened.
+    Load(Rc<SymbolicMemory<C>>, Rc<Expr<C>>)
+}
+
+impl<C: Number> Expr<C> {
+    fn zero() -> Self {
+        Expr::Constant(C::zero())
+    }
+
+    fn is(&self, value: i64) -> bool {
+        matches!(self, Expr::Constant(c) if *c == C::from_i64(value))
+    }
+
+    fn sum(a: Expr<C>, b: Expr<C>) -> Self {
+        match (&a, &b) {
+            (Expr::Constant(x), Expr::Constant(y)) if x.checked_add(y).is_some() => Expr::Constant(x.checked_add(y).unwrap()),
+            _ if a.is(0) => b,
+            _ if b.is(0) => a,
+            _ => Expr::Add(Rc::new(a), Rc::new(b))
+        }
+    }
+
+    fn product(a: Expr<C>, b: Expr<C>) -> Self {
+        match (&a, &b) {
+            (Expr::Constant(x), Expr::Constant(y)) if x.checked_mul(y).is_some() => Expr::Constant(x.checked_mul(y).unwrap()),
+            _ if a.is(0) || b.is(0) => Expr::zero(),
+            _ if a.is(1) => b,
+            _ if b.is(1) => a,
+            _ => Expr::Multiply(Rc::new(a), Rc::new(b))
+        }
+    }
+
+    fn less_than(a: Expr<C>, b: Expr<C>) -> Self {
+        match (&a, &b) {
+            (Expr::Constant(x), Expr::Constant(y)) => Expr::Constant(C::from_i64((x < y) as i64)),
+            _ => Expr::LessThan(Rc::new(a), Rc::new(b))
+        }
+    }
+
+    fn equal(a: Expr<C>, b: Expr<C>) -> Self {
+        match (&a, &b) {
+            (Expr::Constant(x), Expr::Constant(y)) => Expr::Constant(C::from_i64((x == y) as i64)),
+            _ => Expr::Equal(Rc::new(a), Rc::new(b))
+        }
+    }
+
+    pub fn constant(&self) -> Option<&C> {
+        match self {
+            Expr::Constant(value) => Some(value),
+            _ => None
+        }
+    }
+
+    /// The symbols the value may depend on. A load depends on every symbol
+    /// in the memory it reads.
+    pub fn symbols(&self) -> BTreeSet<usize> {
+        let mut symbols = BTreeSet::new();
+        self.collect_symbols(&mut symbols);
+        symbols
+    }
+
+    fn collect_symbols(&self, symbols: &mut BTreeSet<usize>) {
+        match self {
+            Expr::Constant(_) => (),
+            Expr::Symbol(symbol) => { symbols.insert(*symbol); },
+            Expr::Add(a, b) | Expr::Multiply(a, b) | Expr::LessThan(a, b) | Expr::Equal(a, b) => {
+                a.collect_symbols(symbols);
+                b.collect_symbols(symbols);
+            },
+            Expr::Load(memory, address) => {
+                address.collect_symbols(symbols);
+                for cell in memory.cells() { cell.collect_symbols(symbols); }
+            }
+        }
+    }
+
+    /// The value for the given symbol values, None when the machine would
+    /// fail computing it: on overflow or a negative address.
+    pub fn eval(&self, values: &[C]) -> Option<C> {
+        self.eval_with(values, Overflow::Trap)
+    }
+
+    /// Like eval, with sums and products following the overflow policy.
+    pub fn eval_with(&self, values: &[C], overflow: Overflow) -> Option<C> {
+        let eval = |expr: &Expr<C>| expr.eval_with(values, overflow);
+        match self {
+            Expr::Constant(value) => Some(value.clone()),
+            Expr::Symbol(symbol) => values.get(*symbol).cloned(),
+            Expr::Add(a, b) => {
+                let (a, b) = (eval(a)?, eval(b)?);
+                match overflow {
+                    Overflow::Trap => a.checked_add(&b),
+                    Overflow::Wrap => Some(a.wrapping_add(&b)),
+                    Overflow::Saturate => Some(a.saturating_add(&b))
+                }
+            },
+            Expr::Multiply(a, b) => {
+                let (a, b) = (eval(a)?, eval(b)?);
+                match overflow {
+                    Overflow::Trap => a.checked_mul(&b),
+                    Overflow::Wrap => Some(a.wrapping_mul(&b)),
+                    Overflow::Saturate => Some(a.saturating_mul(&b))
+                }
+            },
+            Expr::LessThan(a, b) => Some(C::from_i64((eval(a)? < eval(b)?) as i64)),
+            Expr::Equal(a, b) => Some(C::from_i64((eval(a)? == eval(b)?) as i64)),
+            Expr::Load(memory, address) => {
+                let address = eval(address)?.to_i64().filter(|a| *a >= 0)?;
+                eval(&memory.get(address as usize))
+            }
+        }
+    }
+
+    /// The value as a sum of symbols times coefficients plus a constant,
+    /// when it is one.
+    fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Constant(value) => Some(Linear { terms: BTreeMap::new(), constant: value.to_i64()? as i128 }),
+            Expr::Symbol(symbol) => Some(Linear { terms: [(*symbol, 1)].into(), constant: 0 }),
+            Expr::Add(a, b) => a.linear()?.add(&b.linear()?),
+            Expr::Multiply(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                match (a.terms.is_empty(), b.terms.is_empty()) {
+                    (true, _) => b.scale(a.constant),
+                    (_, true) => a.scale(b.constant),
+                    _ => None
+                }
+            },
+            _ => None
+        }
+    }
+}
+
+impl<C: Number> fmt::Display for Expr<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Constant(value) => write!(f, "{}", value),
+            Expr::Symbol(symbol) => write!(f, "s{}", symbol),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Multiply(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equal(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(_, address) => write!(f, "mem[{}]", address)
+        }
+    }
+}
+
+struct Linear {
+    terms: BTreeMap<usize, i128>,
+    constant: i128
+}
+
+impl Linear {
+    fn add(mut self, other: &Linear) -> Option<Linear> {
+        for (symbol, coefficient) in &other.terms {
+            let term = self.terms.entry(*symbol).or_insert(0);
+            *term = term.checked_add(*coefficient)?;
+        }
+        self.terms.retain(|_, coefficient| *coefficient != 0);
+        Some(Linear { constant: self.constant.checked_add(other.constant)?, terms: self.terms })
+    }
+
+    fn scale(self, factor: i128) -> Option<Linear> {
+        let terms = self.terms.into_iter()
+            .map(|(symbol, coefficient)| Some((symbol, coefficient.checked_mul(factor)?)))
+            .collect::<Option<BTreeMap<_, _>>>()?;
+        let terms = terms.into_iter().filter(|(_, coefficient)| *coefficient != 0).collect();
+        Some(Linear { terms, constant: self.constant.checked_mul(factor)? })
+    }
+}
+
+/// Memory holding symbolic values, the image and any cells written beyond it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SymbolicMemory<C = Cell> {
+    image: Vec<Expr<C>>,
+    beyond: BTreeMap<usize, Expr<C>>
+}
+
+impl<C: Number> SymbolicMemory<C> {
+    pub fn get(&self, address: usize) -> Expr<C> {
+        match self.image.get(address) {
+            Some(value) => value.clone(),
+            None => self.beyond.get(&address).cloned().unwrap_or_else(Expr::zero)
+        }
+    }
+
+    fn set(&mut self, address: usize, value: Expr<C>) {
+        match self.image.get_mut(address) {
+            Some(cell) => *cell = value,
+            None => { self.beyond.insert(address, value); }
+        }
+    }
+
+    /// The cells written beyond the image, by address.
+    pub fn beyond(&self) -> impl Iterator<Item = (usize, &Expr<C>)> {
+        self.beyond.iter().map(|(address, value)| (*address, value))
+    }
+
+    fn cells(&self) -> impl Iterator<Item = &Expr<C>> {
+        self.image.iter().chain(self.beyond.values())
+    }
+}
+
+/// How a path through the program ended.
+#[derive(Debug, PartialEq, Clone)]
+pub enum End<C = Cell> {
+    Halted,
+    NeedInput,
+    StepLimit,
+    /// The machine fails with this error for the values taking the path.
+    Error(IntCodeError<C>)
+}
+
+/// One way through the program, taken for the symbol values that make all
+/// constraints non-zero.
+#[derive(Debug, Clone)]
+pub struct Path<C = Cell> {
+    pub constraints: Vec<Expr<C>>,
+    pub outputs: Vec<Expr<C>>,
+    pub end: End<C>,
+    /// Instructions executed along the path.
+    pub steps: usize,
+    memory: Rc<SymbolicMemory<C>>
+}
+
+impl<C: Number> Path<C> {
+    /// The cell at the end of the path.
+    pub fn cell(&self, address: usize) -> Expr<C> {
+        self.memory.get(address)
+    }
+
+    pub fn memory(&self) -> &SymbolicMemory<C> {
+        &self.memory
+    }
+}
+
+/// What a solution should make the program do.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Target<C = Cell> {
+    /// The output with this index, counting from 0, has the value.
+    Output(usize, C),
+    /// The cell holds the value when the program halts.
+    Cell(usize, C)
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Cell(usize),
+    Input
+}
+
+#[derive(Debug, Clone)]
+struct Symbol {
+    source: Source,
+    domain: RangeInclusive<i64>
+}
+
+#[derive(Clone)]
+struct State<C> {
+    pc: usize,
+    relative_base: C,
+    memory: Rc<SymbolicMemory<C>>,
+    input: VecDeque<Expr<C>>,
+    outputs: Vec<Expr<C>>,
+    constraints: Vec<Expr<C>>,
+    /// Values forced concrete on this path, so a fork that runs the
+    /// instruction again gets the same one.
+    assumed: Vec<(Expr<C>, C)>,
+    steps: usize
+}
+
+impl<C> State<C> {
+    fn into_path(self, end: End<C>) -> Path<C> {
+        Path { constraints: self.constraints, outputs: self.outputs, end, steps: self.steps, memory: self.memory }
+    }
+}
+
+/// Why a path stopped before the instruction completed.
+enum Stop<C> {
+    End(End<C>),
+    /// The whole exploration has to give up.
+    Fail(IntCodeError<C>)
+}
+
+type Outcome<T, C> = Result<T, Stop<C>>;
+
+/// Runs a program with chosen memory cells and inputs as symbols, to find
+/// values for them that make it reach a target.
+///
+/// The program splits into paths where a jump depends on symbols. Where an
+/// address, opcode or relative base depends on them, the path splits once
+/// for every value it can take, as long as there are few enough. Reads
+/// through a computed address stay symbolic.
+///
+/// A target value that depends linearly on the symbols is solved for
+/// directly, anything else is searched for within the symbol domains.
+/// Arithmetic on symbols follows the overflow policy of the program, and
+/// every solution is checked by running a copy of the program with it. Extension instructions end a
+/// path with an error.
+pub struct Symbolic<C = Cell> {
+    program: IntCode<C>,
+    symbols: Vec<Symbol>,
+    input: Vec<Expr<C>>,
+    overflow: Overflow,
+    max_paths: usize,
+    max_steps: usize
+}
+
+impl<C: Number> Symbolic<C> {
+    pub fn new(program: &IntCode<C>) -> Self {
+        Symbolic {
+            program: program.clone(),
+            symbols: Vec::new(),
+            input: Vec::new(),
+            overflow: program.snapshot().overflow,
+            max_paths: 1024,
+            max_steps: 1_000_000
+        }
+    }
+
+    /// Makes the cell a symbol with values in the domain, returning its
+    /// index in solutions.
+    pub fn cell(&mut self, address: usize, domain: RangeInclusive<i64>) -> usize {
+        self.symbols.push(Symbol { source: Source::Cell(address), domain });
+        self.symbols.len() - 1
+    }
+
+    /// Queues a symbolic input after the inputs added so far, returning its
+    /// index in solutions.
+    pub fn input(&mut self, domain: RangeInclusive<i64>) -> usize {
+        self.symbols.push(Symbol { source: Source::Input, domain });
+        self.input.push(Expr::Symbol(self.symbols.len() - 1));
+        self.symbols.len() - 1
+    }
+
+    pub fn add_input(&mut self, value: C) {
+        self.input.push(Expr::Constant(value));
+    }
+
+    /// Exploring more paths than this is an error.
+    pub fn set_max_paths(&mut self, max_paths: usize) {
+        self.max_paths = max_paths;
+    }
+
+    /// Paths longer than this many instructions end with End::StepLimit.
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+    }
+
+    fn start(&self) -> State<C> {
+        let snapshot = self.program.snapshot();
+        let mut memory = SymbolicMemory {
+            image: snapshot.memory.into_iter().map(Expr::Constant).collect(),
+            beyond: BTreeMap::new()
+        };
+        for (start, cells) in snapshot.pages {
+            for (offset, value) in cells.into_iter().enumerate().filter(|(_, v)| !v.is_zero()) {
+                memory.set(start + offset, Expr::Constant(value));
+            }
+        }
+        for (index, symbol) in self.symbols.iter().enumerate() {
+            if let Source::Cell(address) = symbol.source { memory.set(address, Expr::Symbol(index)); }
+        }
+        State {
+            pc: snapshot.pc,
+            relative_base: snapshot.relative_base,
+            memory: Rc::new(memory),
+            input: snapshot.input.into_iter().map(Expr::Constant).chain(self.input.iter().cloned()).collect(),
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            assumed: Vec::new(),
+            steps: 0
+        }
+    }
+
+    /// Every path through the program.
+    pub fn explore(&self) -> Result<Vec<Path<C>>, IntCodeError<C>> {
+        let mut work = vec![self.start()];
+        let mut started = 1;
+        let mut paths = Vec::new();
+        while let Some(mut state) = work.pop() {
+            let mut forks = Vec::new();
+            let end = loop {
+                if state.steps == self.max_steps { break End::StepLimit; }
+                let result = self.step(&mut state, &mut forks);
+                // Forks made before the step failed still have to be explored
+                if !forks.is_empty() {
+                    started += forks.len();
+                    if started > self.max_paths { return Err(IntCodeError::SearchLimit { what: "paths", limit: self.max_paths }); }
+                    work.append(&mut forks);
+                }
+                match result {
+                    Ok(()) => state.steps += 1,
+                    Err(Stop::End(end)) => break end,
+                    Err(Stop::Fail(error)) => return Err(error)
+                }
+            };
+            paths.push(state.into_path(end));
+        }
+        Ok(paths)
+    }
+
+    /// Values for the symbols, in the order they were made, that make the
+    /// program reach the target. None when no values in the domains do.
+    pub fn solve(&self, target: &Target<C>) -> Result<Option<Vec<C>>, IntCodeError<C>> {
+        for path in self.explore()? {
+            let goal = match target {
+                Target::Output(index, value) => path.outputs.get(*index).map(|output| (output.clone(), value)),
+                Target::Cell(address, value) if path.end == End::Halted => Some((path.cell(*address), value)),
+                Target::Cell(..) => None
+            };
+            if let Some((expr, value)) = goal {
+                if let Some(values) = self.solve_path(&path, &expr, value, target)? { return Ok(Some(values)); }
+            }
+        }
+        Ok(None)
+    }
+
+    fn solve_path(&self, path: &Path<C>, expr: &Expr<C>, value: &C, target: &Target<C>) -> Result<Option<Vec<C>>, IntCodeError<C>> {
+        let mut involved = expr.symbols();
+        for constraint in &path.constraints { involved.extend(constraint.symbols()); }
+        let start: Vec<i64> = self.symbols.iter().map(|s| *s.domain.start()).collect();
+        let accept = |values: &[i64]| -> Option<Vec<C>> {
+            let values: Vec<C> = values.iter().map(|v| C::from_i64(*v)).collect();
+            let reached = expr.eval_with(&values, self.overflow).as_ref() == Some(value)
+                && path.constraints.iter().all(|c| c.eval_with(&values, self.overflow).is_some_and(|v| !v.is_zero()));
+            (reached && self.check(&values, target)).then_some(values)
+        };
+        // Solving algebraically assumes sums and products never wrap or saturate
+        let linear = expr.linear().zip(value.to_i64())
+            .filter(|(linear, _)| self.overflow == Overflow::Trap && !linear.terms.is_empty());
+        match linear {
+            Some((linear, value)) => {
+                // Solve for the symbol with the largest domain, search the rest
+                let (solved, coefficient) = linear.terms.iter()
+                    .max_by_key(|(symbol, _)| domain_size(&self.symbols[**symbol].domain))
+                    .map(|(s, c)| (*s, *c))
+                    .unwrap();
+                involved.remove(&solved);
+                for mut values in self.assignments(&involved, start)? {
+                    let rest = linear.terms.iter()
+                        .filter(|(symbol, _)| **symbol != solved)
+                        .try_fold(linear.constant, |sum, (symbol, c)| i128::checked_add(sum, i128::checked_mul(*c, values[*symbol] as i128)?));
+                    let remaining = match rest.and_then(|rest| (value as i128).checked_sub(rest)) {
+                        Some(remaining) if remaining % coefficient == 0 => remaining / coefficient,
+                        _ => continue
+                    };
+                    match i64::try_from(remaining) {
+                        Ok(v) if self.symbols[solved].domain.contains(&v) => values[solved] = v,
+                        _ => continue
+                    }
+                    if let Some(solution) = accept(&values) { return Ok(Some(solution)); }
+                }
+                Ok(None)
+            },
+            None => Ok(self.assignments(&involved, start)?.find_map(|values| accept(&values)))
+        }
+    }
+
+    /// Every combination of values for the symbols, the others staying as
+    /// in base.
+    fn assignments(&self, symbols: &BTreeSet<usize>, base: Vec<i64>) -> Result<impl Iterator<Item = Vec<i64>> + '_, IntCodeError<C>> {
+        let symbols: Vec<usize> = symbols.iter().copied().collect();
+        let count = symbols.iter().try_fold(1u128, |n, s| n.checked_mul(domain_size(&self.symbols[*s].domain)));
+        if count.is_none_or(|n| n > SEARCH_LIMIT) {
+            return Err(IntCodeError::SearchLimit { what: "candidates", limit: SEARCH_LIMIT as usize });
+        }
+        Ok(Assignments { symbols, all: &self.symbols, next: Some(base) })
+    }
+
+    /// Runs a copy of the program with the values.
+    fn check(&self, values: &[C], target: &Target<C>) -> bool {
+        let mut machine = self.program.clone();
+        for (symbol, value) in self.symbols.iter().zip(values) {
+            if let Source::Cell(address) = symbol.source { machine.poke(address, value.clone()); }
+        }
+        for input in &self.input {
+            match input.eval_with(values, self.overflow) {
+                Some(value) => machine.add_input(value),
+                None => return false
+            }
+        }
+        let mut outputs = Vec::new();
+        for _ in 0..=self.max_steps {
+            match machine.step() {
+                Ok(None) => (),
+                Ok(Some(IntCodeState::Output(output))) => {
+                    outputs.push(output);
+                    if let Target::Output(index, value) = target {
+                        if let Some(output) = outputs.get(*index) { return output == value; }
+                    }
+                },
+                Ok(Some(IntCodeState::Done)) => return match target {
+                    Target::Output(..) => false,
+                    Target::Cell(address, value) => machine.peek(*address) == *value
+                },
+                Ok(Some(IntCodeState::NeedInput)) | Err(_) => return false
+            }
+        }
+        false
+    }
+
+    /// A value that has to be concrete. When it depends on symbols the path
+    /// splits, one fork for each other value it can take.
+    fn concrete(&self, state: &mut State<C>, forks: &mut Vec<State<C>>, value: Expr<C>, instruction: &C) -> Outcome<C, C> {
+        if let Expr::Constant(value) = value { return Ok(value); }
+        if let Some((_, known)) = state.assumed.iter().find(|(expr, _)| *expr == value) { return Ok(known.clone()); }
+        let symbols = value.symbols();
+        let too_many = || Stop::Fail(IntCodeError::SymbolicValue { pc: state.pc, instruction: instruction.clone() });
+        let count = symbols.iter().try_fold(1u128, |n, s| n.checked_mul(domain_size(&self.symbols[*s].domain)));
+        if count.is_none_or(|n| n > CONCRETE_LIMIT) { return Err(too_many()); }
+        let base = self.symbols.iter().map(|s| *s.domain.start()).collect();
+        let mut possible: Vec<C> = Vec::new();
+        for values in self.assignments(&symbols, base).map_err(|_| too_many())? {
+            let values: Vec<C> = values.into_iter().map(C::from_i64).collect();
+            if let Some(v) = value.eval_with(&values, self.overflow) {
+                if !possible.contains(&v) { possible.push(v); }
+            }
+        }
+        // Every value fails on the way, there is nothing to go on with
+        let nothing = || Stop::End(End::Error(IntCodeError::SymbolicValue { pc: state.pc, instruction: instruction.clone() }));
+        let first = possible.first().cloned().ok_or_else(nothing)?;
+        for v in possible.into_iter().skip(1) {
+            let mut fork = state.clone();
+            fork.constraints.push(Expr::equal(value.clone(), Expr::Constant(v.clone())));
+            fork.assumed.push((value.clone(), v));
+            forks.push(fork);
+        }
+        state.constraints.push(Expr::equal(value.clone(), Expr::Constant(first.clone())));
+        state.assumed.push((value, first.clone()));
+        Ok(first)
+    }
+
+    fn address(&self, state: &State<C>, value: C, instruction: &C) -> Outcome<usize, C> {
+        let pc = state.pc;
+        if value < C::zero() {
+            return Err(Stop::End(End::Error(IntCodeError::NegativeAddress { pc, instruction: instruction.clone(), address: value })));
+        }
+        match value.to_i64() {
+            Some(a) if a as u64 <= usize::MAX as u64 => Ok(a as usize),
+            _ => Err(Stop::End(End::Error(IntCodeError::AddressOutOfRange { pc, instruction: instruction.clone(), address: value })))
+        }
+    }
+
+    fn relative(&self, state: &State<C>, offset: &C, instruction: &C) -> Outcome<usize, C> {
+        let overflow = || Stop::End(End::Error(IntCodeError::Overflow { pc: state.pc, instruction: instruction.clone() }));
+        let address = offset.checked_add(&state.relative_base).ok_or_else(overflow)?;
+        self.address(state, address, instruction)
+    }
+
+    fn read(&self, state: &mut State<C>, modes: &[i64], index: usize, instruction: &C) -> Outcome<Expr<C>, C> {
+        let parameter = state.memory.get(state.pc + 1 + index);
+        let invalid = |mode| Stop::End(End::Error(IntCodeError::InvalidMode { pc: state.pc, instruction: instruction.clone(), mode }));
+        match (modes[index], parameter) {
+            (1, parameter) => Ok(parameter),
+            (0, Expr::Constant(address)) => Ok(state.memory.get(self.address(state, address, instruction)?)),
+            (2, Expr::Constant(offset)) => Ok(state.memory.get(self.relative(state, &offset, instruction)?)),
+            (0, address) => Ok(Expr::Load(state.memory.clone(), Rc::new(address))),
+            (2, offset) => {
+                let address = Expr::sum(offset, Expr::Constant(state.relative_base.clone()));
+                Ok(Expr::Load(state.memory.clone(), Rc::new(address)))
+            },
+            (mode, _) => Err(invalid(mode))
+        }
+    }
+
+    fn write_address(&self, state: &mut State<C>, forks: &mut Vec<State<C>>, modes: &[i64], index: usize, instruction: &C) -> Outcome<usize, C> {
+        let parameter = state.memory.get(state.pc + 1 + index);
+        let fail = |error| Err(Stop::End(End::Error(error)));
+        match modes[index] {
+            0 => {
+                let address = self.concrete(state, forks, parameter, instruction)?;
+                self.address(state, address, instruction)
+            },
+            1 => fail(IntCodeError::WriteInImmediateMode { pc: state.pc, instruction: instruction.clone() }),
+            2 => {
+                let offset = self.concrete(state, forks, parameter, instruction)?;
+                self.relative(state, &offset, instruction)
+            },
+            mode => fail(IntCodeError::InvalidMode { pc: state.pc, instruction: instruction.clone(), mode })
+        }
+    }
+
+    fn arithmetic(&self, state: &State<C>, a: Expr<C>, b: Expr<C>, multiply: bool, instruction: &C) -> Outcome<Expr<C>, C> {
+        let (x, y) = match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => (x, y),
+            _ if multiply => return Ok(Expr::product(a, b)),
+            _ => return Ok(Expr::sum(a, b))
+        };
+        let value = match (self.overflow, multiply) {
+            (Overflow::Trap, false) => x.checked_add(y),
+            (Overflow::Trap, true) => x.checked_mul(y),
+            (Overflow::Wrap, false) => Some(x.wrapping_add(y)),
+            (Overflow::Wrap, true) => Some(x.wrapping_mul(y)),
+            (Overflow::Saturate, false) => Some(x.saturating_add(y)),
+            (Overflow::Saturate, true) => Some(x.saturating_mul(y))
+        };
+        let overflow = || Stop::End(End::Error(IntCodeError::Overflow { pc: state.pc, instruction: instruction.clone() }));
+        value.map(Expr::Constant).ok_or_else(overflow)
+    }
+
+    /// Executes one instruction, leaving any forks it makes in forks.
+    fn step(&self, state: &mut State<C>, forks: &mut Vec<State<C>>) -> Outcome<(), C> {
+        let pc = state.pc;
+        let word = state.memory.get(pc);
+        let instruction = self.concrete(state, forks, word.clone(), &C::zero())?;
+        let code = instruction.to_i64().unwrap_or(-1);
+        let modes = [code / 100 % 10, code / 1000 % 10, code / 10000 % 10];
+        let end = |error| Err(Stop::End(End::Error(error)));
+        match code % 100 {
+            opcode @ (1 | 2 | 7 | 8) => {
+                let a = self.read(state, &modes, 0, &instruction)?;
+                let b = self.read(state, &modes, 1, &instruction)?;
+                let address = self.write_address(state, forks, &modes, 2, &instruction)?;
+                let value = match opcode {
+                    1 | 2 => self.arithmetic(state, a, b, opcode == 2, &instruction)?,
+                    7 => Expr::less_than(a, b),
+                    _ => Expr::equal(a, b)
+                };
+                Rc::make_mut(&mut state.memory).set(address, value);
+                state.pc += 4;
+            },
+            3 => {
+                if state.input.is_empty() { return Err(Stop::End(End::NeedInput)); }
+                let address = self.write_address(state, forks, &modes, 0, &instruction)?;
+                let value = state.input.pop_front().unwrap();
+                Rc::make_mut(&mut state.memory).set(address, value);
+                state.pc += 2;
+            },
+            4 => {
+                let value = self.read(state, &modes, 0, &instruction)?;
+                state.outputs.push(value);
+                state.pc += 2;
+            },
+            opcode @ (5 | 6) => {
+                let condition = self.read(state, &modes, 0, &instruction)?;
+                let jumps_on_zero = opcode == 6;
+                let zero = Expr::equal(condition.clone(), Expr::zero());
+                let assumed = state.assumed.iter().find(|(expr, _)| *expr == zero).map(|(_, value)| !value.is_zero());
+                let jumps = match (condition.constant(), assumed) {
+                    (Some(value), _) => value.is_zero() == jumps_on_zero,
+                    (None, Some(is_zero)) => is_zero == jumps_on_zero,
+                    (None, None) => {
+                        let (jump, fall) = if jumps_on_zero { (zero.clone(), condition) } else { (condition, zero.clone()) };
+                        let mut fork = state.clone();
+                        fork.constraints.push(fall);
+                        fork.assumed.push((zero.clone(), C::from_i64(!jumps_on_zero as i64)));
+                        fork.pc += 3;
+                        fork.steps += 1;
+                        forks.push(fork);
+                        state.constraints.push(jump);
+                        state.assumed.push((zero, C::from_i64(jumps_on_zero as i64)));
+                        true
+                    }
+                };
+                if jumps {
+                    let target = self.read(state, &modes, 1, &instruction)?;
+                    let target = self.concrete(state, forks, target, &instruction)?;
+                    state.pc = self.address(state, target, &instruction)?;
+                } else {
+                    state.pc += 3;
+                }
+            },
+            9 => {
+                let value = self.read(state, &modes, 0, &instruction)?;
+                let value = self.concrete(state, forks, value, &instruction)?;
+                let overflow = IntCodeError::Overflow { pc, instruction: instruction.clone() };
+                state.relative_base = match value.checked_add(&state.relative_base) {
+                    Some(base) => base,
+                    None => return end(overflow)
+                };
+                state.pc += 2;
+            },
+            99 => return Err(Stop::End(End::Halted)),
+            opcode if self.program.opcode_of(opcode).is_some() => return end(IntCodeError::Extension {
+                pc, instruction, message: "Extensions cannot be executed symbolically".to_string()
+            }),
+            _ => return end(IntCodeError::InvalidOpcode { pc, instruction })
+        }
+        Ok(())
+    }
+}
+
+fn domain_size(domain: &RangeInclusive<i64>) -> u128 {
+    match domain.is_empty() {
+        true => 0,
+        false => (*domain.end() as i128 - *domain.start() as i128 + 1) as u128
+    }
+}
+
+/// Counts through the domains of some symbols like an odometer.
+struct Assignments<'a> {
+    symbols: Vec<usize>,
+    all: &'a [Symbol],
+    next: Option<Vec<i64>>
+}
+
+impl Iterator for Assignments<'_> {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Vec<i64>> {
+        let current = self.next.take()?;
+        if self.symbols.iter().any(|s| self.all[*s].domain.is_empty()) { return None; }
+        let mut next = current.clone();
+        for symbol in &self.symbols {
+            let domain = &self.all[*symbol].domain;
+            let start = *domain.start();
+            if next[*symbol] < start { next[*symbol] = start; }
+            if next[*symbol] < *domain.end() {
+                next[*symbol] += 1;
+                self.next = Some(next);
+                return Some(current);
+            }
+            next[*symbol] = start;
+        }
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbolic(program: &str) -> Symbolic {
+        Symbolic::new(&IntCode::string_to_program(program).unwrap())
+    }
+
+    #[test]
+    fn patched_cells_should_be_solved_for_algebraically() {
+        let program = IntCode::file_to_program("../day2/src/day2.txt").unwrap();
+        let mut symbolic = Symbolic::new(&program);
+        let noun = symbolic.cell(1, 0..=99);
+        let verb = symbolic.cell(2, 0..=99);
+        let paths = symbolic.explore().unwrap();
+
+        assert_eq!(1, paths.len());
+        assert!(paths[0].cell(0).linear().is_some());
+        let values = symbolic.solve(&Target::Cell(0, 19690720)).unwrap().unwrap();
+        assert_eq!((71, 95), (values[noun], values[verb]));
+    }
+
+    #[test]
+    fn products_of_inputs_should_be_searched_for() {
+        let mut symbolic = symbolic("3,20,3,21,2,20,21,22,1001,22,1,22,4,22,99");
+        symbolic.input(0..=9);
+        symbolic.input(0..=9);
+
+        let values = symbolic.solve(&Target::Output(0, 13)).unwrap().unwrap();
+        assert_eq!(12, values[0] * values[1]);
+        assert_eq!(None, symbolic.solve(&Target::Output(0, 99)).unwrap());
+    }
+
+    #[test]
+    fn jumps_on_symbols_should_split_paths() {
+        let mut symbolic = symbolic("3,30,1007,30,5,31,1005,31,17,1002,30,2,32,4,32,99,0,104,1,99");
+        symbolic.input(-100..=100);
+
+        let paths = symbolic.explore().unwrap();
+        assert_eq!(2, paths.len());
+        assert_eq!(vec![7], symbolic.solve(&Target::Output(0, 14)).unwrap().unwrap());
+        assert_eq!(vec![-100], symbolic.solve(&Target::Output(0, 1)).unwrap().unwrap());
+        assert_eq!(None, symbolic.solve(&Target::Output(0, 15)).unwrap());
+    }
+
+    #[test]
+    fn reads_through_symbols_should_see_memory_as_it_was() {
+        let mut symbolic = symbolic("3,3,4,0,99,42");
+        symbolic.input(0..=5);
+
+        assert_eq!(vec![5], symbolic.solve(&Target::Output(0, 42)).unwrap().unwrap());
+    }
+
+    #[test]
+    fn jump_targets_on_symbols_should_fork_per_value() {
+        let mut symbolic = symbolic("3,100,106,0,100,104,1,99,104,2,99");
+        symbolic.input(0..=10);
+        symbolic.set_max_steps(1000);
+
+        let paths = symbolic.explore().unwrap();
+        assert_eq!(11, paths.len());
+        assert!(paths.iter().any(|p| p.end == End::NeedInput));
+        // Jumping to 2 jumps there forever
+        assert!(paths.iter().any(|p| p.end == End::StepLimit));
+        assert_eq!(vec![8], symbolic.solve(&Target::Output(0, 2)).unwrap().unwrap());
+    }
+
+    #[test]
+    fn forks_should_survive_a_first_value_that_fails() {
+        let mut symbolic = symbolic("3,100,106,0,100,104,7,99");
+        symbolic.input(-3..=10);
+
+        let paths = symbolic.explore().unwrap();
+        assert_eq!(14, paths.len());
+        assert!(matches!(paths[0].end, End::Error(IntCodeError::NegativeAddress { .. })));
+        assert_eq!(vec![5], symbolic.solve(&Target::Output(0, 7)).unwrap().unwrap());
+    }
+
+    #[test]
+    fn symbols_should_follow_the_overflow_policy() {
+        let program = "3,100,1001,100,9223372036854775807,101,4,101,99";
+        let mut symbolic = symbolic(program);
+        symbolic.input(1..=10);
+        assert_eq!(None, symbolic.solve(&Target::Output(0, i64::MAX)).unwrap());
+
+        let mut p = IntCode::string_to_program(program).unwrap();
+        p.set_overflow(Overflow::Wrap);
+        let mut symbolic = Symbolic::new(&p);
+        symbolic.input(0..=10);
+        assert_eq!(vec![1], symbolic.solve(&Target::Output(0, i64::MIN)).unwrap().unwrap());
+
+        p.set_overflow(Overflow::Saturate);
+        let mut symbolic = Symbolic::new(&p);
+        symbolic.input(1..=10);
+        assert_eq!(vec![1], symbolic.solve(&Target::Output(0, i64::MAX)).unwrap().unwrap());
+    }
+
+    #[test]
+    fn search_should_stay_bounded() {
+        let mut symbolic = symbolic("3,20,3,21,2,20,21,22,4,22,99");
+        symbolic.input(0..=1_000_000);
+        symbolic.input(0..=1_000_000);
+
+        assert_eq!(Err(IntCodeError::SearchLimit { what: "candidates", limit: 10_000_000 }), symbolic.solve(&Target::Output(0, 7)));
+    }
+}
@@ -0,0 +1,259 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::{Cell, IntCode, IntCodeError, Number};
+use crate::channels::{channel, Node, Status};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TopologyError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for TopologyError {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MachineSpec<C = Cell> {
+    pub name: String,
+    pub program: String,
+    pub input: Vec<C>
+}
+
+/// A network of named machines where each machine's output is sent to the
+/// input of the machines it is connected to.
+///
+/// The text format has one statement per line, `#` starts a comment:
+///
+/// ```text
+/// machine <name> <program file> [input <value>, ...]
+/// <name> -> <name>, ...
+/// ```
+///
+/// Initial input values are queued before anything else arrives, so day7's
+/// phase settings go there.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Topology<C = Cell> {
+    pub machines: Vec<MachineSpec<C>>,
+    /// Pairs of machine indexes, from output to input.
+    pub connections: Vec<(usize, usize)>
+}
+
+/// How a machine ended up when the network stopped.
+#[derive(Debug, PartialEq, Clone)]
+pub enum MachineStatus<C = Cell> {
+    Halted,
+    /// Waiting for input when no other machine could send any.
+    Blocked,
+    Failed(IntCodeError<C>)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MachineReport<C = Cell> {
+    pub name: String,
+    pub status: MachineStatus<C>,
+    pub output: Vec<C>
+}
+
+impl<C: Number> fmt::Display for MachineReport<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match &self.status {
+            MachineStatus::Halted => "halted".to_string(),
+            MachineStatus::Blocked => "blocked on input".to_string(),
+            MachineStatus::Failed(e) => format!("failed: {}", e)
+        };
+        let output: Vec<String> = self.output.iter().map(|v| v.to_string()).collect();
+        write!(f, "{}: {}, output [{}]", self.name, status, output.join(", "))
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_machine<C: Number>(words: &str) -> Result<MachineSpec<C>, String> {
+    let mut parts = words.split_whitespace();
+    let name = parts.next().ok_or("Missing machine name")?;
+    if !is_name(name) { return Err(format!("Invalid machine name '{}'", name)); }
+    let program = parts.next().ok_or_else(|| format!("Missing program file for '{}'", name))?;
+    let input = match parts.next() {
+        None => Vec::new(),
+        Some("input") => parts.collect::<Vec<_>>().join(" ").split(',')
+            .map(|v| v.trim().parse().map_err(|_| format!("Invalid input value '{}'", v.trim())))
+            .collect::<Result<_, _>>()?,
+        Some(extra) => return Err(format!("Unexpected '{}'", extra))
+    };
+    Ok(MachineSpec { name: name.to_string(), program: program.to_string(), input })
+}
+
+impl<C: Number> Topology<C> {
+    pub fn parse(text: &str) -> Result<Self, TopologyError> {
+        let mut machines: Vec<MachineSpec<C>> = Vec::new();
+        let mut links = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let error = |message| TopologyError { line: i + 1, message };
+            let line = match line.find('#') {
+                Some(j) => &line[..j],
+                None => line
+            }.trim();
+            if line.is_empty() { continue; }
+            if let Some(rest) = line.strip_prefix("machine ") {
+                let machine = parse_machine(rest).map_err(error)?;
+                if machines.iter().any(|m| m.name == machine.name) {
+                    return Err(error(format!("Machine '{}' defined twice", machine.name)));
+                }
+                machines.push(machine);
+            } else if let Some((from, to)) = line.split_once("->") {
+                for target in to.split(',') {
+                    links.push((i + 1, from.trim().to_string(), target.trim().to_string()));
+                }
+            } else {
+                return Err(error(format!("Expected 'machine' or '->' in '{}'", line)));
+            }
+        }
+
+        // Connections may name machines defined further down
+        let index = |line: usize, name: &str| machines.iter().position(|m| m.name == name)
+            .ok_or_else(|| TopologyError { line, message: format!("Unknown machine '{}'", name) });
+        let connections = links.iter()
+            .map(|(line, from, to)| Ok((index(*line, from)?, index(*line, to)?)))
+            .collect::<Result<_, _>>()?;
+        Ok(Topology { machines, connections })
+    }
+
+    /// Reads a topology file. Program files are relative to its directory.
+    pub fn load(file_name: &str) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(file_name).map_err(|e| format!("{}: {}", file_name, e))?;
+        let mut topology = Topology::parse(&text)?;
+        let dir = Path::new(file_name).parent().unwrap_or_else(|| Path::new(""));
+        for machine in &mut topology.machines {
+            machine.program = dir.join(&machine.program).to_string_lossy().into_owned();
+        }
+        Ok(topology)
+    }
+
+    /// Reads every program and runs the network on the current thread until
+    /// each machine has halted, failed, or is waiting for input that will
+    /// never come. Every value a machine outputs ends up in its report.
+    pub fn run(&self) -> Result<Vec<MachineReport<C>>, IntCodeError<C>> {
+        let (senders, receivers): (Vec<_>, Vec<_>) = self.machines.iter().map(|_| channel()).unzip();
+        let mut nodes = Vec::new();
+        let mut collected = Vec::new();
+        for (spec, receiver) in self.machines.iter().zip(receivers) {
+            let mut machine = IntCode::from_file(&spec.program)?;
+            spec.input.iter().for_each(|v| machine.add_input(v.clone()));
+            let mut node = Node::new(machine, receiver);
+            let (collector, output) = channel();
+            node.connect(collector);
+            collected.push(output);
+            nodes.push(node);
+        }
+        for (from, to) in &self.connections {
+            nodes[*from].connect(senders[*to].clone());
+        }
+
+        let mut statuses: Vec<Option<MachineStatus<C>>> = vec![None; nodes.len()];
+        loop {
+            let sent_before: u64 = nodes.iter().map(|n| n.sent()).sum();
+            for (node, status) in nodes.iter_mut().zip(statuses.iter_mut()) {
+                if let Some(MachineStatus::Halted) | Some(MachineStatus::Failed(_)) = status { continue; }
+                *status = Some(match node.poll() {
+                    Ok(Status::Halted) => MachineStatus::Halted,
+                    Ok(Status::Blocked) => MachineStatus::Blocked,
+                    Err(e) => MachineStatus::Failed(e)
+                });
+            }
+            let sent_after: u64 = nodes.iter().map(|n| n.sent()).sum();
+            if sent_after == sent_before { break; }
+        }
+
+        Ok(self.machines.iter().zip(statuses).zip(collected)
+            .map(|((spec, status), output)| MachineReport {
+                name: spec.name.clone(),
+                status: status.unwrap(),
+                output: output.try_iter().collect()
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEEDBACK: &str = "
+        # day7 feedback loop, phases first then the initial signal
+        machine a ../day7/src/day7.txt input 6, 0
+        machine b ../day7/src/day7.txt input 7
+        machine c ../day7/src/day7.txt input 9
+        machine d ../day7/src/day7.txt input 5
+        machine e ../day7/src/day7.txt input 8
+        a -> b
+        b -> c
+        c -> d
+        d -> e
+        e -> a
+    ";
+
+    #[test]
+    fn parse_should_resolve_names() {
+        let topology: Topology = Topology::parse(FEEDBACK).unwrap();
+
+        assert_eq!(5, topology.machines.len());
+        assert_eq!(vec![6, 0], topology.machines[0].input);
+        assert_eq!((4, 0), topology.connections[4]);
+    }
+
+    #[test]
+    fn fan_out_should_connect_every_target() {
+        let topology: Topology = Topology::parse("a -> b, c\nmachine a x\nmachine b x\nmachine c x").unwrap();
+
+        assert_eq!(vec![(0, 1), (0, 2)], topology.connections);
+    }
+
+    #[test]
+    fn input_should_only_be_a_keyword_after_the_program() {
+        let topology: Topology = Topology::parse("machine a inputs/a.txt\nmachine b input.txt input 1,2").unwrap();
+
+        assert_eq!(("inputs/a.txt", vec![]), (topology.machines[0].program.as_str(), topology.machines[0].input.clone()));
+        assert_eq!(("input.txt", vec![1, 2]), (topology.machines[1].program.as_str(), topology.machines[1].input.clone()));
+        assert!(Topology::<Cell>::parse("machine a x.txt inputs 1").is_err());
+    }
+
+    #[test]
+    fn parse_errors_should_carry_the_line_number() {
+        let parse = |text| Topology::<Cell>::parse(text).unwrap_err();
+
+        assert_eq!(TopologyError { line: 2, message: "Unknown machine 'b'".to_string() }, parse("machine a x\na -> b"));
+        assert_eq!(2, parse("machine a x\nmachine a y").line);
+        assert_eq!(1, parse("machine a x input 1, y").line);
+        assert_eq!(1, parse("machine 1a x").line);
+        assert_eq!(1, parse("run a").line);
+    }
+
+    #[test]
+    fn feedback_loop_should_match_day7() {
+        let reports = Topology::<Cell>::parse(FEEDBACK).unwrap().run().unwrap();
+
+        assert!(reports.iter().all(|r| r.status == MachineStatus::Halted));
+        assert_eq!(Some(&79846026), reports[4].output.last());
+    }
+
+    #[test]
+    fn starved_machines_should_be_reported_as_blocked() {
+        let reports = Topology::<Cell>::parse("machine a ../day7/src/day7.txt input 0\nmachine b ../day7/src/day7.txt\na -> b").unwrap().run().unwrap();
+
+        assert_eq!(MachineStatus::Blocked, reports[0].status);
+        assert_eq!(MachineStatus::Blocked, reports[1].status);
+        assert_eq!("b: blocked on input, output []", reports[1].to_string());
+    }
+}
@@ -0,0 +1,670 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::{Cell, IntCode, IntCodeError, IntCodeState, MemoryStats, Number, Observer};
+use crate::input::{self, InputProvider};
+use crate::instruction::{Extension, Opcode};
+
+/// What a single executed instruction did.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TraceRecord<C = Cell> {
+    /// Number of instructions traced before this one.
+    pub step: u64,
+    pub pc: usize,
+    pub instruction: C,
+    /// Parameter values as resolved, in order. A jump that is not taken
+    /// never resolves its target.
+    pub operands: Vec<C>,
+    pub writes: Vec<(usize, C)>,
+    /// The relative base after the instruction.
+    pub relative_base: C,
+    pub input: Option<C>,
+    pub output: Option<C>,
+    /// The extension the instruction ran, if any. Trace files do not keep
+    /// it, records read back only know the standard opcodes.
+    pub extension: Option<Extension>
+}
+
+impl<C: Number> TraceRecord<C> {
+    fn new(step: u64) -> Self {
+        TraceRecord {
+            step, pc: 0, instruction: C::zero(), operands: Vec::new(), writes: Vec::new(),
+            relative_base: C::zero(), input: None, output: None, extension: None
+        }
+    }
+
+    pub fn opcode(&self) -> Option<Opcode> {
+        if let Some(extension) = self.extension { return Some(Opcode::Extension(extension)); }
+        Opcode::from_code(self.instruction.to_i64()? % 100)
+    }
+
+    pub fn writes_to(&self, address: usize) -> bool {
+        self.writes.iter().any(|(a, _)| *a == address)
+    }
+}
+
+impl<C: Number> fmt::Display for TraceRecord<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = self.opcode().map_or("?", |op| op.mnemonic());
+        let operands: Vec<String> = self.operands.iter().map(|v| v.to_string()).collect();
+        write!(f, "{:8} {:5}: {:15} ({})", self.step, self.pc, mnemonic, operands.join(", "))?;
+        for (address, value) in &self.writes {
+            write!(f, " [{}] <- {}", address, value)?;
+        }
+        if let Some(value) = &self.input { write!(f, " in {}", value)?; }
+        if let Some(value) = &self.output { write!(f, " out {}", value)?; }
+        write!(f, " rb {}", self.relative_base)
+    }
+}
+
+/// Collects the observer callbacks of one step into a record.
+struct Recorder<C> {
+    record: TraceRecord<C>
+}
+
+impl<C: Number> Observer<C> for Recorder<C> {
+    fn instruction(&mut self, pc: usize, instruction: &C) {
+        self.record.pc = pc;
+        self.record.instruction = instruction.clone();
+    }
+
+    fn parameter(&mut self, value: &C) {
+        self.record.operands.push(value.clone());
+    }
+
+    fn write(&mut self, address: usize, _old: &C, new: &C) {
+        self.record.writes.push((address, new.clone()));
+    }
+
+    fn input(&mut self, value: &C) {
+        self.record.input = Some(value.clone());
+    }
+
+    fn output(&mut self, value: &C) {
+        self.record.output = Some(value.clone());
+    }
+}
+
+/// Where trace records go.
+pub trait TraceSink<C> {
+    fn record(&mut self, record: &TraceRecord<C>) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Keeps the most recent records in memory.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<C = Cell> {
+    capacity: usize,
+    records: VecDeque<TraceRecord<C>>
+}
+
+impl<C> RingBuffer<C> {
+    pub fn new(capacity: usize) -> Self {
+        RingBuffer { capacity, records: VecDeque::with_capacity(capacity) }
+    }
+
+    /// The retained records, oldest first.
+    pub fn records(&self) -> &VecDeque<TraceRecord<C>> {
+        &self.records
+    }
+}
+
+impl<C: Clone> TraceSink<C> for RingBuffer<C> {
+    fn record(&mut self, record: &TraceRecord<C>) -> io::Result<()> {
+        if self.capacity == 0 { return Ok(()); }
+        if self.records.len() == self.capacity { self.records.pop_front(); }
+        self.records.push_back(record.clone());
+        Ok(())
+    }
+}
+
+/// Writes one JSON object per record and line.
+pub struct JsonLines<W> {
+    writer: W
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLines { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn json_option<C: Number>(value: &Option<C>) -> String {
+    value.as_ref().map_or("null".to_string(), |v| v.to_string())
+}
+
+impl<C: Number, W: Write> TraceSink<C> for JsonLines<W> {
+    fn record(&mut self, record: &TraceRecord<C>) -> io::Result<()> {
+        let operands: Vec<String> = record.operands.iter().map(|v| v.to_string()).collect();
+        let writes: Vec<String> = record.writes.iter().map(|(a, v)| format!("[{},{}]", a, v)).collect();
+        let mnemonic = record.opcode().map_or("?", |op| op.mnemonic());
+        writeln!(self.writer,
+            "{{\"step\":{},\"pc\":{},\"instruction\":{},\"opcode\":\"{}\",\"operands\":[{}],\"writes\":[{}],\"relative_base\":{},\"input\":{},\"output\":{}}}",
+            record.step, record.pc, record.instruction, mnemonic, operands.join(","), writes.join(","),
+            record.relative_base, json_option(&record.input), json_option(&record.output))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Identifies the binary format, the last byte is the format version.
+pub const BINARY_MAGIC: &[u8; 8] = b"ICTRACE\x01";
+
+const HAS_INPUT: u8 = 1;
+const HAS_OUTPUT: u8 = 2;
+
+/// A compact binary format: a header followed by records made of LEB128
+/// varints, cells are zigzag encoded. Only cells that fit in an i64 can be
+/// written.
+pub struct Binary<W> {
+    writer: W
+}
+
+impl<W: Write> Binary<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(BINARY_MAGIC)?;
+        Ok(Binary { writer })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn varint(&mut self, mut value: u64) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(10);
+        while value >= 0x80 {
+            bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+        self.writer.write_all(&bytes)
+    }
+
+    fn cell<C: Number>(&mut self, value: &C) -> io::Result<()> {
+        let value = value.to_i64().ok_or_else(|| invalid_data(format!("Cell {} does not fit in 64 bits", value)))?;
+        self.varint(((value << 1) ^ (value >> 63)) as u64)
+    }
+}
+
+impl<C: Number, W: Write> TraceSink<C> for Binary<W> {
+    fn record(&mut self, record: &TraceRecord<C>) -> io::Result<()> {
+        self.varint(record.step)?;
+        self.varint(record.pc as u64)?;
+        self.cell(&record.instruction)?;
+        self.varint(record.operands.len() as u64)?;
+        for value in &record.operands { self.cell(value)?; }
+        self.varint(record.writes.len() as u64)?;
+        for (address, value) in &record.writes {
+            self.varint(*address as u64)?;
+            self.cell(value)?;
+        }
+        self.cell(&record.relative_base)?;
+        let flags = if record.input.is_some() { HAS_INPUT } else { 0 } | if record.output.is_some() { HAS_OUTPUT } else { 0 };
+        self.writer.write_all(&[flags])?;
+        if let Some(value) = &record.input { self.cell(value)?; }
+        if let Some(value) = &record.output { self.cell(value)?; }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Runs a machine while sending a record of every executed instruction to
+/// the sink. A step that stops to wait for input is not recorded, neither
+/// is a step that fails.
+pub struct Tracer<S, C = Cell> {
+    sink: S,
+    steps: u64,
+    peak_memory: Option<MemoryStats>,
+    cell: std::marker::PhantomData<C>
+}
+
+fn io_error<C>(e: io::Error) -> IntCodeError<C> {
+    IntCodeError::Io(e.to_string())
+}
+
+impl<C: Number, S: TraceSink<C>> Tracer<S, C> {
+    pub fn new(sink: S) -> Self {
+        Tracer { sink, steps: 0, peak_memory: None, cell: std::marker::PhantomData }
+    }
+
+    /// Memory usage when the traced machines had the most allocated.
+    pub fn peak_memory(&self) -> Option<MemoryStats> {
+        self.peak_memory
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+
+    pub fn flush(&mut self) -> Result<(), IntCodeError<C>> {
+        self.sink.flush().map_err(io_error)
+    }
+
+    /// Like IntCode::step, recording the instruction.
+    pub fn step(&mut self, machine: &mut IntCode<C>) -> Result<Option<IntCodeState<C>>, IntCodeError<C>> {
+        let mut recorder = Recorder { record: TraceRecord::new(self.steps) };
+        let state = machine.step_with(&mut recorder)?;
+        if state == Some(IntCodeState::NeedInput) { return Ok(state); }
+        recorder.record.relative_base = machine.relative_base().clone();
+        if let Some(Opcode::Extension(extension)) = recorder.record.instruction.to_i64().and_then(|word| machine.opcode_of(word % 100)) {
+            recorder.record.extension = Some(extension);
+        }
+        let memory = machine.memory_stats();
+        if self.peak_memory.is_none_or(|peak| memory.cells > peak.cells) { self.peak_memory = Some(memory); }
+        self.sink.record(&recorder.record).map_err(io_error)?;
+        self.steps += 1;
+        Ok(state)
+    }
+
+    /// Like IntCode::run_slice, recording every instruction.
+    pub fn run_slice(&mut self, machine: &mut IntCode<C>) -> Result<IntCodeState<C>, IntCodeError<C>> {
+        loop {
+            if let Some(state) = self.step(machine)? { return Ok(state); }
+        }
+    }
+
+    /// Like IntCode::run_slice_from, recording every instruction.
+    pub fn run_slice_from(&mut self, machine: &mut IntCode<C>, input: &mut impl InputProvider<C>) -> Result<IntCodeState<C>, IntCodeError<C>> {
+        input::run_slice_from(machine, input, |machine| self.run_slice(machine))
+    }
+
+    /// Like IntCode::run_program, recording every instruction.
+    pub fn run_program(&mut self, machine: &mut IntCode<C>) -> Result<Vec<C>, IntCodeError<C>> {
+        let mut output = Vec::new();
+        loop {
+            match self.run_slice(machine)? {
+                IntCodeState::Done => return Ok(output),
+                IntCodeState::NeedInput => return Err(IntCodeError::InputStarvation {
+                    pc: machine.pc(), instruction: machine.peek(machine.pc())
+                }),
+                IntCodeState::Output(value) => output.push(value)
+            }
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The subset of JSON written by JsonLines.
+#[derive(Debug)]
+enum Json {
+    Null,
+    Number(String),
+    String,
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize
+}
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.pos = self.text.len() - self.text[self.pos..].trim_start().len();
+    }
+
+    fn expect(&mut self, token: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.text[self.pos..].starts_with(token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' at column {}", token, self.pos + 1))
+        }
+    }
+
+    fn next_is(&mut self, token: char) -> bool {
+        self.skip_whitespace();
+        self.text[self.pos..].starts_with(token)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let rest = &self.text[self.pos..];
+        let end = rest.find('"').ok_or("Unterminated string")?;
+        self.pos += end + 1;
+        Ok(rest[..end].to_string())
+    }
+
+    fn list<T>(&mut self, close: char, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let mut items = Vec::new();
+        if self.next_is(close) {
+            self.pos += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.next_is(',') {
+                self.pos += 1;
+            } else {
+                self.expect(close)?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let rest = &self.text[self.pos..];
+        if rest.starts_with('"') {
+            self.string()?;
+            Ok(Json::String)
+        } else if rest.starts_with('[') {
+            self.pos += 1;
+            Ok(Json::Array(self.list(']', |p| p.value())?))
+        } else if rest.starts_with('{') {
+            self.pos += 1;
+            Ok(Json::Object(self.list('}', |p| {
+                let key = p.string()?;
+                p.expect(':')?;
+                Ok((key, p.value()?))
+            })?))
+        } else if rest.starts_with("null") {
+            self.pos += 4;
+            Ok(Json::Null)
+        } else {
+            let len = rest.find(|c: char| !(c.is_ascii_digit() || c == '-')).unwrap_or(rest.len());
+            if len == 0 { return Err(format!("Unexpected input at column {}", self.pos + 1)); }
+            self.pos += len;
+            Ok(Json::Number(rest[..len].to_string()))
+        }
+    }
+}
+
+fn json_number<T: std::str::FromStr>(value: &Json) -> Result<T, String> {
+    match value {
+        Json::Number(text) => text.parse().map_err(|_| format!("Invalid number {}", text)),
+        _ => Err(format!("Expected a number, found {:?}", value))
+    }
+}
+
+fn json_numbers<T: std::str::FromStr>(value: &Json) -> Result<Vec<T>, String> {
+    match value {
+        Json::Array(values) => values.iter().map(json_number).collect(),
+        _ => Err(format!("Expected an array, found {:?}", value))
+    }
+}
+
+fn json_record<C: Number>(line: &str) -> Result<TraceRecord<C>, String> {
+    let mut parser = JsonParser { text: line, pos: 0 };
+    let fields = match parser.value()? {
+        Json::Object(fields) => fields,
+        _ => return Err("Expected an object".to_string())
+    };
+    let mut record = TraceRecord::new(0);
+    for (key, value) in &fields {
+        match key.as_str() {
+            "step" => record.step = json_number(value)?,
+            "pc" => record.pc = json_number(value)?,
+            "instruction" => record.instruction = json_number(value)?,
+            "operands" => record.operands = json_numbers(value)?,
+            "writes" => record.writes = match value {
+                Json::Array(writes) => writes.iter().map(|w| match w {
+                    Json::Array(pair) if pair.len() == 2 => Ok((json_number(&pair[0])?, json_number(&pair[1])?)),
+                    _ => Err("A write is an [address, value] pair".to_string())
+                }).collect::<Result<_, String>>()?,
+                _ => return Err("Expected an array of writes".to_string())
+            },
+            "relative_base" => record.relative_base = json_number(value)?,
+            "input" => record.input = match value { Json::Null => None, v => Some(json_number(v)?) },
+            "output" => record.output = match value { Json::Null => None, v => Some(json_number(v)?) },
+            _ => {}
+        }
+    }
+    Ok(record)
+}
+
+/// Reads records written by JsonLines.
+pub fn read_json_lines<C: Number>(reader: impl BufRead) -> io::Result<Vec<TraceRecord<C>>> {
+    let mut records = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() { continue; }
+        let record = json_record(&line).map_err(|e| invalid_data(format!("line {}: {}", i + 1, e)))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn read_byte(reader: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match reader.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0]))
+    }
+}
+
+fn read_varint_from(reader: &mut impl Read, first: u8) -> io::Result<u64> {
+    let mut value = 0;
+    let mut byte = first;
+    let mut shift = 0;
+    loop {
+        if shift > 63 { return Err(invalid_data("Varint too long".to_string())); }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 { return Ok(value); }
+        shift += 7;
+        byte = read_byte(reader)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    }
+}
+
+fn read_varint(reader: &mut impl Read) -> io::Result<u64> {
+    let first = read_byte(reader)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    read_varint_from(reader, first)
+}
+
+fn read_cell<C: Number>(reader: &mut impl Read) -> io::Result<C> {
+    let value = read_varint(reader)?;
+    Ok(C::from_i64((value >> 1) as i64 ^ -((value & 1) as i64)))
+}
+
+fn read_cells<C: Number>(reader: &mut impl Read) -> io::Result<Vec<C>> {
+    let len = read_varint(reader)?;
+    (0..len).map(|_| read_cell(reader)).collect()
+}
+
+/// Reads records written by Binary, including the header.
+pub fn read_binary<C: Number>(mut reader: impl Read) -> io::Result<Vec<TraceRecord<C>>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != BINARY_MAGIC { return Err(invalid_data("Not a binary trace of a supported version".to_string())); }
+    let mut records = Vec::new();
+    while let Some(first) = read_byte(&mut reader)? {
+        let step = read_varint_from(&mut reader, first)?;
+        let pc = read_varint(&mut reader)? as usize;
+        let instruction = read_cell(&mut reader)?;
+        let operands = read_cells(&mut reader)?;
+        let writes = (0..read_varint(&mut reader)?)
+            .map(|_| Ok((read_varint(&mut reader)? as usize, read_cell(&mut reader)?)))
+            .collect::<io::Result<_>>()?;
+        let relative_base = read_cell(&mut reader)?;
+        let flags = read_byte(&mut reader)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        let input = if flags & HAS_INPUT != 0 { Some(read_cell(&mut reader)?) } else { None };
+        let output = if flags & HAS_OUTPUT != 0 { Some(read_cell(&mut reader)?) } else { None };
+        records.push(TraceRecord { step, pc, instruction, operands, writes, relative_base, input, output, extension: None });
+    }
+    Ok(records)
+}
+
+/// Reads a trace file in either format, telling them apart by the binary header.
+pub fn read_trace_file<C: Number>(file_name: &str) -> io::Result<Vec<TraceRecord<C>>> {
+    let mut reader = BufReader::new(File::open(file_name)?);
+    if reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+        read_binary(reader)
+    } else {
+        read_json_lines(reader)
+    }
+}
+
+/// Selects records from a trace. All conditions that are set must hold,
+/// `last` then keeps only the final matching records.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Filter {
+    pub pc: Option<usize>,
+    pub opcode: Option<Opcode>,
+    pub writes_to: Option<usize>,
+    pub io: bool,
+    pub last: Option<usize>
+}
+
+impl Filter {
+    pub fn matches<C: Number>(&self, record: &TraceRecord<C>) -> bool {
+        self.pc.is_none_or(|pc| record.pc == pc)
+            && self.opcode.is_none_or(|op| record.opcode() == Some(op))
+            && self.writes_to.is_none_or(|address| record.writes_to(address))
+            && (!self.io || record.input.is_some() || record.output.is_some())
+    }
+
+    pub fn apply<'a, C: Number>(&self, records: &'a [TraceRecord<C>]) -> Vec<&'a TraceRecord<C>> {
+        let matching: Vec<_> = records.iter().filter(|r| self.matches(r)).collect();
+        let skip = self.last.map_or(0, |last| matching.len().saturating_sub(last));
+        matching[skip..].to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads a value, outputs it doubled and stores it at 100
+    const DOUBLER: &str = "3,100,1002,100,2,100,4,100,99";
+
+    fn trace_doubler<S: TraceSink<Cell>>(sink: S) -> S {
+        let mut p = IntCode::string_to_program(DOUBLER).unwrap();
+        p.add_input(21);
+        let mut tracer = Tracer::new(sink);
+        assert_eq!(vec![42], tracer.run_program(&mut p).unwrap());
+        tracer.into_sink()
+    }
+
+    #[test]
+    fn should_record_operands_writes_and_io() {
+        let records: Vec<_> = trace_doubler(RingBuffer::new(10)).records().iter().cloned().collect();
+
+        assert_eq!(4, records.len());
+        assert_eq!(TraceRecord {
+            step: 0, pc: 0, instruction: 3, operands: vec![], writes: vec![(100, 21)],
+            relative_base: 0, input: Some(21), output: None, extension: None
+        }, records[0]);
+        assert_eq!(vec![21, 2], records[1].operands);
+        assert_eq!(vec![(100, 42)], records[1].writes);
+        assert_eq!(Some(42), records[2].output);
+        assert_eq!(Some(Opcode::Halt), records[3].opcode());
+    }
+
+    #[test]
+    fn ring_buffer_should_keep_the_last_records() {
+        let sink = trace_doubler(RingBuffer::new(2));
+        let steps: Vec<u64> = sink.records().iter().map(|r| r.step).collect();
+
+        assert_eq!(vec![2, 3], steps);
+    }
+
+    #[test]
+    fn waiting_for_input_should_not_be_recorded() {
+        let mut p = IntCode::string_to_program(DOUBLER).unwrap();
+        let mut tracer = Tracer::new(RingBuffer::new(10));
+
+        assert_eq!(IntCodeState::NeedInput, tracer.run_slice(&mut p).unwrap());
+        assert!(tracer.sink().records().is_empty());
+    }
+
+    #[test]
+    fn relative_base_should_be_recorded_after_the_instruction() {
+        let mut p = IntCode::string_to_program("109,19,204,-19,99").unwrap();
+        let mut tracer = Tracer::new(RingBuffer::new(10));
+        tracer.run_program(&mut p).unwrap();
+        let records = tracer.sink().records();
+
+        assert_eq!(19, records[0].relative_base);
+        assert_eq!(Some(109), records[1].output);
+    }
+
+    #[test]
+    fn peak_memory_should_be_tracked() {
+        let mut p = IntCode::string_to_program("21101,1,2,20,99").unwrap();
+        let mut tracer = Tracer::new(RingBuffer::new(10));
+        tracer.run_program(&mut p).unwrap();
+
+        assert_eq!(Some(1), tracer.peak_memory().map(|m| m.pages));
+    }
+
+    #[test]
+    fn json_lines_should_round_trip() {
+        let expected: Vec<_> = trace_doubler(RingBuffer::new(10)).records().iter().cloned().collect();
+        let json = trace_doubler(JsonLines::new(Vec::new())).into_inner();
+        let text = String::from_utf8(json).unwrap();
+
+        assert!(text.starts_with("{\"step\":0,\"pc\":0,\"instruction\":3,\"opcode\":\"INPUT\",\"operands\":[],\"writes\":[[100,21]],"));
+        assert_eq!(expected, read_json_lines::<Cell>(text.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn binary_should_round_trip() {
+        let expected: Vec<_> = trace_doubler(RingBuffer::new(10)).records().iter().cloned().collect();
+        let binary = trace_doubler(Binary::new(Vec::new()).unwrap()).into_inner();
+
+        assert!(binary.starts_with(BINARY_MAGIC));
+        assert_eq!(expected, read_binary::<Cell>(binary.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn binary_should_encode_negative_and_large_cells() {
+        let record = TraceRecord {
+            step: 1 << 40, pc: 7, instruction: 1101, operands: vec![i64::MIN, -1], writes: vec![(1000, i64::MAX)],
+            relative_base: -5, input: None, output: Some(-300), extension: None
+        };
+        let mut sink = Binary::new(Vec::new()).unwrap();
+        sink.record(&record).unwrap();
+
+        assert_eq!(vec![record], read_binary::<Cell>(sink.into_inner().as_slice()).unwrap());
+    }
+
+    #[test]
+    fn truncated_binary_should_be_an_error() {
+        let mut binary = trace_doubler(Binary::new(Vec::new()).unwrap()).into_inner();
+        binary.pop();
+
+        assert!(read_binary::<Cell>(binary.as_slice()).is_err());
+    }
+
+    #[test]
+    fn filter_should_select_writes_and_last_records() {
+        let records: Vec<_> = trace_doubler(RingBuffer::new(10)).records().iter().cloned().collect();
+        let writes = Filter { writes_to: Some(100), ..Filter::default() };
+        let last = Filter { last: Some(2), ..Filter::default() };
+        let io = Filter { io: true, ..Filter::default() };
+
+        assert_eq!(vec![0, 1], writes.apply(&records).iter().map(|r| r.step).collect::<Vec<_>>());
+        assert_eq!(vec![2, 3], last.apply(&records).iter().map(|r| r.step).collect::<Vec<_>>());
+        assert_eq!(vec![0, 2], io.apply(&records).iter().map(|r| r.step).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn display_should_show_one_line_per_record() {
+        let records = trace_doubler(RingBuffer::new(10));
+
+        assert_eq!("       1     2: MULTIPLY        (21, 2) [100] <- 42 rb 0", records.records()[1].to_string());
+    }
+}